@@ -3,8 +3,9 @@ use futures::TryStreamExt;
 use log::{info, warn};
 use rayhunter::{
     analysis::analyzer::{AnalyzerConfig, EventType, Harness},
-    diag::DataType,
+    diag::{DataType, LogBody, Message, ModemInfo},
     gsmtap_parser,
+    hdlc::HdlcFrameStats,
     pcap::GsmtapPcapWriter,
     qmdl::QmdlReader,
 };
@@ -53,12 +54,14 @@ async fn analyze_file(enable_dummy_analyzer: bool, qmdl_path: &str, show_skipped
     let mut total_messages = 0;
     let mut warnings = 0;
     let mut skipped = 0;
+    let mut frame_stats = HdlcFrameStats::default();
     while let Some(container) = qmdl_stream
         .try_next()
         .await
         .expect("failed getting QMDL container")
     {
-        let row = harness.analyze_qmdl_messages(container);
+        frame_stats += container.frame_stats;
+        let row = harness.analyze_qmdl_messages(&container);
         total_messages += 1;
         for reason in row.skipped_message_reasons {
             *skipped_reasons.entry(reason).or_insert(0) += 1;
@@ -94,6 +97,12 @@ async fn analyze_file(enable_dummy_analyzer: bool, qmdl_path: &str, show_skipped
     info!(
         "{qmdl_path}: {total_messages} messages analyzed, {warnings} warnings, {skipped} messages skipped"
     );
+    if !frame_stats.is_empty() {
+        info!(
+            "{qmdl_path}: {} HDLC frames reassembled, {} dropped",
+            frame_stats.reassembled, frame_stats.dropped
+        );
+    }
 }
 
 async fn pcapify(qmdl_path: &PathBuf) {
@@ -107,7 +116,9 @@ async fn pcapify(qmdl_path: &PathBuf) {
     let pcap_file = &mut File::create(&pcap_path)
         .await
         .expect("failed to open pcap file");
-    let mut pcap_writer = GsmtapPcapWriter::new(pcap_file).await.unwrap();
+    let mut pcap_writer = GsmtapPcapWriter::new(pcap_file, &ModemInfo::default())
+        .await
+        .unwrap();
     pcap_writer.write_iface_header().await.unwrap();
     while let Some(container) = qmdl_reader
         .get_next_messages_container()
@@ -115,7 +126,17 @@ async fn pcapify(qmdl_path: &PathBuf) {
         .expect("failed to get container")
     {
         for msg in container.into_messages().into_iter().flatten() {
-            if let Ok(Some((timestamp, parsed))) = gsmtap_parser::parse(msg) {
+            if let Message::Log {
+                timestamp,
+                body: LogBody::IpTraffic { msg, .. },
+                ..
+            } = msg
+            {
+                pcap_writer
+                    .write_ip_packet(msg, timestamp)
+                    .await
+                    .expect("failed to write");
+            } else if let Ok(Some((timestamp, parsed))) = gsmtap_parser::parse(msg) {
                 pcap_writer
                     .write_gsmtap_message(parsed, timestamp)
                     .await
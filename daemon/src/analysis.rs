@@ -1,4 +1,6 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
 use std::{future, pin};
 
 use axum::Json;
@@ -7,17 +9,19 @@ use axum::{
     http::StatusCode,
 };
 use futures::TryStreamExt;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use rayhunter::analysis::analyzer::{AnalyzerConfig, Harness};
 use rayhunter::diag::{DataType, MessagesContainer};
 use rayhunter::qmdl::QmdlReader;
 use serde::Serialize;
 use tokio::fs::File;
 use tokio::io::{AsyncWriteExt, BufWriter};
-use tokio::sync::mpsc::Receiver;
+use tokio::sync::mpsc::{Receiver, Sender, UnboundedReceiver, UnboundedSender};
 use tokio::sync::{RwLock, RwLockWriteGuard};
+use tokio::time::Instant;
 use tokio_util::task::TaskTracker;
 
+use crate::display;
 use crate::dummy_analyzer::TestAnalyzer;
 use crate::qmdl_store::RecordingStore;
 use crate::server::ServerState;
@@ -64,7 +68,7 @@ impl AnalysisWriter {
     // to the analysis file and returning the file's new length.
     pub async fn analyze(
         &mut self,
-        container: MessagesContainer,
+        container: &MessagesContainer,
     ) -> Result<(usize, bool), std::io::Error> {
         let row = self.harness.analyze_qmdl_messages(container);
         if !row.is_empty() {
@@ -89,6 +93,246 @@ impl AnalysisWriter {
     }
 }
 
+/// How many containers the diag thread can get ahead of live analysis before
+/// we give up on analyzing the recording as it's captured
+pub const LIVE_ANALYSIS_QUEUE_SIZE: usize = 128;
+
+pub enum LiveAnalysisMessage {
+    /// A new recording has started, whose analysis goes in this file
+    Start { name: String, analysis_file: File },
+    Container {
+        container: MessagesContainer,
+        read_at: Instant,
+    },
+    /// The current recording has ended. If it's incomplete, some of its
+    /// containers didn't fit in the queue, so it gets analyzed again from its
+    /// QMDL file instead.
+    Finish { complete: bool },
+}
+
+/// Tracks how far live analysis has fallen behind capture, for
+/// /api/system-stats
+#[derive(Default)]
+pub struct LiveAnalysisMetrics {
+    queue_depth: AtomicUsize,
+    lag_ms: AtomicU64,
+    max_lag_ms: AtomicU64,
+    dropped_containers: AtomicU64,
+    deferred_recordings: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LiveAnalysisStats {
+    pub queue_depth: usize,
+    pub queue_capacity: usize,
+    /// How long the most recently analyzed container spent in the queue
+    pub lag_ms: u64,
+    pub max_lag_ms: u64,
+    pub dropped_containers: u64,
+    pub deferred_recordings: u64,
+}
+
+impl LiveAnalysisMetrics {
+    pub fn stats(&self) -> LiveAnalysisStats {
+        LiveAnalysisStats {
+            queue_depth: self.queue_depth.load(Ordering::Relaxed),
+            queue_capacity: LIVE_ANALYSIS_QUEUE_SIZE,
+            lag_ms: self.lag_ms.load(Ordering::Relaxed),
+            max_lag_ms: self.max_lag_ms.load(Ordering::Relaxed),
+            dropped_containers: self.dropped_containers.load(Ordering::Relaxed),
+            deferred_recordings: self.deferred_recordings.load(Ordering::Relaxed),
+        }
+    }
+
+    fn record_lag(&self, lag: Duration) {
+        let lag_ms = lag.as_millis() as u64;
+        self.lag_ms.store(lag_ms, Ordering::Relaxed);
+        self.max_lag_ms.fetch_max(lag_ms, Ordering::Relaxed);
+    }
+}
+
+/// The diag thread's end of the live analysis queue. Nothing here waits, so a
+/// slow analysis never holds up reads from the diag device. Once more than
+/// `capacity` containers are queued, the rest of the recording is skipped and
+/// it's re-analyzed from disk when it ends. Starts and finishes can't be
+/// skipped, so the channel itself is unbounded and only containers count
+/// towards the capacity.
+pub struct LiveAnalysisSender {
+    tx: UnboundedSender<LiveAnalysisMessage>,
+    capacity: usize,
+    metrics: Arc<LiveAnalysisMetrics>,
+    overflowed: bool,
+}
+
+impl LiveAnalysisSender {
+    pub fn new(
+        tx: UnboundedSender<LiveAnalysisMessage>,
+        capacity: usize,
+        metrics: Arc<LiveAnalysisMetrics>,
+    ) -> Self {
+        Self {
+            tx,
+            capacity,
+            metrics,
+            overflowed: false,
+        }
+    }
+
+    pub fn start(&mut self, name: String, analysis_file: File) {
+        self.overflowed = false;
+        let msg = LiveAnalysisMessage::Start {
+            name,
+            analysis_file,
+        };
+        if let Err(e) = self.tx.send(msg) {
+            warn!("couldn't start live analysis: {e}");
+        }
+    }
+
+    pub fn analyze(&mut self, container: MessagesContainer) {
+        if self.overflowed {
+            self.metrics
+                .dropped_containers
+                .fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        // count it before sending, so the worker never sees the depth go
+        // below zero
+        if self.metrics.queue_depth.fetch_add(1, Ordering::Relaxed) >= self.capacity {
+            warn!("live analysis queue is full, deferring analysis until the recording ends");
+            self.metrics.queue_depth.fetch_sub(1, Ordering::Relaxed);
+            self.metrics
+                .dropped_containers
+                .fetch_add(1, Ordering::Relaxed);
+            self.overflowed = true;
+            return;
+        }
+        let msg = LiveAnalysisMessage::Container {
+            container,
+            read_at: Instant::now(),
+        };
+        if self.tx.send(msg).is_err() {
+            self.metrics.queue_depth.fetch_sub(1, Ordering::Relaxed);
+            warn!("live analysis thread has exited");
+        }
+    }
+
+    pub fn finish(&mut self) {
+        let msg = LiveAnalysisMessage::Finish {
+            complete: !self.overflowed,
+        };
+        if let Err(e) = self.tx.send(msg) {
+            warn!("couldn't finish live analysis: {e}");
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run_live_analysis_thread(
+    task_tracker: &TaskTracker,
+    mut live_analysis_rx: UnboundedReceiver<LiveAnalysisMessage>,
+    qmdl_store_lock: Arc<RwLock<RecordingStore>>,
+    analysis_status_lock: Arc<RwLock<AnalysisStatus>>,
+    analysis_sender: Sender<AnalysisCtrlMessage>,
+    ui_update_sender: Sender<display::DisplayState>,
+    metrics: Arc<LiveAnalysisMetrics>,
+    enable_dummy_analyzer: bool,
+    analyzer_config: AnalyzerConfig,
+) {
+    task_tracker.spawn(async move {
+        let mut current: Option<(String, AnalysisWriter)> = None;
+        // runs until the diag thread exits and drops its sender
+        while let Some(msg) = live_analysis_rx.recv().await {
+            match msg {
+                LiveAnalysisMessage::Start {
+                    name,
+                    analysis_file,
+                } => {
+                    if let Some((_, analysis_writer)) = current.take() {
+                        close_analysis_writer(analysis_writer).await;
+                    }
+                    match AnalysisWriter::new(
+                        analysis_file,
+                        enable_dummy_analyzer,
+                        &analyzer_config,
+                    )
+                    .await
+                    {
+                        Ok(analysis_writer) => current = Some((name, analysis_writer)),
+                        Err(e) => error!("failed to create analysis writer for {name}: {e}"),
+                    }
+                }
+                LiveAnalysisMessage::Container { container, read_at } => {
+                    metrics.queue_depth.fetch_sub(1, Ordering::Relaxed);
+                    let Some((name, analysis_writer)) = current.as_mut() else {
+                        continue;
+                    };
+                    let (analysis_file_len, heuristic_warning) =
+                        match analysis_writer.analyze(&container).await {
+                            Ok(output) => output,
+                            Err(e) => {
+                                error!("failed to analyze container: {e}");
+                                continue;
+                            }
+                        };
+                    metrics.record_lag(read_at.elapsed());
+                    if heuristic_warning {
+                        info!("a heuristic triggered on this run!");
+                        if let Err(e) = ui_update_sender
+                            .send(display::DisplayState::WarningDetected)
+                            .await
+                        {
+                            warn!("couldn't send ui update message: {e}");
+                        }
+                    }
+                    let mut qmdl_store = qmdl_store_lock.write().await;
+                    if let Some((index, _)) = qmdl_store.entry_for_name(name)
+                        && let Err(e) = qmdl_store
+                            .update_entry_analysis_size(index, analysis_file_len)
+                            .await
+                    {
+                        error!("failed to update analysis file size: {e}");
+                    }
+                }
+                LiveAnalysisMessage::Finish { complete } => {
+                    let Some((name, analysis_writer)) = current.take() else {
+                        continue;
+                    };
+                    close_analysis_writer(analysis_writer).await;
+                    if complete {
+                        if let Err(e) = analysis_sender
+                            .send(AnalysisCtrlMessage::RecordingFinished(name))
+                            .await
+                        {
+                            warn!("couldn't send analysis message: {e}");
+                        }
+                        continue;
+                    }
+                    info!("queueing {name} for re-analysis, since live analysis fell behind");
+                    metrics.deferred_recordings.fetch_add(1, Ordering::Relaxed);
+                    let queued = queue_qmdl(&name, &mut analysis_status_lock.write().await);
+                    if queued
+                        && let Err(e) = analysis_sender
+                            .send(AnalysisCtrlMessage::NewFilesQueued)
+                            .await
+                    {
+                        warn!("couldn't send analysis message: {e}");
+                    }
+                }
+            }
+        }
+        if let Some((_, analysis_writer)) = current {
+            close_analysis_writer(analysis_writer).await;
+        }
+    });
+}
+
+async fn close_analysis_writer(analysis_writer: AnalysisWriter) {
+    if let Err(e) = analysis_writer.close().await {
+        error!("failed to close analysis writer: {e}");
+    }
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct AnalysisStatus {
     queued: Vec<String>,
@@ -183,7 +427,7 @@ async fn perform_analysis(
         .expect("failed getting QMDL container")
     {
         let (size_bytes, _) = analysis_writer
-            .analyze(container)
+            .analyze(&container)
             .await
             .map_err(|e| format!("{e:?}"))?;
         debug!("{name} analysis: {size_bytes} bytes written");
@@ -293,3 +537,85 @@ pub async fn start_analysis(
     }
     Ok((StatusCode::ACCEPTED, Json(analysis_status.clone())))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    fn make_container() -> MessagesContainer {
+        MessagesContainer {
+            data_type: DataType::UserSpace,
+            num_messages: 0,
+            messages: Vec::new(),
+            frame_stats: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_live_analysis_overflow() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let metrics = Arc::new(LiveAnalysisMetrics::default());
+        let mut sender = LiveAnalysisSender::new(tx, 2, metrics.clone());
+
+        sender.analyze(make_container());
+        sender.analyze(make_container());
+        assert_eq!(metrics.stats().queue_depth, 2);
+        assert_eq!(metrics.stats().dropped_containers, 0);
+
+        // once the queue's full, the rest of the recording is dropped, even
+        // if there's room again
+        sender.analyze(make_container());
+        assert!(matches!(
+            rx.recv().await,
+            Some(LiveAnalysisMessage::Container { .. })
+        ));
+        sender.analyze(make_container());
+        let stats = metrics.stats();
+        assert_eq!(stats.queue_depth, 2);
+        assert_eq!(stats.dropped_containers, 2);
+
+        assert!(matches!(
+            rx.recv().await,
+            Some(LiveAnalysisMessage::Container { .. })
+        ));
+        sender.finish();
+        assert!(matches!(
+            rx.recv().await,
+            Some(LiveAnalysisMessage::Finish { complete: false })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_live_analysis_finish_with_full_queue() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let metrics = Arc::new(LiveAnalysisMetrics::default());
+        let mut sender = LiveAnalysisSender::new(tx, 1, metrics.clone());
+
+        // finishing and starting a recording mustn't wait for the worker to
+        // make room in a full queue
+        sender.analyze(make_container());
+        sender.analyze(make_container());
+        sender.finish();
+        let analysis_file = File::from_std(tempfile::tempfile().unwrap());
+        sender.start("next".to_string(), analysis_file);
+        sender.analyze(make_container());
+
+        assert!(matches!(
+            rx.recv().await,
+            Some(LiveAnalysisMessage::Container { .. })
+        ));
+        assert!(matches!(
+            rx.recv().await,
+            Some(LiveAnalysisMessage::Finish { complete: false })
+        ));
+        assert!(matches!(
+            rx.recv().await,
+            Some(LiveAnalysisMessage::Start { .. })
+        ));
+        // the new recording's container is dropped too, as the worker hasn't
+        // yet made room for it
+        assert_eq!(metrics.stats().dropped_containers, 2);
+        assert!(rx.try_recv().is_err());
+    }
+}
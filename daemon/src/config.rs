@@ -1,6 +1,11 @@
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 
 use rayhunter::analysis::analyzer::AnalyzerConfig;
+use rayhunter::diag_device::LogMaskConfig;
+use rayhunter::log_codes;
+use rayhunter::qmdl::ReplaySpeed;
 
 use crate::error::RayhunterError;
 
@@ -14,7 +19,25 @@ pub struct Config {
     pub enable_dummy_analyzer: bool,
     pub colorblind_mode: bool,
     pub key_input_mode: u8,
+    /// Whether to capture user-plane IP traffic alongside signalling. When
+    /// disabled, IP packets aren't logged by the modem or written to pcaps.
+    pub capture_user_plane: bool,
+    /// Play back this QMDL file instead of reading from the diag device, so
+    /// the daemon can run without a modem (e.g. on a laptop)
+    pub replay_qmdl_path: Option<String>,
+    /// Talk diag over this serial tty (e.g. /dev/ttyUSB0 for a phone or USB
+    /// modem attached to a laptop) instead of /dev/diag
+    pub serial_device_path: Option<String>,
+    /// How fast to play back `replay_qmdl_path`: 1.0 for the original
+    /// timing, higher values to speed it up, or 0 for as fast as possible
+    pub replay_speed: f64,
+    /// Reconnect to the diag device if it sends nothing for this many seconds
+    /// while recording. 0 disables stall detection.
+    pub diag_stall_timeout_secs: u64,
     pub analyzers: AnalyzerConfig,
+    /// Which groups of diag log codes the modem should send us, plus any
+    /// extra log codes to enable
+    pub log_mask: LogMaskConfig,
 }
 
 impl Default for Config {
@@ -27,7 +50,42 @@ impl Default for Config {
             enable_dummy_analyzer: false,
             colorblind_mode: false,
             key_input_mode: 0,
+            capture_user_plane: true,
+            replay_qmdl_path: None,
+            serial_device_path: None,
+            replay_speed: 1.0,
+            diag_stall_timeout_secs: 120,
             analyzers: AnalyzerConfig::default(),
+            log_mask: LogMaskConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn diag_stall_timeout(&self) -> Option<Duration> {
+        match self.diag_stall_timeout_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    /// The log codes to enable for `log_mask`. User-plane IP traffic is left
+    /// out unless `capture_user_plane` is set.
+    pub fn diag_log_codes(&self, log_mask: &LogMaskConfig) -> Vec<u32> {
+        let mut codes = log_mask.log_codes();
+        if !self.capture_user_plane {
+            codes.retain(|&code| code != log_codes::LOG_DATA_PROTOCOL_LOGGING_C);
+        }
+        codes
+    }
+
+    pub fn replay_speed(&self) -> ReplaySpeed {
+        if self.replay_speed <= 0.0 {
+            ReplaySpeed::AsFastAsPossible
+        } else if self.replay_speed == 1.0 {
+            ReplaySpeed::Original
+        } else {
+            ReplaySpeed::Accelerated(self.replay_speed)
         }
     }
 }
@@ -57,3 +115,50 @@ pub fn parse_args() -> Args {
         config_path: args[1].clone(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rayhunter::diag_device::LogCodeGroup;
+
+    #[test]
+    fn test_log_mask_from_toml() {
+        let config: Config = toml::from_str(
+            r#"
+            capture_user_plane = false
+
+            [log_mask]
+            groups = ["l3_signalling", "nas", "ip_traffic"]
+            extra_log_codes = [0xb0c3]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.log_mask.groups,
+            vec![
+                LogCodeGroup::L3Signalling,
+                LogCodeGroup::Nas,
+                LogCodeGroup::IpTraffic
+            ]
+        );
+
+        let codes = config.diag_log_codes(&config.log_mask);
+        assert!(codes.contains(&log_codes::LOG_LTE_RRC_OTA_MSG_LOG_C));
+        assert!(codes.contains(&log_codes::LOG_LTE_NAS_EMM_OTA_IN_MSG_LOG_C));
+        assert!(codes.contains(&log_codes::LOG_LTE_RRC_STATE));
+        assert!(!codes.contains(&log_codes::LOG_LTE_ML1_SERVING_CELL_INFO));
+        // left out since capture_user_plane is off
+        assert!(!codes.contains(&log_codes::LOG_DATA_PROTOCOL_LOGGING_C));
+    }
+
+    #[test]
+    fn test_default_log_mask_enables_every_group() {
+        let config = Config::default();
+        let codes = config.diag_log_codes(&config.log_mask);
+        for group in LogCodeGroup::ALL {
+            for code in group.log_codes() {
+                assert!(codes.contains(code), "{group:?} code {code:#x} missing");
+            }
+        }
+    }
+}
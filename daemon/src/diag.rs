@@ -1,24 +1,28 @@
-use std::pin::pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::body::Body;
+use axum::Json;
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
 use axum::http::header::CONTENT_TYPE;
 use axum::response::{IntoResponse, Response};
-use futures::{StreamExt, TryStreamExt};
+use futures::future::Either;
+use futures::{Stream, StreamExt, TryStreamExt};
 use log::{debug, error, info, warn};
-use rayhunter::analysis::analyzer::AnalyzerConfig;
 use rayhunter::diag::DataType;
-use rayhunter::diag_device::DiagDevice;
-use rayhunter::qmdl::QmdlWriter;
+use rayhunter::diag::MessagesContainer;
+use rayhunter::diag_device::{DiagDevice, DiagDeviceError, LogMaskConfig};
+use rayhunter::diag_serial::SerialTransport;
+use rayhunter::qmdl::{QmdlReplay, QmdlWriter};
 use tokio::fs::File;
 use tokio::sync::RwLock;
 use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::time::Instant;
 use tokio_util::io::ReaderStream;
 use tokio_util::task::TaskTracker;
 
-use crate::analysis::{AnalysisCtrlMessage, AnalysisWriter};
+use crate::analysis::LiveAnalysisSender;
 use crate::display;
 use crate::gps::{GpsCoordinate, GpsWriter};
 use crate::qmdl_store::{RecordingStore, RecordingStoreError};
@@ -27,28 +31,181 @@ use crate::server::ServerState;
 pub enum DiagDeviceCtrlMessage {
     StopRecording,
     StartRecording,
+    /// Re-sends the log mask to the modem with these log codes enabled
+    SetLogMask {
+        log_codes: Vec<u32>,
+        event_reports: bool,
+        f3_messages: bool,
+    },
     Exit,
 }
 
+// how long each attempt at re-opening the diag device keeps trying for
+const DIAG_RECONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// how long to wait between failed reconnection attempts, doubling each time
+// up to the max
+const DIAG_RECONNECT_MIN_BACKOFF: Duration = Duration::from_secs(5);
+const DIAG_RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Where the diag read thread gets its messages from
+pub enum DiagSource {
+    Device(DiagDevice),
+    Serial {
+        dev: DiagDevice<SerialTransport>,
+        path: String,
+    },
+    Replay(QmdlReplay<File>),
+    /// A device which has been closed to reconnect to it, but couldn't be
+    /// opened again yet
+    Disconnected(DisconnectedSource),
+}
+
+/// What's needed to open a disconnected diag device again
+pub struct DisconnectedSource {
+    // None for /dev/diag
+    serial_path: Option<String>,
+    log_codes: Vec<u32>,
+    event_reports: bool,
+    f3_messages: bool,
+}
+
+impl DiagSource {
+    fn as_stream(
+        &mut self,
+    ) -> impl Stream<Item = Result<MessagesContainer, DiagDeviceError>> + '_ {
+        match self {
+            DiagSource::Device(dev) => Either::Left(Either::Left(dev.as_stream().into_stream())),
+            DiagSource::Serial { dev, .. } => {
+                Either::Left(Either::Right(dev.as_stream().into_stream()))
+            }
+            DiagSource::Replay(replay) => Either::Right(Either::Left(
+                replay
+                    .as_stream()
+                    .map_err(DiagDeviceError::ReplayReadFailed)
+                    .into_stream(),
+            )),
+            DiagSource::Disconnected(_) => Either::Right(Either::Right(futures::stream::pending())),
+        }
+    }
+
+    async fn set_log_mask(
+        &mut self,
+        log_codes: Vec<u32>,
+        event_reports: bool,
+        f3_messages: bool,
+    ) -> Result<(), DiagDeviceError> {
+        match self {
+            DiagSource::Device(dev) => {
+                dev.set_log_codes(log_codes);
+                dev.set_event_reports(event_reports);
+                dev.set_f3_messages(f3_messages);
+                dev.config_logs().await
+            }
+            DiagSource::Serial { dev, .. } => {
+                dev.set_log_codes(log_codes);
+                dev.set_event_reports(event_reports);
+                dev.set_f3_messages(f3_messages);
+                dev.config_logs().await
+            }
+            DiagSource::Replay(_) => {
+                warn!("can't change the log mask of a QMDL replay, ignoring");
+                Ok(())
+            }
+            DiagSource::Disconnected(disconnected) => {
+                info!("diag device is disconnected, log mask will be set once it's reconnected");
+                disconnected.log_codes = log_codes;
+                disconnected.event_reports = event_reports;
+                disconnected.f3_messages = f3_messages;
+                Ok(())
+            }
+        }
+    }
+
+    fn can_reconnect(&self) -> bool {
+        !matches!(self, DiagSource::Replay(_))
+    }
+
+    /// Closes the diag device after it's errored out or stalled, keeping
+    /// hold of what's needed to reconnect to it
+    fn disconnect(&mut self) {
+        let disconnected = match self {
+            DiagSource::Device(dev) => DisconnectedSource {
+                serial_path: None,
+                log_codes: dev.log_codes().to_vec(),
+                event_reports: dev.event_reports(),
+                f3_messages: dev.f3_messages(),
+            },
+            DiagSource::Serial { dev, path } => DisconnectedSource {
+                serial_path: Some(path.clone()),
+                log_codes: dev.log_codes().to_vec(),
+                event_reports: dev.event_reports(),
+                f3_messages: dev.f3_messages(),
+            },
+            DiagSource::Replay(_) | DiagSource::Disconnected(_) => return,
+        };
+        // /dev/diag can't be opened again until the old file is closed
+        *self = DiagSource::Disconnected(disconnected);
+    }
+
+    /// Re-opens a disconnected diag device and re-applies its log mask. On
+    /// failure, it stays disconnected.
+    async fn reconnect(&mut self) -> Result<(), DiagDeviceError> {
+        let DiagSource::Disconnected(disconnected) = self else {
+            return Ok(());
+        };
+        *self = match &disconnected.serial_path {
+            None => {
+                let mut dev = DiagDevice::new_with_retries(DIAG_RECONNECT_TIMEOUT).await?;
+                dev.set_log_codes(disconnected.log_codes.clone());
+                dev.set_event_reports(disconnected.event_reports);
+                dev.set_f3_messages(disconnected.f3_messages);
+                dev.config_logs().await?;
+                DiagSource::Device(dev)
+            }
+            Some(path) => {
+                let mut dev = DiagDevice::with_transport(SerialTransport::open(path).await?);
+                dev.set_log_codes(disconnected.log_codes.clone());
+                dev.set_event_reports(disconnected.event_reports);
+                dev.set_f3_messages(disconnected.f3_messages);
+                dev.config_logs().await?;
+                DiagSource::Serial {
+                    dev,
+                    path: path.clone(),
+                }
+            }
+        };
+        Ok(())
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn run_diag_read_thread(
     task_tracker: &TaskTracker,
-    mut dev: DiagDevice,
+    mut source: DiagSource,
     mut qmdl_file_rx: Receiver<DiagDeviceCtrlMessage>,
     mut gps_rx: Receiver<GpsCoordinate>,
     ui_update_sender: Sender<display::DisplayState>,
     qmdl_store_lock: Arc<RwLock<RecordingStore>>,
-    analysis_sender: Sender<AnalysisCtrlMessage>,
-    enable_dummy_analyzer: bool,
-    analyzer_config: AnalyzerConfig,
+    mut live_analysis: LiveAnalysisSender,
+    stall_timeout: Option<Duration>,
 ) {
     task_tracker.spawn(async move {
         let (initial_qmdl_file, initial_analysis_file) = qmdl_store_lock.write().await.new_entry().await.expect("failed creating QMDL file entry");
         let mut maybe_qmdl_writer: Option<QmdlWriter<File>> = Some(QmdlWriter::new(initial_qmdl_file));
+        let initial_entry_name = qmdl_store_lock.read().await.get_current_entry()
+            .map(|(_, entry)| entry.name.clone())
+            .expect("QmdlStore didn't have current entry after creating it");
+        live_analysis.start(initial_entry_name, initial_analysis_file);
         let mut maybe_gps_writer: Option<GpsWriter> = None;
-        let mut diag_stream = pin!(dev.as_stream().into_stream());
-        let mut maybe_analysis_writer = Some(AnalysisWriter::new(initial_analysis_file, enable_dummy_analyzer, &analyzer_config).await
-            .expect("failed to create analysis writer"));
+        let can_reconnect = source.can_reconnect();
+        let mut diag_stream = Box::pin(source.as_stream());
+        let mut diag_stream_ended = false;
+        let mut last_container_at = Instant::now();
+        // while disconnected, when to next try reconnecting and how long to
+        // wait after that if it fails
+        let mut disconnected = false;
+        let mut reconnect_at = Instant::now();
+        let mut reconnect_backoff = DIAG_RECONNECT_MIN_BACKOFF;
         
         // Create initial GPS writer
         {
@@ -66,6 +223,7 @@ pub fn run_diag_read_thread(
             }
         }
         loop {
+            let mut needs_reconnect = false;
             tokio::select! {
                 msg = qmdl_file_rx.recv() => {
                     match msg {
@@ -80,6 +238,7 @@ pub fn run_diag_read_thread(
                             };
 
                             maybe_qmdl_writer = Some(QmdlWriter::new(qmdl_file));
+                            last_container_at = Instant::now();
 
                             // Close previous GPS writer
                             if let Some(gps_writer) = maybe_gps_writer.take() {
@@ -101,29 +260,31 @@ pub fn run_diag_read_thread(
                                 }
                             }
 
-                            if let Some(analysis_writer) = maybe_analysis_writer {
-                                analysis_writer.close().await.expect("failed to close analysis writer");
+                            // the live analysis worker takes this lock too, so
+                            // let go of it before waiting on the queue
+                            let new_entry_name = qmdl_store.get_current_entry()
+                                .map(|(_, entry)| entry.name.clone());
+                            drop(qmdl_store);
+                            // finishing is a no-op if we'd already stopped
+                            // recording
+                            live_analysis.finish();
+                            if let Some(name) = new_entry_name {
+                                live_analysis.start(name, new_analysis_file);
                             }
 
-                            maybe_analysis_writer = Some(AnalysisWriter::new(new_analysis_file, enable_dummy_analyzer, &analyzer_config).await
-                                .expect("failed to write to analysis file"));
-
-                            if let Err(e) = ui_update_sender.send(display::DisplayState::Recording).await {
+                            // we're not actually capturing anything until
+                            // the diag device is back
+                            let state = if disconnected {
+                                display::DisplayState::CaptureDegraded
+                            } else {
+                                display::DisplayState::Recording
+                            };
+                            if let Err(e) = ui_update_sender.send(state).await {
                                 warn!("couldn't send ui update message: {e}");
                             }
                         },
                         Some(DiagDeviceCtrlMessage::StopRecording) => {
-                            let mut qmdl_store = qmdl_store_lock.write().await;
-                            if let Some((_, entry)) = qmdl_store.get_current_entry() {
-                                    if let Err(e) = analysis_sender
-                                    .send(AnalysisCtrlMessage::RecordingFinished(
-                                            entry.name.to_string(),
-                                    ))
-                                    .await {
-                                        warn!("couldn't send analysis message: {e}");
-                                    }
-                            }
-                            if let Err(e) = qmdl_store.close_current_entry().await {
+                            if let Err(e) = qmdl_store_lock.write().await.close_current_entry().await {
                                 error!("couldn't close current entry: {e}");
                             }
 
@@ -138,15 +299,22 @@ pub fn run_diag_read_thread(
                                 }
                             }
                             
-                            if let Some(analysis_writer) = maybe_analysis_writer {
-                                analysis_writer.close().await.expect("failed to close analysis writer");
-                            }
-                            maybe_analysis_writer = None;
+                            live_analysis.finish();
 
                             if let Err(e) = ui_update_sender.send(display::DisplayState::Paused).await {
                                 warn!("couldn't send ui update message: {e}");
                             }
                         },
+                        Some(DiagDeviceCtrlMessage::SetLogMask { log_codes, event_reports, f3_messages }) => {
+                            // the stream borrows the source, so let go of it
+                            // while we talk to the modem
+                            drop(diag_stream);
+                            match source.set_log_mask(log_codes, event_reports, f3_messages).await {
+                                Ok(()) => info!("diag log mask updated"),
+                                Err(e) => error!("couldn't update diag log mask: {e}"),
+                            }
+                            diag_stream = Box::pin(source.as_stream());
+                        },
                         // None means all the Senders have been dropped, so it's
                         // time to go
                         Some(DiagDeviceCtrlMessage::Exit) | None => {
@@ -158,10 +326,10 @@ pub fn run_diag_read_thread(
                                     warn!("Failed to close GPS writer on exit: {}", e);
                                 }
                             }
-                            
-                            if let Some(analysis_writer) = maybe_analysis_writer {
-                                analysis_writer.close().await.expect("failed to close analysis writer");
-                            }
+
+                            // dropping the live analysis sender lets the
+                            // worker drain its queue and close the analysis
+                            // file
                             return Ok(())
                         },
                     }
@@ -179,9 +347,17 @@ pub fn run_diag_read_thread(
                         }
                     }
                 }
-                maybe_container = diag_stream.next() => {
-                    match maybe_container.unwrap() {
-                        Ok(container) => {
+                maybe_container = diag_stream.next(), if !diag_stream_ended => {
+                    match maybe_container {
+                        // only a replay ever runs out of messages. Keep
+                        // serving the recording and UI until we're told to
+                        // exit.
+                        None => {
+                            info!("diag source finished, no more messages to read");
+                            diag_stream_ended = true;
+                        }
+                        Some(Ok(container)) => {
+                            last_container_at = Instant::now();
                             if container.data_type != DataType::UserSpace {
                                 debug!("skipping non-userspace diag messages...");
                                 continue;
@@ -195,32 +371,73 @@ pub fn run_diag_read_thread(
                                 let index = qmdl_store.current_entry.expect("DiagDevice had qmdl_writer, but QmdlStore didn't have current entry???");
                                 qmdl_store.update_entry_qmdl_size(index, qmdl_writer.total_written).await
                                     .expect("failed to update qmdl file size");
+                                if !container.frame_stats.is_empty() {
+                                    qmdl_store.update_entry_frame_stats(index, container.frame_stats).await
+                                        .expect("failed to update HDLC frame counts");
+                                }
                                 debug!("done!");
+                                // analysis happens off the read loop, so it
+                                // can't hold up reading from the diag device
+                                live_analysis.analyze(container);
                             } else {
                                 debug!("no qmdl_writer set, continuing...");
                             }
-
-                            if let Some(analysis_writer) = maybe_analysis_writer.as_mut() {
-                                let analysis_output = analysis_writer.analyze(container).await
-                                    .expect("failed to analyze container");
-                                let (analysis_file_len, heuristic_warning) = analysis_output;
-                                if heuristic_warning {
-                                    info!("a heuristic triggered on this run!");
-                                    ui_update_sender.send(display::DisplayState::WarningDetected).await
-                                        .expect("couldn't send ui update message: {}");
-                                }
-                                let mut qmdl_store = qmdl_store_lock.write().await;
-                                let index = qmdl_store.current_entry.expect("DiagDevice had qmdl_writer, but QmdlStore didn't have current entry???");
-                                qmdl_store.update_entry_analysis_size(index, analysis_file_len).await
-                                    .expect("failed to update analysis file size");
-                            }
                         },
-                        Err(err) => {
+                        Some(Err(err)) => {
                             error!("error reading diag device: {err}");
-                            return Err(err);
+                            if !can_reconnect {
+                                return Err(err);
+                            }
+                            needs_reconnect = true;
                         }
                     }
                 }
+                // only a stall if we're expecting something to record
+                _ = tokio::time::sleep_until(last_container_at + stall_timeout.unwrap_or_default()),
+                    if stall_timeout.is_some() && can_reconnect && !disconnected && maybe_qmdl_writer.is_some() => {
+                    warn!("no diag messages for {:?}, assuming the device is stuck", last_container_at.elapsed());
+                    needs_reconnect = true;
+                }
+                // keep trying until we get the device back or are told to
+                // exit. The current recording, if any, carries on, so it
+                // can still be stopped as usual in the meantime.
+                _ = tokio::time::sleep_until(reconnect_at), if disconnected => {
+                    info!("reconnecting to diag device...");
+                    drop(diag_stream);
+                    match source.reconnect().await {
+                        Ok(()) => {
+                            info!("reconnected to diag device");
+                            disconnected = false;
+                            reconnect_backoff = DIAG_RECONNECT_MIN_BACKOFF;
+                            last_container_at = Instant::now();
+                            let state = if maybe_qmdl_writer.is_some() {
+                                display::DisplayState::Recording
+                            } else {
+                                display::DisplayState::Paused
+                            };
+                            if let Err(e) = ui_update_sender.send(state).await {
+                                warn!("couldn't send ui update message: {e}");
+                            }
+                        }
+                        Err(e) => {
+                            error!("couldn't reconnect to diag device, retrying in {reconnect_backoff:?}: {e}");
+                            reconnect_at = Instant::now() + reconnect_backoff;
+                            reconnect_backoff = (reconnect_backoff * 2).min(DIAG_RECONNECT_MAX_BACKOFF);
+                        }
+                    }
+                    diag_stream = Box::pin(source.as_stream());
+                }
+            }
+
+            if needs_reconnect {
+                if let Err(e) = ui_update_sender.send(display::DisplayState::CaptureDegraded).await {
+                    warn!("couldn't send ui update message: {e}");
+                }
+                drop(diag_stream);
+                source.disconnect();
+                diag_stream = Box::pin(source.as_stream());
+                disconnected = true;
+                reconnect_at = Instant::now();
             }
         }
     });
@@ -266,6 +483,30 @@ pub async fn stop_recording(
     Ok((StatusCode::ACCEPTED, "ok".to_string()))
 }
 
+pub async fn set_log_mask(
+    State(state): State<Arc<ServerState>>,
+    Json(log_mask): Json<LogMaskConfig>,
+) -> Result<(StatusCode, String), (StatusCode, String)> {
+    if state.config.debug_mode {
+        return Err((StatusCode::FORBIDDEN, "server is in debug mode".to_string()));
+    }
+    state
+        .diag_device_ctrl_sender
+        .send(DiagDeviceCtrlMessage::SetLogMask {
+            log_codes: state.config.diag_log_codes(&log_mask),
+            event_reports: log_mask.event_reports,
+            f3_messages: log_mask.f3_messages,
+        })
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("couldn't send set log mask message: {e}"),
+            )
+        })?;
+    Ok((StatusCode::ACCEPTED, "ok".to_string()))
+}
+
 pub async fn delete_recording(
     State(state): State<Arc<ServerState>>,
     Path(qmdl_name): Path<String>,
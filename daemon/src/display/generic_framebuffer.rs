@@ -61,6 +61,7 @@ impl Color {
                 }
             }
             DisplayState::WarningDetected => Color::Red,
+            DisplayState::CaptureDegraded => Color::Yellow,
         }
     }
 }
@@ -24,4 +24,7 @@ pub enum DisplayState {
     Recording,
     Paused,
     WarningDetected,
+    /// The diag device errored out or stopped sending us anything, and we're
+    /// trying to reconnect to it
+    CaptureDegraded,
 }
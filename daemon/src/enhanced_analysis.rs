@@ -146,7 +146,7 @@ impl EnhancedAnalysisWriter {
     /// Analyze QMDL messages with GPS correlation
     pub async fn analyze_with_gps(
         &mut self,
-        container: MessagesContainer,
+        container: &MessagesContainer,
     ) -> Result<(usize, bool), std::io::Error> {
         let mut row = self.harness.analyze_qmdl_messages(container);
         
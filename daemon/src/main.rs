@@ -16,6 +16,7 @@ mod stats;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use crate::config::{parse_args, parse_config};
 use crate::diag::run_diag_read_thread;
@@ -26,27 +27,36 @@ use crate::server::{ServerState, get_config, get_qmdl, get_zip, serve_static, se
 use crate::stats::{get_qmdl_manifest, get_system_stats};
 
 use analysis::{
-    AnalysisCtrlMessage, AnalysisStatus, get_analysis_status, run_analysis_thread, start_analysis,
+    AnalysisCtrlMessage, AnalysisStatus, LIVE_ANALYSIS_QUEUE_SIZE, LiveAnalysisMessage,
+    LiveAnalysisMetrics, LiveAnalysisSender, get_analysis_status, run_analysis_thread,
+    run_live_analysis_thread, start_analysis,
 };
 use axum::Router;
 use axum::response::Redirect;
 use axum::routing::{get, post};
 use diag::{
-    DiagDeviceCtrlMessage, delete_all_recordings, delete_recording, get_analysis_report,
-    start_recording, stop_recording,
+    DiagDeviceCtrlMessage, DiagSource, delete_all_recordings, delete_recording, get_analysis_report,
+    set_log_mask, start_recording, stop_recording,
 };
-use log::{error, info};
+use log::{error, info, warn};
 use qmdl_store::RecordingStoreError;
-use rayhunter::diag_device::DiagDevice;
+use rayhunter::diag_device::{DiagDevice, DiagTransport, ModemInfo};
+use rayhunter::diag_serial::SerialTransport;
+use rayhunter::qmdl::{QmdlReader, QmdlReplay};
+use tokio::fs::File;
 use tokio::net::TcpListener;
 use tokio::select;
 use tokio::sync::mpsc::{self, Sender};
 use tokio::sync::{RwLock, oneshot};
 use tokio::task::JoinHandle;
+use tokio::time::timeout;
 use tokio_util::task::TaskTracker;
 
 type AppRouter = Router<Arc<ServerState>>;
 
+// how long to wait for the modem to tell us about itself at startup
+const MODEM_INFO_TIMEOUT: Duration = Duration::from_secs(5);
+
 fn get_router() -> AppRouter {
     Router::new()
         .route("/api/pcap/{name}", get(get_pcap))
@@ -62,6 +72,7 @@ fn get_router() -> AppRouter {
         .route("/api/analysis-report/{name}", get(get_analysis_report))
         .route("/api/analysis", get(get_analysis_status))
         .route("/api/analysis/{name}", post(start_analysis))
+        .route("/api/log-mask", post(set_log_mask))
         .route("/api/config", get(get_config))
         .route("/api/config", post(set_config))
         .route("/api/v1/gps/{lat_lon}", post(gps::receive_gps_coordinate))
@@ -125,9 +136,40 @@ async fn init_qmdl_store(config: &config::Config) -> Result<RecordingStore, Rayh
     }
 }
 
+// Configures the modem's logging, and returns what it told us about itself.
+// Not knowing the modem's firmware isn't worth failing over, so we don't.
+async fn init_diag_device<T: DiagTransport>(
+    dev: &mut DiagDevice<T>,
+    config: &config::Config,
+) -> Result<ModemInfo, RayhunterError> {
+    // ask before logging is enabled, so the response isn't buried in logs
+    let modem_info = match timeout(MODEM_INFO_TIMEOUT, dev.modem_info()).await {
+        Ok(Ok(modem_info)) => {
+            info!("modem info: {modem_info:?}");
+            modem_info
+        }
+        Ok(Err(e)) => {
+            warn!("failed to get modem info: {e}");
+            ModemInfo::default()
+        }
+        Err(_) => {
+            warn!("timed out getting modem info");
+            ModemInfo::default()
+        }
+    };
+    dev.set_log_codes(config.diag_log_codes(&config.log_mask));
+    dev.set_event_reports(config.log_mask.event_reports);
+    dev.set_f3_messages(config.log_mask.f3_messages);
+    dev.config_logs()
+        .await
+        .map_err(RayhunterError::DiagInitError)?;
+    Ok(modem_info)
+}
+
 // Start a thread that'll track when user hits ctrl+c. When that happens,
 // trigger various cleanup tasks, including sending signals to other threads to
 // shutdown
+
 #[allow(clippy::too_many_arguments)]
 fn run_shutdown_thread(
     task_tracker: &TaskTracker,
@@ -212,7 +254,8 @@ async fn run_with_config(
     println!("R A Y H U N T E R 🐳");
 
     let store = init_qmdl_store(&config).await?;
-    let analysis_status = AnalysisStatus::new(&store);
+    let analysis_status_lock = Arc::new(RwLock::new(AnalysisStatus::new(&store)));
+    let live_analysis_metrics = Arc::new(LiveAnalysisMetrics::default());
     let qmdl_store_lock = Arc::new(RwLock::new(store));
     let (diag_tx, diag_rx) = mpsc::channel::<DiagDeviceCtrlMessage>(1);
     let (ui_update_tx, ui_update_rx) = mpsc::channel::<display::DisplayState>(1);
@@ -223,24 +266,69 @@ async fn run_with_config(
     if !config.debug_mode {
         let (ui_shutdown_tx, ui_shutdown_rx) = oneshot::channel();
         maybe_ui_shutdown_tx = Some(ui_shutdown_tx);
-        let mut dev = DiagDevice::new()
-            .await
-            .map_err(RayhunterError::DiagInitError)?;
-        dev.config_logs()
-            .await
-            .map_err(RayhunterError::DiagInitError)?;
+        let mut modem_info = ModemInfo::default();
+        let source = match &config.replay_qmdl_path {
+            Some(path) => {
+                info!("Replaying {path} instead of reading from the diag device");
+                let qmdl_file = File::open(path).await?;
+                let file_size = qmdl_file.metadata().await?.len() as usize;
+                let reader = QmdlReader::new(qmdl_file, Some(file_size));
+                DiagSource::Replay(QmdlReplay::new(reader, config.replay_speed()))
+            }
+            None => match &config.serial_device_path {
+                Some(path) => {
+                    info!("Using diag over serial device {path}");
+                    let transport = SerialTransport::open(path)
+                        .await
+                        .map_err(RayhunterError::DiagInitError)?;
+                    let mut dev = DiagDevice::with_transport(transport);
+                    modem_info = init_diag_device(&mut dev, &config).await?;
+                    DiagSource::Serial {
+                        dev,
+                        path: path.clone(),
+                    }
+                }
+                None => {
+                    let mut dev = DiagDevice::new()
+                        .await
+                        .map_err(RayhunterError::DiagInitError)?;
+                    modem_info = init_diag_device(&mut dev, &config).await?;
+                    DiagSource::Device(dev)
+                }
+            },
+        };
+
+        qmdl_store_lock.write().await.modem_info = modem_info;
+
+        info!("Starting Live Analysis Thread");
+        let (live_analysis_tx, live_analysis_rx) =
+            mpsc::unbounded_channel::<LiveAnalysisMessage>();
+        run_live_analysis_thread(
+            &task_tracker,
+            live_analysis_rx,
+            qmdl_store_lock.clone(),
+            analysis_status_lock.clone(),
+            analysis_tx.clone(),
+            ui_update_tx.clone(),
+            live_analysis_metrics.clone(),
+            config.enable_dummy_analyzer,
+            config.analyzers.clone(),
+        );
 
         info!("Starting Diag Thread");
         run_diag_read_thread(
             &task_tracker,
-            dev,
+            source,
             diag_rx,
             gps_rx,
             ui_update_tx.clone(),
             qmdl_store_lock.clone(),
-            analysis_tx.clone(),
-            config.enable_dummy_analyzer,
-            config.analyzers.clone(),
+            LiveAnalysisSender::new(
+                live_analysis_tx,
+                LIVE_ANALYSIS_QUEUE_SIZE,
+                live_analysis_metrics.clone(),
+            ),
+            config.diag_stall_timeout(),
         );
         info!("Starting UI");
         display::update_ui(&task_tracker, &config, ui_shutdown_rx, ui_update_rx);
@@ -258,7 +346,6 @@ async fn run_with_config(
 
     let (daemon_restart_tx, daemon_restart_rx) = oneshot::channel::<()>();
     let (server_shutdown_tx, server_shutdown_rx) = oneshot::channel::<()>();
-    let analysis_status_lock = Arc::new(RwLock::new(analysis_status));
     run_analysis_thread(
         &task_tracker,
         analysis_rx,
@@ -288,6 +375,7 @@ async fn run_with_config(
         ui_update_sender: ui_update_tx,
         analysis_status_lock,
         analysis_sender: analysis_tx,
+        live_analysis_metrics,
         gps_sender: gps_tx,
         daemon_restart_tx: Arc::new(RwLock::new(Some(daemon_restart_tx))),
     });
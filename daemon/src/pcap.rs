@@ -7,7 +7,8 @@ use axum::http::StatusCode;
 use axum::http::header::CONTENT_TYPE;
 use axum::response::{IntoResponse, Response};
 use log::error;
-use rayhunter::diag::DataType;
+use rayhunter::diag::{DataType, LogBody, Message};
+use rayhunter::diag_device::ModemInfo;
 use rayhunter::gsmtap_parser;
 use rayhunter::pcap::GsmtapPcapWriter;
 use rayhunter::qmdl::QmdlReader;
@@ -37,6 +38,8 @@ pub async fn get_pcap(
         ));
     }
     let qmdl_size_bytes = entry.qmdl_size_bytes;
+    let modem_info = entry.modem_info();
+    let capture_user_plane = state.config.capture_user_plane;
     let qmdl_file = qmdl_store
         .open_entry_qmdl(entry_index)
         .await
@@ -46,7 +49,15 @@ pub async fn get_pcap(
     let (reader, writer) = duplex(1024);
 
     tokio::spawn(async move {
-        if let Err(e) = generate_pcap_data(writer, qmdl_file, qmdl_size_bytes).await {
+        if let Err(e) = generate_pcap_data(
+            writer,
+            qmdl_file,
+            qmdl_size_bytes,
+            &modem_info,
+            capture_user_plane,
+        )
+        .await
+        {
             error!("failed to generate PCAP: {e:?}");
         }
     });
@@ -60,12 +71,14 @@ pub async fn generate_pcap_data<R, W>(
     writer: W,
     qmdl_file: R,
     qmdl_size_bytes: usize,
+    modem_info: &ModemInfo,
+    capture_user_plane: bool,
 ) -> Result<(), Error>
 where
     W: AsyncWrite + Unpin + Send,
     R: AsyncRead + Unpin,
 {
-    let mut pcap_writer = GsmtapPcapWriter::new(writer).await?;
+    let mut pcap_writer = GsmtapPcapWriter::new(writer, modem_info).await?;
     pcap_writer.write_iface_header().await?;
 
     let mut reader = QmdlReader::new(qmdl_file, Some(qmdl_size_bytes));
@@ -76,6 +89,17 @@ where
 
         for maybe_msg in container.into_messages() {
             match maybe_msg {
+                Ok(Message::Log {
+                    timestamp,
+                    body: LogBody::IpTraffic { msg, .. },
+                    ..
+                }) => {
+                    // captures made before user-plane capture was disabled
+                    // may still contain IP traffic
+                    if capture_user_plane {
+                        pcap_writer.write_ip_packet(msg, timestamp).await?;
+                    }
+                }
                 Ok(msg) => {
                     let maybe_gsmtap_msg = gsmtap_parser::parse(msg)?;
                     if let Some((timestamp, gsmtap_msg)) = maybe_gsmtap_msg {
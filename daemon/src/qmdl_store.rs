@@ -2,6 +2,8 @@ use std::io::{self, ErrorKind};
 use std::path::{Path, PathBuf};
 
 use chrono::{DateTime, Local};
+use rayhunter::diag_device::ModemInfo;
+use rayhunter::hdlc::HdlcFrameStats;
 use rayhunter::util::RuntimeMetadata;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -36,6 +38,9 @@ pub struct RecordingStore {
     pub path: PathBuf,
     pub manifest: Manifest,
     pub current_entry: Option<usize>, // index into manifest
+    // what the modem told us about itself at startup, recorded in each new
+    // entry
+    pub modem_info: ModemInfo,
 }
 
 #[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
@@ -53,10 +58,19 @@ pub struct ManifestEntry {
     pub rayhunter_version: Option<String>,
     pub system_os: Option<String>,
     pub arch: Option<String>,
+    pub modem_firmware: Option<String>,
+    pub modem_model: Option<String>,
+    /// How many HDLC frames straddled diag reads and had to be pieced back
+    /// together while recording
+    #[serde(default)]
+    pub frames_reassembled: u32,
+    /// How many partial HDLC frames had to be thrown away while recording
+    #[serde(default)]
+    pub frames_dropped: u32,
 }
 
 impl ManifestEntry {
-    fn new() -> Self {
+    fn new(modem_info: &ModemInfo) -> Self {
         let now = Local::now();
         let metadata = RuntimeMetadata::new();
         ManifestEntry {
@@ -68,6 +82,17 @@ impl ManifestEntry {
             rayhunter_version: Some(metadata.rayhunter_version),
             system_os: Some(metadata.system_os),
             arch: Some(metadata.arch),
+            modem_firmware: modem_info.firmware.clone(),
+            modem_model: modem_info.model.clone(),
+            frames_reassembled: 0,
+            frames_dropped: 0,
+        }
+    }
+
+    pub fn modem_info(&self) -> ModemInfo {
+        ModemInfo {
+            firmware: self.modem_firmware.clone(),
+            model: self.modem_model.clone(),
         }
     }
 
@@ -119,6 +144,7 @@ impl RecordingStore {
             path,
             manifest,
             current_entry: None,
+            modem_info: ModemInfo::default(),
         })
     }
 
@@ -138,6 +164,7 @@ impl RecordingStore {
                 entries: Vec::new(),
             },
             current_entry: None,
+            modem_info: ModemInfo::default(),
         };
 
         store.write_manifest().await?;
@@ -163,7 +190,7 @@ impl RecordingStore {
         if self.current_entry.is_some() {
             self.close_current_entry().await?;
         }
-        let new_entry = ManifestEntry::new();
+        let new_entry = ManifestEntry::new(&self.modem_info);
         let qmdl_filepath = new_entry.get_qmdl_filepath(&self.path);
         let qmdl_file = File::create(&qmdl_filepath)
             .await
@@ -234,6 +261,18 @@ impl RecordingStore {
         self.write_manifest().await
     }
 
+    // Adds to the given entry's counts of reassembled and dropped HDLC frames
+    pub async fn update_entry_frame_stats(
+        &mut self,
+        entry_index: usize,
+        frame_stats: HdlcFrameStats,
+    ) -> Result<(), RecordingStoreError> {
+        let entry = &mut self.manifest.entries[entry_index];
+        entry.frames_reassembled += frame_stats.reassembled;
+        entry.frames_dropped += frame_stats.dropped;
+        self.write_manifest().await
+    }
+
     // Sets the given entry's analysis file size
     pub async fn update_entry_analysis_size(
         &mut self,
@@ -376,6 +415,20 @@ mod tests {
         assert_eq!(loaded_store.manifest.entries.len(), 0);
     }
 
+    #[tokio::test]
+    async fn test_entries_record_modem_info() {
+        let dir = make_temp_dir();
+        let mut store = RecordingStore::create(dir.path()).await.unwrap();
+        store.modem_info = ModemInfo {
+            firmware: Some("MPSS.JO.2.0.c1.4-00047-M9607".to_string()),
+            model: Some("RC400L".to_string()),
+        };
+        let _ = store.new_entry().await.unwrap();
+        let entry_index = store.current_entry.unwrap();
+        let manifest = RecordingStore::read_manifest(dir.path()).await.unwrap();
+        assert_eq!(manifest.entries[entry_index].modem_info(), store.modem_info);
+    }
+
     #[tokio::test]
     async fn test_creating_updating_and_closing_entries() {
         let dir = make_temp_dir();
@@ -406,6 +459,25 @@ mod tests {
             store.manifest
         );
 
+        let frame_stats = HdlcFrameStats {
+            reassembled: 3,
+            dropped: 1,
+        };
+        store
+            .update_entry_frame_stats(entry_index, frame_stats)
+            .await
+            .unwrap();
+        store
+            .update_entry_frame_stats(entry_index, frame_stats)
+            .await
+            .unwrap();
+        let entry = &store.manifest.entries[entry_index];
+        assert_eq!((entry.frames_reassembled, entry.frames_dropped), (6, 2));
+        assert_eq!(
+            RecordingStore::read_manifest(dir.path()).await.unwrap(),
+            store.manifest
+        );
+
         store.close_current_entry().await.unwrap();
         assert!(matches!(
             store.close_current_entry().await,
@@ -19,7 +19,7 @@ use tokio::sync::{RwLock, oneshot};
 use tokio_util::compat::FuturesAsyncWriteCompatExt;
 use tokio_util::io::ReaderStream;
 
-use crate::analysis::{AnalysisCtrlMessage, AnalysisStatus};
+use crate::analysis::{AnalysisCtrlMessage, AnalysisStatus, LiveAnalysisMetrics};
 use crate::config::Config;
 use crate::gps::GpsCoordinate;
 use crate::pcap::generate_pcap_data;
@@ -34,6 +34,7 @@ pub struct ServerState {
     pub ui_update_sender: Sender<display::DisplayState>,
     pub analysis_status_lock: Arc<RwLock<AnalysisStatus>>,
     pub analysis_sender: Sender<AnalysisCtrlMessage>,
+    pub live_analysis_metrics: Arc<LiveAnalysisMetrics>,
     pub gps_sender: Sender<GpsCoordinate>,
     pub daemon_restart_tx: Arc<RwLock<Option<oneshot::Sender<()>>>>,
 }
@@ -144,7 +145,7 @@ pub async fn get_zip(
     Path(entry_name): Path<String>,
 ) -> Result<Response, (StatusCode, String)> {
     let qmdl_idx = entry_name.trim_end_matches(".zip").to_owned();
-    let (entry_index, qmdl_size_bytes) = {
+    let (entry_index, qmdl_size_bytes, modem_info) = {
         let qmdl_store = state.qmdl_store_lock.read().await;
         let (entry_index, entry) = qmdl_store.entry_for_name(&qmdl_idx).ok_or((
             StatusCode::NOT_FOUND,
@@ -158,10 +159,11 @@ pub async fn get_zip(
             ));
         }
 
-        (entry_index, entry.qmdl_size_bytes)
+        (entry_index, entry.qmdl_size_bytes, entry.modem_info())
     };
 
     let qmdl_store_lock = state.qmdl_store_lock.clone();
+    let capture_user_plane = state.config.capture_user_plane;
 
     let (reader, writer) = duplex(8192);
 
@@ -204,8 +206,14 @@ pub async fn get_zip(
                         .take(qmdl_size_bytes as u64)
                 };
 
-                if let Err(e) =
-                    generate_pcap_data(&mut entry_writer, qmdl_file_for_pcap, qmdl_size_bytes).await
+                if let Err(e) = generate_pcap_data(
+                    &mut entry_writer,
+                    qmdl_file_for_pcap,
+                    qmdl_size_bytes,
+                    &modem_info,
+                    capture_user_plane,
+                )
+                .await
                 {
                     // if we fail to generate the PCAP file, we should still continue and give the
                     // user the QMDL.
@@ -316,6 +324,7 @@ mod tests {
             ui_update_sender: ui_tx,
             analysis_status_lock: Arc::new(RwLock::new(analysis_status)),
             analysis_sender: analysis_tx,
+            live_analysis_metrics: Arc::new(LiveAnalysisMetrics::default()),
             gps_sender: gps_tx,
             daemon_restart_tx: Arc::new(RwLock::new(None)),
         })
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use crate::analysis::LiveAnalysisStats;
 use crate::qmdl_store::ManifestEntry;
 use crate::server::ServerState;
 
@@ -16,14 +17,16 @@ pub struct SystemStats {
     pub disk_stats: DiskStats,
     pub memory_stats: MemoryStats,
     pub runtime_metadata: RuntimeMetadata,
+    pub live_analysis: LiveAnalysisStats,
 }
 
 impl SystemStats {
-    pub async fn new(qmdl_path: &str) -> Result<Self, String> {
+    pub async fn new(qmdl_path: &str, live_analysis: LiveAnalysisStats) -> Result<Self, String> {
         Ok(Self {
             disk_stats: DiskStats::new(qmdl_path).await?,
             memory_stats: MemoryStats::new().await?,
             runtime_metadata: RuntimeMetadata::new(),
+            live_analysis,
         })
     }
 }
@@ -111,7 +114,8 @@ pub async fn get_system_stats(
     State(state): State<Arc<ServerState>>,
 ) -> Result<Json<SystemStats>, (StatusCode, String)> {
     let qmdl_store = state.qmdl_store_lock.read().await;
-    match SystemStats::new(qmdl_store.path.to_str().unwrap()).await {
+    let live_analysis = state.live_analysis_metrics.stats();
+    match SystemStats::new(qmdl_store.path.to_str().unwrap(), live_analysis).await {
         Ok(stats) => Ok(Json(stats)),
         Err(err) => {
             error!("error getting system stats: {err}");
@@ -38,6 +38,7 @@ export type AnalysisRow = {
 export type PacketAnalysis = {
     timestamp: Date;
     events: Event[];
+    subscription_id: number | null;
 };
 export type Event = QualitativeWarning | InformationalEvent;
 export enum EventType {
@@ -92,6 +93,7 @@ export function parse_finished_report(report_json: NewlineDeliminatedJson): Anal
             return {
                 timestamp: analysis_json.timestamp,
                 events,
+                subscription_id: analysis_json.subscription_id ?? null,
             };
         });
         num_skipped_packets += row_json.skipped_message_reasons.length;
@@ -12,6 +12,10 @@ interface JsonManifestEntry {
     last_message_time: string;
     qmdl_size_bytes: number;
     analysis_size_bytes: number;
+    modem_firmware: string | null;
+    modem_model: string | null;
+    frames_reassembled: number;
+    frames_dropped: number;
 }
 
 export class Manifest {
@@ -56,6 +60,10 @@ export class ManifestEntry {
     public last_message_time: Date | undefined = $state(undefined);
     public qmdl_size_bytes = $state(0);
     public analysis_size_bytes = $state(0);
+    public modem_firmware: string | undefined;
+    public modem_model: string | undefined;
+    public frames_reassembled = $state(0);
+    public frames_dropped = $state(0);
     public analysis_status: AnalysisStatus | undefined = $state(undefined);
     public analysis_report: AnalysisReport | string | undefined = $state(undefined);
 
@@ -64,6 +72,10 @@ export class ManifestEntry {
         this.qmdl_size_bytes = json.qmdl_size_bytes;
         this.analysis_size_bytes = json.analysis_size_bytes;
         this.start_time = new Date(json.start_time);
+        this.modem_firmware = json.modem_firmware ?? undefined;
+        this.modem_model = json.modem_model ?? undefined;
+        this.frames_reassembled = json.frames_reassembled;
+        this.frames_dropped = json.frames_dropped;
         if (json.last_message_time) {
             this.last_message_time = new Date(json.last_message_time);
         }
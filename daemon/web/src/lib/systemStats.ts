@@ -2,6 +2,7 @@ export interface SystemStats {
     disk_stats: DiskStats;
     memory_stats: MemoryStats;
     runtime_metadata: RuntimeMetadata;
+    live_analysis: LiveAnalysisStats;
 }
 
 export interface RuntimeMetadata {
@@ -24,3 +25,12 @@ export interface MemoryStats {
     used: string,
     free: string,
 }
+
+export interface LiveAnalysisStats {
+    queue_depth: number,
+    queue_capacity: number,
+    lag_ms: number,
+    max_lag_ms: number,
+    dropped_containers: number,
+    deferred_recordings: number,
+}
@@ -6,13 +6,41 @@ export interface AnalyzerConfig {
     connection_redirect_2g_downgrade: boolean;
     lte_sib6_and_7_downgrade: boolean;
     null_cipher: boolean;
+    auth_skipped: boolean;
+    emm_reject: boolean;
+    identity_requested: boolean;
+    identity_requested_imei_packet_threshold: number;
+    identity_requested_tmsi_packet_threshold: number;
+    imsi_provided: boolean;
+}
+
+export type LogCodeGroup =
+    | 'l3_signalling'
+    | 'nas'
+    | 'lte_rrc_info'
+    | 'ml1_measurements'
+    | 'gsm_l1'
+    | 'wcdma'
+    | 'ip_traffic';
+
+export interface LogMaskConfig {
+    groups: LogCodeGroup[];
+    extra_log_codes: number[];
+    event_reports: boolean;
+    f3_messages: boolean;
 }
 
 export interface Config {
     ui_level: number;
     colorblind_mode: boolean;
     key_input_mode: number;
+    capture_user_plane: boolean;
+    replay_qmdl_path?: string | null;
+    replay_speed: number;
+    diag_stall_timeout_secs: number;
+    serial_device_path?: string | null;
     analyzers: AnalyzerConfig;
+    log_mask: LogMaskConfig;
 }
 
 export async function req(method: string, url: string): Promise<string> {
@@ -0,0 +1,283 @@
+//! Measures how fast, and with how many allocations, the analysis harness
+//! gets through a QMDL file. This runs without criterion so that it can be
+//! built for the devices themselves, but prints its results the same way:
+//!
+//!     cargo bench -p rayhunter --bench analysis
+//!
+//! By default it runs over a synthetic capture of common log types. Set
+//! `RAYHUNTER_BENCH_QMDL` to the path of a real capture to use that instead.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use deku::prelude::*;
+use rayhunter::analysis::analyzer::{AnalyzerConfig, Harness};
+use rayhunter::diag::{
+    CRC_CCITT, LogBody, LteRrcOtaPacket, Message, MessagesContainer, Nas4GMessageDirection,
+    Timestamp,
+};
+use rayhunter::hdlc::{HdlcFrameAssembler, HdlcFrameStats, hdlc_encapsulate};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// how many times to go over the sample for each measurement
+const ITERATIONS: usize = 20;
+// how many copies of each synthetic message to put in the sample
+const SYNTHETIC_REPEATS: usize = 500;
+
+fn rrc_ota(pdu_num: u8, payload: &[u8]) -> Message {
+    Message::Log {
+        pending_msgs: 0,
+        outer_length: 0,
+        inner_length: 0,
+        log_type: 0xb0c0,
+        timestamp: Timestamp { ts: 0 },
+        body: LogBody::LteRrcOtaMessage {
+            ext_header_version: 26,
+            packet: LteRrcOtaPacket::V25 {
+                rrc_rel_maj: 15,
+                rrc_rel_min: 64,
+                nr_rrc_rel_maj: 15,
+                nr_rrc_rel_min: 64,
+                bearer_id: 0,
+                phy_cell_id: 270,
+                earfcn: 1811,
+                sfn_subfn: 0,
+                pdu_num,
+                sib_mask: 0,
+                len: payload.len() as u16,
+                packet: payload.to_vec(),
+            },
+        },
+        subscription_id: None,
+    }
+}
+
+fn nas(payload: &[u8]) -> Message {
+    Message::Log {
+        pending_msgs: 0,
+        outer_length: 0,
+        inner_length: 0,
+        log_type: 0xb0ec,
+        timestamp: Timestamp { ts: 0 },
+        body: LogBody::Nas4GMessage {
+            direction: Nas4GMessageDirection::Downlink,
+            ext_header_version: 1,
+            rrc_rel: 15,
+            rrc_version_minor: 0,
+            rrc_version_major: 15,
+            msg: payload.to_vec(),
+        },
+        subscription_id: None,
+    }
+}
+
+fn gsm_bcch(payload: &[u8]) -> Message {
+    Message::Log {
+        pending_msgs: 0,
+        outer_length: 0,
+        inner_length: 0,
+        log_type: 0x512f,
+        timestamp: Timestamp { ts: 0 },
+        body: LogBody::GsmRrSignallingMessage {
+            channel_type: 0x81,
+            message_type: payload[1],
+            length: payload.len() as u8,
+            msg: payload.to_vec(),
+        },
+        subscription_id: None,
+    }
+}
+
+// Log lengths are ignored when serializing, so patch them in afterwards
+fn serialize(msg: &Message) -> Vec<u8> {
+    let mut data = msg.to_bytes().expect("failed to serialize message");
+    let len = (data.len() - 4) as u16;
+    data[2..4].copy_from_slice(&len.to_le_bytes());
+    data[4..6].copy_from_slice(&len.to_le_bytes());
+    data
+}
+
+fn synthetic_qmdl() -> Vec<u8> {
+    let messages = [
+        // GSM system information type 3
+        serialize(&gsm_bcch(&[
+            0x06, 0x1b, 0x00, 0x01, 0x00, 0xf1, 0x10, 0x00, 0x01, 0x49, 0x03, 0x05, 0x27, 0x47,
+            0x40, 0xe5, 0x04, 0x00, 0x2c, 0x0b, 0x2b, 0x2b,
+        ])),
+        // NB-IoT paging
+        serialize(&rrc_ota(
+            47,
+            &[
+                0x40, 0x81, 0x2d, 0xea, 0xdb, 0xee, 0xf3, 0x20, 0x02, 0x02, 0x02, 0x46, 0x8a, 0xcf,
+                0x12,
+            ],
+        )),
+        // a ciphered NAS message
+        serialize(&nas(&[0x27, 0x01, 0x02, 0x03, 0x04, 0x2a, 0x99, 0x88])),
+        // an LTE ML1 serving cell info log
+        vec![
+            16, 0, 22, 0, 22, 0, 0xe4, 0xb0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01, 0x05, 0x10, 0x00, 0x14,
+            0x05, 0x04, 0x01, 0x00, 0x00,
+        ],
+    ];
+    let mut qmdl = Vec::new();
+    for _ in 0..SYNTHETIC_REPEATS {
+        for msg in &messages {
+            qmdl.extend(hdlc_encapsulate(msg, &CRC_CCITT));
+        }
+    }
+    qmdl
+}
+
+// Splits a QMDL file into containers of a few frames each, like the ones the
+// diag device hands us
+fn containers(qmdl: &[u8]) -> Vec<MessagesContainer> {
+    let mut frames = Vec::new();
+    HdlcFrameAssembler::new().push(qmdl, &mut frames);
+    frames
+        .chunks(8)
+        .map(|chunk| MessagesContainer::from_frames(chunk.to_vec(), HdlcFrameStats::default()))
+        .collect()
+}
+
+struct Measurement {
+    elapsed: Duration,
+    allocations: usize,
+    allocated_bytes: usize,
+}
+
+fn measure(mut f: impl FnMut()) -> Measurement {
+    // warm up
+    f();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let allocated_bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    Measurement {
+        elapsed: start.elapsed() / ITERATIONS as u32,
+        allocations: (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / ITERATIONS,
+        allocated_bytes: (ALLOCATED_BYTES.load(Ordering::Relaxed) - allocated_bytes) / ITERATIONS,
+    }
+}
+
+/// Like [measure], but runs `setup` before each iteration to build the
+/// routine's input, without timing it or counting its allocations
+fn measure_batched<I>(mut setup: impl FnMut() -> I, mut routine: impl FnMut(I)) -> Measurement {
+    // warm up
+    routine(setup());
+    let mut elapsed = Duration::ZERO;
+    let mut allocations = 0;
+    let mut allocated_bytes = 0;
+    for _ in 0..ITERATIONS {
+        let input = setup();
+        let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+        let allocated_bytes_before = ALLOCATED_BYTES.load(Ordering::Relaxed);
+        let start = Instant::now();
+        routine(input);
+        elapsed += start.elapsed();
+        allocations += ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+        allocated_bytes += ALLOCATED_BYTES.load(Ordering::Relaxed) - allocated_bytes_before;
+    }
+    Measurement {
+        elapsed: elapsed / ITERATIONS as u32,
+        allocations: allocations / ITERATIONS,
+        allocated_bytes: allocated_bytes / ITERATIONS,
+    }
+}
+
+fn report(name: &str, measurement: &Measurement, qmdl_bytes: usize, num_messages: usize) {
+    let secs = measurement.elapsed.as_secs_f64();
+    println!("{name}");
+    println!(
+        "{:>24} {:.3} ms",
+        "time:",
+        measurement.elapsed.as_secs_f64() * 1000.0
+    );
+    println!(
+        "{:>24} {:.2} MiB/s, {:.0} messages/s",
+        "thrpt:",
+        qmdl_bytes as f64 / secs / (1024.0 * 1024.0),
+        num_messages as f64 / secs
+    );
+    println!(
+        "{:>24} {:.1} allocations/message, {:.0} bytes/message",
+        "allocs:",
+        measurement.allocations as f64 / num_messages as f64,
+        measurement.allocated_bytes as f64 / num_messages as f64
+    );
+}
+
+fn main() {
+    let qmdl = match std::env::var("RAYHUNTER_BENCH_QMDL") {
+        Ok(path) => std::fs::read(&path).expect("failed to read QMDL file"),
+        Err(_) => synthetic_qmdl(),
+    };
+    let containers = containers(&qmdl);
+    let num_messages: usize = containers.iter().map(|c| c.messages.len()).sum();
+    println!(
+        "sample: {} bytes, {} messages in {} containers\n",
+        qmdl.len(),
+        num_messages,
+        containers.len()
+    );
+
+    let decode = measure(|| {
+        for container in &containers {
+            for msg in container.messages() {
+                black_box(msg).ok();
+            }
+        }
+    });
+    report("decode", &decode, qmdl.len(), num_messages);
+
+    // the owning API, for comparison. It consumes the containers, so each
+    // iteration gets its own copies, made outside the timed region
+    let into_messages = measure_batched(
+        || containers.clone(),
+        |containers| {
+            for container in containers {
+                for msg in container.into_messages() {
+                    black_box(msg).ok();
+                }
+            }
+        },
+    );
+    report("into_messages", &into_messages, qmdl.len(), num_messages);
+
+    let mut harness = Harness::new_with_config(&AnalyzerConfig::default());
+    let analyze = measure(|| {
+        for container in &containers {
+            black_box(harness.analyze_qmdl_messages(container));
+        }
+    });
+    report("analyze_qmdl_messages", &analyze, qmdl.len(), num_messages);
+}
@@ -2,12 +2,19 @@ use chrono::{DateTime, FixedOffset};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 
+use crate::diag_events::{self, StateChange};
 use crate::util::RuntimeMetadata;
-use crate::{diag::MessagesContainer, gsmtap_parser};
+use crate::{
+    diag::{Message, MessagesContainer},
+    gsmtap_parser,
+};
 
 use super::{
+    auth_skipped::AuthSkippedAnalyzer,
     connection_redirect_downgrade::ConnectionRedirect2GDowngradeAnalyzer,
-    imsi_requested::ImsiRequestedAnalyzer, information_element::InformationElement,
+    emm_reject::EmmRejectAnalyzer, identity_requested::IdentityRequestedAnalyzer,
+    imsi_provided::ImsiProvidedAnalyzer, imsi_requested::{self, ImsiRequestedAnalyzer},
+    information_element::InformationElement,
     null_cipher::NullCipherAnalyzer, priority_2g_downgrade::LteSib6And7DowngradeAnalyzer,
     cellular_network::CellularNetworkAnalyzer,
 };
@@ -19,6 +26,15 @@ pub struct AnalyzerConfig {
     pub connection_redirect_2g_downgrade: bool,
     pub lte_sib6_and_7_downgrade: bool,
     pub null_cipher: bool,
+    pub auth_skipped: bool,
+    pub emm_reject: bool,
+    pub identity_requested: bool,
+    /// How many packets into an analysis IMEI and IMEISV requests are still
+    /// put down to the phone attaching after being turned on
+    pub identity_requested_imei_packet_threshold: usize,
+    /// Likewise for TMSI requests
+    pub identity_requested_tmsi_packet_threshold: usize,
+    pub imsi_provided: bool,
     pub cellular_network: bool,
 }
 
@@ -29,6 +45,12 @@ impl Default for AnalyzerConfig {
             connection_redirect_2g_downgrade: true,
             lte_sib6_and_7_downgrade: true,
             null_cipher: true,
+            auth_skipped: true,
+            emm_reject: true,
+            identity_requested: true,
+            identity_requested_imei_packet_threshold: imsi_requested::PACKET_THRESHHOLD,
+            identity_requested_tmsi_packet_threshold: imsi_requested::PACKET_THRESHHOLD,
+            imsi_provided: true,
             cellular_network: true,
         }
     }
@@ -84,6 +106,14 @@ pub trait Analyzer {
     /// [Analyzer] updates per message, since it may be run over hundreds or
     /// thousands of them alongside many other [Analyzers](Analyzer).
     fn analyze_information_element(&mut self, ie: &InformationElement) -> Option<Event>;
+
+    /// Analyze a modem state change reported through a diag event rather than
+    /// an OTA message, such as the RRC state or a radio link failure. Most
+    /// heuristics only need [Analyzer::analyze_information_element], so this
+    /// does nothing by default.
+    fn analyze_state_change(&mut self, _change: &StateChange) -> Option<Event> {
+        None
+    }
 }
 
 /// A [QmdlAnalyzer] operates at the QMDL message level, before GSMTAP parsing.
@@ -100,6 +130,14 @@ pub trait QmdlAnalyzer {
     /// Analyze a single QMDL message, possibly returning an [Event] if relevant
     /// information is found or extracted.
     fn analyze_qmdl_message(&mut self, qmdl_message: &crate::diag::Message) -> Option<Event>;
+
+    /// Analyze the [InformationElement] the QMDL message last passed to
+    /// [QmdlAnalyzer::analyze_qmdl_message] decoded to, if it decoded to one.
+    /// This lets QMDL analyzers look at fully decoded messages without
+    /// decoding them a second time, so it does nothing by default.
+    fn analyze_information_element(&mut self, _ie: &InformationElement) -> Option<Event> {
+        None
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -119,6 +157,8 @@ pub struct PacketAnalysis {
     pub timestamp: DateTime<FixedOffset>,
     pub events: Vec<Option<Event>>,
     pub gps_correlation: Option<super::gps_correlation::GpsCorrelation>,
+    /// Which SIM the analyzed message was for, if the modem said
+    pub subscription_id: Option<u32>,
 }
 
 #[derive(Serialize, Debug)]
@@ -179,6 +219,21 @@ impl Harness {
         if analyzer_config.null_cipher {
             harness.add_analyzer(Box::new(NullCipherAnalyzer {}));
         }
+        if analyzer_config.auth_skipped {
+            harness.add_analyzer(Box::new(AuthSkippedAnalyzer::new()));
+        }
+        if analyzer_config.emm_reject {
+            harness.add_analyzer(Box::new(EmmRejectAnalyzer::new()));
+        }
+        if analyzer_config.identity_requested {
+            harness.add_analyzer(Box::new(IdentityRequestedAnalyzer::new(
+                analyzer_config.identity_requested_imei_packet_threshold,
+                analyzer_config.identity_requested_tmsi_packet_threshold,
+            )));
+        }
+        if analyzer_config.imsi_provided {
+            harness.add_analyzer(Box::new(ImsiProvidedAnalyzer::new()));
+        }
         if analyzer_config.cellular_network {
             harness.add_qmdl_analyzer(Box::new(CellularNetworkAnalyzer::new()));
         }
@@ -194,13 +249,13 @@ impl Harness {
         self.qmdl_analyzers.push(analyzer);
     }
 
-    pub fn analyze_qmdl_messages(&mut self, container: MessagesContainer) -> AnalysisRow {
+    pub fn analyze_qmdl_messages(&mut self, container: &MessagesContainer) -> AnalysisRow {
         let mut row = AnalysisRow {
             timestamp: chrono::Local::now().fixed_offset(),
             skipped_message_reasons: Vec::new(),
             analysis: Vec::new(),
         };
-        for maybe_qmdl_message in container.into_messages() {
+        for maybe_qmdl_message in container.messages() {
             let qmdl_message = match maybe_qmdl_message {
                 Ok(msg) => msg,
                 Err(err) => {
@@ -209,6 +264,13 @@ impl Harness {
                 }
             };
 
+            let subscription_id = match &qmdl_message {
+                Message::Log {
+                    subscription_id, ..
+                } => *subscription_id,
+                _ => None,
+            };
+
             // Run QMDL-level analyzers first
             let qmdl_analysis_result = self.analyze_qmdl_message(&qmdl_message);
             if qmdl_analysis_result.iter().any(Option::is_some) {
@@ -216,9 +278,29 @@ impl Harness {
                     timestamp: chrono::Local::now().fixed_offset(),
                     events: qmdl_analysis_result,
                     gps_correlation: None, // Will be filled by GPS correlator
+                    subscription_id,
                 });
             }
 
+            // State changes only ever come in as event reports, never as
+            // something GSMTAP can represent
+            if let Message::EventReport { events, .. } = &qmdl_message {
+                for (timestamp, change) in diag_events::state_changes(events) {
+                    let analysis_result = self.analyze_state_change(&change);
+                    if analysis_result.iter().any(Option::is_some) {
+                        row.analysis.push(PacketAnalysis {
+                            timestamp: timestamp
+                                .map(|ts| ts.to_datetime())
+                                .unwrap_or(row.timestamp),
+                            events: analysis_result,
+                            gps_correlation: None, // Will be filled by GPS correlator
+                            subscription_id,
+                        });
+                    }
+                }
+                continue;
+            }
+
             // Then run traditional GSMTAP-based analyzers
             let gsmtap_message = match gsmtap_parser::parse(qmdl_message) {
                 Ok(msg) => msg,
@@ -240,12 +322,23 @@ impl Harness {
                 }
             };
 
+            let qmdl_analysis_result = self.analyze_qmdl_information_element(&element);
+            if qmdl_analysis_result.iter().any(Option::is_some) {
+                row.analysis.push(PacketAnalysis {
+                    timestamp: timestamp.to_datetime(),
+                    events: qmdl_analysis_result,
+                    gps_correlation: None, // Will be filled by GPS correlator
+                    subscription_id,
+                });
+            }
+
             let analysis_result = self.analyze_information_element(&element);
             if analysis_result.iter().any(Option::is_some) {
                 row.analysis.push(PacketAnalysis {
                     timestamp: timestamp.to_datetime(),
                     events: analysis_result,
                     gps_correlation: None, // Will be filled by GPS correlator
+                    subscription_id,
                 });
             }
         }
@@ -259,6 +352,13 @@ impl Harness {
             .collect()
     }
 
+    fn analyze_state_change(&mut self, change: &StateChange) -> Vec<Option<Event>> {
+        self.analyzers
+            .iter_mut()
+            .map(|analyzer| analyzer.analyze_state_change(change))
+            .collect()
+    }
+
     fn analyze_qmdl_message(&mut self, msg: &crate::diag::Message) -> Vec<Option<Event>> {
         self.qmdl_analyzers
             .iter_mut()
@@ -266,6 +366,13 @@ impl Harness {
             .collect()
     }
 
+    fn analyze_qmdl_information_element(&mut self, ie: &InformationElement) -> Vec<Option<Event>> {
+        self.qmdl_analyzers
+            .iter_mut()
+            .map(|analyzer| analyzer.analyze_information_element(ie))
+            .collect()
+    }
+
     pub fn get_names(&self) -> Vec<Cow<'_, str>> {
         let mut names = self.analyzers
             .iter()
@@ -0,0 +1,410 @@
+use std::borrow::Cow;
+
+use telcom_parser::lte_nas::{EMM_CAUSE_SYNCH_FAILURE, EmmMessage, SecurityHeaderType};
+use telcom_parser::lte_rrc::{
+    DL_DCCH_MessageType, DL_DCCH_MessageType_c1, RRCConnectionReconfigurationCriticalExtensions,
+    RRCConnectionReconfigurationCriticalExtensions_c1,
+};
+
+use super::analyzer::{Analyzer, Event, EventType, Severity};
+use super::information_element::{InformationElement, LteInformationElement};
+use crate::diag_events::{LteRrcState, StateChange};
+
+/// How many synch failures in a row it takes before we call it a loop. A
+/// single one is normal after the USIM's sequence number drifts, and the
+/// network resynchronizes on the next Authentication Request.
+const SYNC_FAILURE_LOOP_THRESHOLD: usize = 3;
+
+/// The EMM procedure that a connection was set up for
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Procedure {
+    Attach,
+    TrackingAreaUpdate,
+    ServiceRequest,
+}
+
+impl std::fmt::Display for Procedure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Procedure::Attach => write!(f, "attach"),
+            Procedure::TrackingAreaUpdate => write!(f, "tracking area update"),
+            Procedure::ServiceRequest => write!(f, "service request"),
+        }
+    }
+}
+
+// Many IMSI catchers don't have the subscriber's keys, so they can't run AKA
+// and skip straight to setting up security or accepting the attach
+pub struct AuthSkippedAnalyzer {
+    procedure: Option<Procedure>,
+    /// Whether the network has sent a message protected with the security
+    /// context already in use during the procedure. If it has, the phone and
+    /// network share a security context, and it's normal for the network not
+    /// to authenticate again. How the phone protected its own request says
+    /// nothing about what the network knows.
+    protected: bool,
+    authenticated: bool,
+    /// Synch failures since the phone last accepted an authentication. Kept
+    /// across procedures, as a phone stuck in a loop gives up on each attach
+    /// and starts another.
+    sync_failures: usize,
+    reported: bool,
+}
+
+impl Default for AuthSkippedAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuthSkippedAnalyzer {
+    pub fn new() -> Self {
+        Self {
+            procedure: None,
+            protected: false,
+            authenticated: false,
+            sync_failures: 0,
+            reported: false,
+        }
+    }
+
+    fn start_procedure(&mut self, procedure: Procedure) {
+        *self = Self {
+            procedure: Some(procedure),
+            sync_failures: self.sync_failures,
+            ..Self::new()
+        };
+    }
+
+    /// Notes how a message from the network was protected. Only one protected
+    /// with the context already in use counts: a Security Mode Command is
+    /// protected with the new context it's taking into use, which is only
+    /// known to be the network's after an authentication.
+    fn note_downlink(&mut self, security_header: Option<SecurityHeaderType>) {
+        if matches!(
+            security_header,
+            Some(
+                SecurityHeaderType::IntegrityProtected
+                    | SecurityHeaderType::IntegrityProtectedAndCiphered
+            )
+        ) {
+            self.protected = true;
+        }
+    }
+
+    /// Checks a message from the network, or for data bearers, which aren't
+    /// NAS messages, whatever the network has sent so far
+    fn check_authenticated(
+        &mut self,
+        what: &str,
+        security_header: Option<SecurityHeaderType>,
+    ) -> Option<Event> {
+        let procedure = self.procedure?;
+        self.note_downlink(security_header);
+        if self.protected || self.authenticated || self.reported {
+            return None;
+        }
+        self.reported = true;
+        Some(Event {
+            event_type: EventType::QualitativeWarning {
+                severity: Severity::High,
+            },
+            message: format!(
+                "{what} during a {procedure} without the network authenticating the phone or \
+                using an existing security context first. Emergency calls without a SIM can \
+                cause this too"
+            ),
+        })
+    }
+
+    fn analyze_emm(
+        &mut self,
+        emm: &EmmMessage,
+        security_header: Option<SecurityHeaderType>,
+    ) -> Option<Event> {
+        match emm {
+            EmmMessage::AttachRequest(_) => self.start_procedure(Procedure::Attach),
+            EmmMessage::TrackingAreaUpdateRequest => {
+                self.start_procedure(Procedure::TrackingAreaUpdate)
+            }
+            EmmMessage::ServiceRequest | EmmMessage::ExtendedServiceRequest => {
+                self.start_procedure(Procedure::ServiceRequest)
+            }
+            EmmMessage::TrackingAreaUpdateAccept(_) => self.note_downlink(security_header),
+            EmmMessage::AuthenticationResponse => {
+                self.authenticated = true;
+                self.sync_failures = 0;
+            }
+            EmmMessage::AuthenticationReject => {
+                self.procedure = None;
+                return Some(Event {
+                    event_type: EventType::QualitativeWarning {
+                        severity: Severity::High,
+                    },
+                    message: "Network sent an Authentication Reject, which makes the phone \
+                        treat its SIM as invalid until it's restarted"
+                        .to_string(),
+                });
+            }
+            EmmMessage::AuthenticationFailure(failure)
+                if failure.emm_cause == EMM_CAUSE_SYNCH_FAILURE =>
+            {
+                self.sync_failures += 1;
+                if self.sync_failures == SYNC_FAILURE_LOOP_THRESHOLD {
+                    return Some(Event {
+                        event_type: EventType::QualitativeWarning {
+                            severity: Severity::Medium,
+                        },
+                        message: format!(
+                            "Phone rejected {SYNC_FAILURE_LOOP_THRESHOLD} authentication \
+                            requests in a row for being out of sequence, which can mean the \
+                            network is replaying old authentication vectors"
+                        ),
+                    });
+                }
+            }
+            EmmMessage::SecurityModeCommand(_) => {
+                return self.check_authenticated("NAS Security Mode Command", security_header);
+            }
+            EmmMessage::AttachAccept(_) => {
+                return self.check_authenticated("Attach Accept", security_header);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn analyze_dl_dcch(&mut self, message: &DL_DCCH_MessageType) -> Option<Event> {
+        let DL_DCCH_MessageType::C1(c1) = message else {
+            return None;
+        };
+        match c1 {
+            DL_DCCH_MessageType_c1::RrcConnectionRelease(_) => {
+                self.procedure = None;
+                None
+            }
+            DL_DCCH_MessageType_c1::RrcConnectionReconfiguration(reconfiguration) => {
+                let RRCConnectionReconfigurationCriticalExtensions::C1(
+                    RRCConnectionReconfigurationCriticalExtensions_c1::RrcConnectionReconfiguration_r8(
+                        r8,
+                    ),
+                ) = &reconfiguration.critical_extensions
+                else {
+                    return None;
+                };
+                let adds_bearers = r8
+                    .radio_resource_config_dedicated
+                    .as_ref()
+                    .is_some_and(|config| config.drb_to_add_mod_list.is_some());
+                if adds_bearers {
+                    self.check_authenticated("Data bearers set up", None)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Analyzer for AuthSkippedAnalyzer {
+    fn get_name(&self) -> Cow<str> {
+        Cow::from("Authentication Skipped")
+    }
+
+    fn get_description(&self) -> Cow<str> {
+        Cow::from(
+            "Tests whether the network sets up security, accepts an attach or sets up data \
+            bearers without authenticating the phone first, and reports Authentication \
+            Rejects and repeated authentication synch failures.",
+        )
+    }
+
+    fn analyze_information_element(&mut self, ie: &InformationElement) -> Option<Event> {
+        let InformationElement::LTE(lte_ie) = ie else {
+            return None;
+        };
+        match &**lte_ie {
+            LteInformationElement::NAS(nas) => {
+                let emm = nas.emm.as_ref()?;
+                let security_header = nas
+                    .security_header
+                    .as_ref()
+                    .map(|header| header.security_header_type);
+                self.analyze_emm(emm, security_header)
+            }
+            LteInformationElement::DlDcch(msg) => self.analyze_dl_dcch(&msg.message),
+            _ => None,
+        }
+    }
+
+    fn analyze_state_change(&mut self, change: &StateChange) -> Option<Event> {
+        // once the connection's gone, so is the procedure it was for
+        match change {
+            StateChange::LteRrcState(LteRrcState::IdleCamped | LteRrcState::IdleNotCamped)
+            | StateChange::LteRrcOutOfService
+            | StateChange::LteRrcRadioLinkFailure => self.procedure = None,
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use telcom_parser::lte_nas::{
+        AttachAccept, AttachRequest, EmmCause, EpsMobileIdentity, SecurityModeCommand,
+        TrackingAreaUpdateAccept,
+    };
+
+    // how the network protects messages once it's set up security, and how
+    // it protects the Security Mode Command that sets it up
+    const CURRENT_CONTEXT: Option<SecurityHeaderType> =
+        Some(SecurityHeaderType::IntegrityProtectedAndCiphered);
+    const NEW_CONTEXT: Option<SecurityHeaderType> =
+        Some(SecurityHeaderType::IntegrityProtectedWithNewContext);
+
+    fn attach_request() -> EmmMessage {
+        EmmMessage::AttachRequest(AttachRequest {
+            identity: EpsMobileIdentity::Imsi("001010123456789".to_string()),
+        })
+    }
+
+    fn attach_accept() -> EmmMessage {
+        EmmMessage::AttachAccept(AttachAccept { guti: None })
+    }
+
+    fn smc() -> EmmMessage {
+        EmmMessage::SecurityModeCommand(SecurityModeCommand {
+            selected_algorithms: 0x22.into(),
+        })
+    }
+
+    #[test]
+    fn test_authenticated_attach() {
+        let mut analyzer = AuthSkippedAnalyzer::new();
+        assert!(analyzer.analyze_emm(&attach_request(), None).is_none());
+        assert!(
+            analyzer
+                .analyze_emm(&EmmMessage::AuthenticationRequest, None)
+                .is_none()
+        );
+        assert!(
+            analyzer
+                .analyze_emm(&EmmMessage::AuthenticationResponse, None)
+                .is_none()
+        );
+        assert!(analyzer.analyze_emm(&smc(), NEW_CONTEXT).is_none());
+    }
+
+    #[test]
+    fn test_unauthenticated_attach() {
+        let mut analyzer = AuthSkippedAnalyzer::new();
+        analyzer.analyze_emm(&attach_request(), None);
+        assert!(analyzer.analyze_emm(&smc(), NEW_CONTEXT).is_some());
+        // only reported once per procedure
+        assert!(analyzer.analyze_emm(&attach_accept(), None).is_none());
+    }
+
+    #[test]
+    fn test_existing_security_context() {
+        let mut analyzer = AuthSkippedAnalyzer::new();
+        analyzer.analyze_emm(&attach_request(), None);
+        assert!(
+            analyzer
+                .analyze_emm(&attach_accept(), CURRENT_CONTEXT)
+                .is_none()
+        );
+
+        let mut analyzer = AuthSkippedAnalyzer::new();
+        analyzer.analyze_emm(&EmmMessage::TrackingAreaUpdateRequest, CURRENT_CONTEXT);
+        let accept = EmmMessage::TrackingAreaUpdateAccept(TrackingAreaUpdateAccept { guti: None });
+        analyzer.analyze_emm(&accept, CURRENT_CONTEXT);
+        assert!(
+            analyzer
+                .check_authenticated("Data bearers set up", None)
+                .is_none()
+        );
+
+        // nor do we know what happened before we started listening
+        let mut analyzer = AuthSkippedAnalyzer::new();
+        assert!(analyzer.analyze_emm(&smc(), NEW_CONTEXT).is_none());
+    }
+
+    #[test]
+    fn test_protected_request() {
+        // the phone protects its requests whenever it has a security
+        // context, whether or not the network has the keys to it
+        let mut analyzer = AuthSkippedAnalyzer::new();
+        analyzer.analyze_emm(&EmmMessage::TrackingAreaUpdateRequest, CURRENT_CONTEXT);
+        assert!(analyzer.analyze_emm(&smc(), NEW_CONTEXT).is_some());
+
+        let mut analyzer = AuthSkippedAnalyzer::new();
+        analyzer.analyze_emm(&EmmMessage::ServiceRequest, None);
+        assert!(analyzer.analyze_emm(&smc(), None).is_some());
+
+        let mut analyzer = AuthSkippedAnalyzer::new();
+        analyzer.analyze_emm(&EmmMessage::ServiceRequest, None);
+        assert!(
+            analyzer
+                .check_authenticated("Data bearers set up", None)
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_connection_lost() {
+        let mut analyzer = AuthSkippedAnalyzer::new();
+        analyzer.analyze_emm(&attach_request(), None);
+        analyzer.analyze_state_change(&StateChange::LteRrcState(LteRrcState::IdleCamped));
+        assert!(analyzer.analyze_emm(&smc(), NEW_CONTEXT).is_none());
+    }
+
+    #[test]
+    fn test_sync_failure_loop() {
+        let mut analyzer = AuthSkippedAnalyzer::new();
+        let failure = EmmMessage::AuthenticationFailure(EmmCause {
+            emm_cause: EMM_CAUSE_SYNCH_FAILURE,
+        });
+        analyzer.analyze_emm(&attach_request(), None);
+        for _ in 1..SYNC_FAILURE_LOOP_THRESHOLD {
+            assert!(analyzer.analyze_emm(&failure, None).is_none());
+        }
+        assert!(analyzer.analyze_emm(&failure, None).is_some());
+    }
+
+    #[test]
+    fn test_sync_failure_loop_across_attaches() {
+        let mut analyzer = AuthSkippedAnalyzer::new();
+        let failure = EmmMessage::AuthenticationFailure(EmmCause {
+            emm_cause: EMM_CAUSE_SYNCH_FAILURE,
+        });
+        // the phone gives up on each attach after a single failure, and
+        // tries again
+        for _ in 1..SYNC_FAILURE_LOOP_THRESHOLD {
+            analyzer.analyze_emm(&attach_request(), None);
+            assert!(analyzer.analyze_emm(&failure, None).is_none());
+        }
+        analyzer.analyze_emm(&attach_request(), None);
+        assert!(analyzer.analyze_emm(&failure, None).is_some());
+
+        // a successful authentication ends the loop
+        analyzer.analyze_emm(&EmmMessage::AuthenticationResponse, None);
+        for _ in 1..SYNC_FAILURE_LOOP_THRESHOLD {
+            analyzer.analyze_emm(&attach_request(), None);
+            assert!(analyzer.analyze_emm(&failure, None).is_none());
+        }
+    }
+
+    #[test]
+    fn test_authentication_reject() {
+        let mut analyzer = AuthSkippedAnalyzer::new();
+        analyzer.analyze_emm(&attach_request(), None);
+        assert!(
+            analyzer
+                .analyze_emm(&EmmMessage::AuthenticationReject, None)
+                .is_some()
+        );
+    }
+}
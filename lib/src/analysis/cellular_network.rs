@@ -8,9 +8,15 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::path::Path;
+use chrono::{DateTime, FixedOffset};
 use serde::{Deserialize, Serialize};
+use telcom_parser::lte_rrc::{
+    BCCH_DL_SCH_Message, BCCH_DL_SCH_MessageType, BCCH_DL_SCH_MessageType_c1,
+};
 
 use super::analyzer::{QmdlAnalyzer, Event, EventType};
+use super::information_element::{InformationElement, LteInformationElement};
+use crate::diag::{LogBody, Message};
 use crate::cellular_info::{
     CellularNetworkInfo, LocationInfo, PlmnInfo, RadioAccessTechnology, SignalInfo,
     NeighborCellInfo, CellularInfoExtractor,
@@ -180,6 +186,10 @@ pub struct CellularNetworkAnalyzer {
     
     // Path to CSV directory (configurable)
     csv_directory: Option<String>,
+
+    // EARFCN, PCI and time of the LTE RRC message being analyzed, so a SIB1
+    // it decodes to can be tied to the cell that broadcast it
+    lte_rrc_cell: Option<(u32, u16, DateTime<FixedOffset>)>,
 }
 
 impl Default for CellularNetworkAnalyzer {
@@ -202,6 +212,7 @@ impl CellularNetworkAnalyzer {
             current_location: None,
             info_count: 0,
             csv_directory: Some("/data/rayhunter/captures".to_string()), // Save for offline analysis
+            lte_rrc_cell: None,
         }
     }
 
@@ -356,15 +367,42 @@ impl QmdlAnalyzer for CellularNetworkAnalyzer {
     }
 
     fn analyze_qmdl_message(&mut self, qmdl_message: &crate::diag::Message) -> Option<Event> {
-        // Use the enhanced parser to extract both GSMTAP and cellular info
-        if let Ok(Some((_timestamp, _gsmtap_msg, cellular_info))) = crate::gsmtap_parser::parse_with_cellular_info(qmdl_message.clone(), &mut self.extractor) {
-            if let Some(cellular_info) = cellular_info {
-                self.process_cellular_info(&cellular_info)
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+        self.lte_rrc_cell = match qmdl_message {
+            Message::Log {
+                timestamp,
+                body: LogBody::LteRrcOtaMessage { packet, .. },
+                ..
+            } => Some((
+                packet.get_earfcn(),
+                packet.get_phy_cell_id(),
+                timestamp.to_datetime(),
+            )),
+            _ => None,
+        };
+        let cellular_info =
+            crate::gsmtap_parser::parse_cellular_info(qmdl_message, &mut self.extractor)?;
+        self.process_cellular_info(&cellular_info)
+    }
+
+    // SIB1 is the only place the PLMN, TAC and cell identity of an LTE cell
+    // are broadcast, so take them from the harness's decode of it
+    fn analyze_information_element(&mut self, ie: &InformationElement) -> Option<Event> {
+        let (earfcn, pci, timestamp) = self.lte_rrc_cell.take()?;
+        let InformationElement::LTE(lte_ie) = ie else {
+            return None;
+        };
+        let LteInformationElement::BcchDlSch(BCCH_DL_SCH_Message {
+            message:
+                BCCH_DL_SCH_MessageType::C1(BCCH_DL_SCH_MessageType_c1::SystemInformationBlockType1(
+                    sib1,
+                )),
+        }) = &**lte_ie
+        else {
+            return None;
+        };
+        let cellular_info = self
+            .extractor
+            .extract_from_lte_sib1(earfcn, pci, sib1, timestamp)?;
+        self.process_cellular_info(&cellular_info)
     }
 }
@@ -0,0 +1,271 @@
+use std::borrow::Cow;
+
+use telcom_parser::lte_nas::{EmmCause, EmmMessage};
+
+use super::analyzer::{Analyzer, Event, EventType, Severity};
+use super::information_element::{InformationElement, LteInformationElement};
+
+/// How many messages after a reject we'll still blame it for the phone
+/// showing up on 2G or 3G. We don't get timestamps here, so this stands in
+/// for "shortly afterwards".
+const RESELECTION_WINDOW: usize = 150;
+
+/// An EMM cause that IMSI catchers use to push phones off LTE or lock them
+/// out of the network (TS 24.301 Annex A)
+struct RejectCause {
+    cause: u8,
+    name: &'static str,
+    explanation: &'static str,
+    severity: Severity,
+}
+
+const REJECT_CAUSES: &[RejectCause] = &[
+    RejectCause {
+        cause: 3,
+        name: "Illegal UE",
+        explanation: "the phone treats its SIM as invalid for all networks until it's restarted",
+        severity: Severity::High,
+    },
+    RejectCause {
+        cause: 6,
+        name: "Illegal ME",
+        explanation: "the phone treats its SIM as invalid for all networks until it's restarted",
+        severity: Severity::High,
+    },
+    RejectCause {
+        cause: 7,
+        name: "EPS services not allowed",
+        explanation: "the phone stops using LTE until it's restarted, and falls back to 2G/3G",
+        severity: Severity::High,
+    },
+    RejectCause {
+        cause: 8,
+        name: "EPS services and non-EPS services not allowed",
+        explanation: "the phone treats its SIM as invalid for all networks until it's restarted",
+        severity: Severity::High,
+    },
+    RejectCause {
+        cause: 11,
+        name: "PLMN not allowed",
+        explanation: "the phone adds the network to its forbidden list and looks for another one",
+        severity: Severity::Medium,
+    },
+    RejectCause {
+        cause: 12,
+        name: "Tracking area not allowed",
+        explanation: "the phone stops using cells in this tracking area",
+        severity: Severity::Medium,
+    },
+    RejectCause {
+        cause: 13,
+        name: "Roaming not allowed in this tracking area",
+        explanation: "the phone looks for another network in this tracking area",
+        severity: Severity::Medium,
+    },
+    RejectCause {
+        cause: 14,
+        name: "EPS services not allowed in this PLMN",
+        explanation: "the phone stops using LTE on this network, and falls back to 2G/3G",
+        severity: Severity::High,
+    },
+    RejectCause {
+        cause: 15,
+        name: "No suitable cells in tracking area",
+        explanation: "the phone looks for another tracking area, which real networks also use \
+            to steer phones",
+        severity: Severity::Low,
+    },
+    RejectCause {
+        cause: 22,
+        name: "Congestion",
+        explanation: "the phone backs off before trying again, which real networks also do \
+            under load",
+        severity: Severity::Low,
+    },
+];
+
+fn reject_cause(cause: u8) -> Option<&'static RejectCause> {
+    REJECT_CAUSES.iter().find(|c| c.cause == cause)
+}
+
+/// A reject we're waiting to see the phone leave LTE after
+struct PendingReject {
+    kind: &'static str,
+    cause: &'static RejectCause,
+    packet_num: usize,
+}
+
+// IMSI catchers reject the phone's attach, tracking area update or service
+// request with causes that take it off LTE, or off the network entirely, so
+// it'll connect to a 2G/3G base station they control
+pub struct EmmRejectAnalyzer {
+    packet_num: usize,
+    pending: Option<PendingReject>,
+}
+
+impl Default for EmmRejectAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EmmRejectAnalyzer {
+    pub fn new() -> Self {
+        Self {
+            packet_num: 0,
+            pending: None,
+        }
+    }
+
+    fn analyze_reject(&mut self, kind: &'static str, reject: &EmmCause) -> Option<Event> {
+        let Some(cause) = reject_cause(reject.emm_cause) else {
+            return Some(Event {
+                event_type: EventType::Informational,
+                message: format!("{kind} with EMM cause #{}", reject.emm_cause),
+            });
+        };
+        self.pending = Some(PendingReject {
+            kind,
+            cause,
+            packet_num: self.packet_num,
+        });
+        Some(Event {
+            event_type: EventType::QualitativeWarning {
+                severity: cause.severity.clone(),
+            },
+            message: format!(
+                "{kind} with EMM cause #{} ({}), so {}",
+                cause.cause, cause.name, cause.explanation
+            ),
+        })
+    }
+
+    fn analyze_emm(&mut self, emm: &EmmMessage) -> Option<Event> {
+        match emm {
+            EmmMessage::AttachReject(reject) => self.analyze_reject("Attach Reject", reject),
+            EmmMessage::TrackingAreaUpdateReject(reject) => {
+                self.analyze_reject("Tracking Area Update Reject", reject)
+            }
+            EmmMessage::ServiceReject(reject) => self.analyze_reject("Service Reject", reject),
+            // the phone got back onto LTE, so whatever it does next isn't
+            // down to the reject
+            EmmMessage::AttachAccept(_) => {
+                self.pending = None;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn check_reselection(&mut self, rat: &str) -> Option<Event> {
+        let pending = self.pending.take()?;
+        if self.packet_num - pending.packet_num > RESELECTION_WINDOW {
+            return None;
+        }
+        Some(Event {
+            event_type: EventType::QualitativeWarning {
+                severity: Severity::High,
+            },
+            message: format!(
+                "Phone moved to {rat} right after a {} with EMM cause #{} ({})",
+                pending.kind, pending.cause.cause, pending.cause.name
+            ),
+        })
+    }
+}
+
+impl Analyzer for EmmRejectAnalyzer {
+    fn get_name(&self) -> Cow<str> {
+        Cow::from("EMM Reject Cause")
+    }
+
+    fn get_description(&self) -> Cow<str> {
+        Cow::from(
+            "Tests whether the network rejects an attach, tracking area update or service \
+            request with a cause that takes the phone off LTE or invalidates its SIM, and \
+            whether the phone moves to 2G or 3G right afterwards.",
+        )
+    }
+
+    fn analyze_information_element(&mut self, ie: &InformationElement) -> Option<Event> {
+        self.packet_num += 1;
+        match ie {
+            InformationElement::LTE(lte_ie) => {
+                let LteInformationElement::NAS(nas) = &**lte_ie else {
+                    return None;
+                };
+                self.analyze_emm(nas.emm.as_ref()?)
+            }
+            InformationElement::GSM(_) => self.check_reselection("2G"),
+            InformationElement::UMTS(_) => self.check_reselection("3G"),
+            InformationElement::FiveG(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use telcom_parser::lte_nas::AttachAccept;
+
+    fn attach_reject(emm_cause: u8) -> EmmMessage {
+        EmmMessage::AttachReject(EmmCause { emm_cause })
+    }
+
+    #[test]
+    fn test_reject_causes() {
+        let mut analyzer = EmmRejectAnalyzer::new();
+        let event = analyzer.analyze_emm(&attach_reject(7)).unwrap();
+        assert!(matches!(
+            event.event_type,
+            EventType::QualitativeWarning {
+                severity: Severity::High
+            }
+        ));
+        assert!(event.message.contains("EPS services not allowed"));
+
+        let event = analyzer
+            .analyze_emm(&EmmMessage::ServiceReject(EmmCause { emm_cause: 22 }))
+            .unwrap();
+        assert!(matches!(
+            event.event_type,
+            EventType::QualitativeWarning {
+                severity: Severity::Low
+            }
+        ));
+
+        // protocol errors aren't something IMSI catchers use
+        let event = analyzer.analyze_emm(&attach_reject(111)).unwrap();
+        assert!(matches!(event.event_type, EventType::Informational));
+    }
+
+    #[test]
+    fn test_reselection_after_reject() {
+        let mut analyzer = EmmRejectAnalyzer::new();
+        assert!(analyzer.check_reselection("2G").is_none());
+
+        analyzer.analyze_emm(&EmmMessage::TrackingAreaUpdateReject(EmmCause {
+            emm_cause: 14,
+        }));
+        let event = analyzer.check_reselection("3G").unwrap();
+        assert!(event.message.contains("3G"));
+        // only reported once per reject
+        assert!(analyzer.check_reselection("3G").is_none());
+    }
+
+    #[test]
+    fn test_reselection_long_after_reject() {
+        let mut analyzer = EmmRejectAnalyzer::new();
+        analyzer.analyze_emm(&attach_reject(7));
+        analyzer.packet_num += RESELECTION_WINDOW + 1;
+        assert!(analyzer.check_reselection("2G").is_none());
+    }
+
+    #[test]
+    fn test_recovered_after_reject() {
+        let mut analyzer = EmmRejectAnalyzer::new();
+        analyzer.analyze_emm(&attach_reject(15));
+        analyzer.analyze_emm(&EmmMessage::AttachAccept(AttachAccept { guti: None }));
+        assert!(analyzer.check_reselection("2G").is_none());
+    }
+}
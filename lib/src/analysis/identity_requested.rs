@@ -0,0 +1,165 @@
+use std::borrow::Cow;
+
+use telcom_parser::lte_nas::{EmmMessage, IdentityType};
+
+use super::analyzer::{Analyzer, Event, Severity};
+use super::information_element::{InformationElement, LteInformationElement};
+use super::util::identity_request_event;
+
+// Networks rarely ask for the phone's IMEI outside of attach, while IMSI
+// catchers ask for it to fingerprint the handset. IMSI requests are covered by
+// ImsiRequestedAnalyzer.
+pub struct IdentityRequestedAnalyzer {
+    packet_num: usize,
+    imei_packet_threshold: usize,
+    tmsi_packet_threshold: usize,
+}
+
+impl IdentityRequestedAnalyzer {
+    /// IMEI or IMEISV requests within the first `imei_packet_threshold`
+    /// packets of an analysis, and TMSI requests within the first
+    /// `tmsi_packet_threshold`, are reported with a lower severity, since
+    /// they're likely from the phone attaching after being turned on
+    pub fn new(imei_packet_threshold: usize, tmsi_packet_threshold: usize) -> Self {
+        Self {
+            packet_num: 0,
+            imei_packet_threshold,
+            tmsi_packet_threshold,
+        }
+    }
+
+    fn imei_event(&self, what: &str, severity: Severity, early_severity: Severity) -> Event {
+        identity_request_event(
+            what,
+            self.packet_num,
+            self.imei_packet_threshold,
+            severity,
+            early_severity,
+        )
+    }
+
+    fn analyze_emm(&self, emm: &EmmMessage) -> Option<Event> {
+        match emm {
+            EmmMessage::IdentityRequest(request) => match request.identity_type {
+                IdentityType::Imei => Some(self.imei_event(
+                    "NAS IMEI identity request",
+                    Severity::High,
+                    Severity::Medium,
+                )),
+                IdentityType::Imeisv => Some(self.imei_event(
+                    "NAS IMEISV identity request",
+                    Severity::High,
+                    Severity::Medium,
+                )),
+                IdentityType::Tmsi => Some(identity_request_event(
+                    "NAS TMSI identity request",
+                    self.packet_num,
+                    self.tmsi_packet_threshold,
+                    Severity::Medium,
+                    Severity::Low,
+                )),
+                _ => None,
+            },
+            // real networks routinely ask for the IMEISV when setting up
+            // security during attach, so this is a weaker signal
+            EmmMessage::SecurityModeComplete(complete) if complete.includes_imeisv => {
+                Some(self.imei_event(
+                    "IMEISV sent in a NAS Security Mode Complete",
+                    Severity::Medium,
+                    Severity::Low,
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Analyzer for IdentityRequestedAnalyzer {
+    fn get_name(&self) -> Cow<str> {
+        Cow::from("Identity Requested")
+    }
+
+    fn get_description(&self) -> Cow<str> {
+        Cow::from(
+            "Tests whether the network asks for the phone's IMEI, IMEISV or TMSI in a NAS \
+            Identity Request, or the phone sends its IMEISV in a NAS Security Mode Complete.",
+        )
+    }
+
+    fn analyze_information_element(&mut self, ie: &InformationElement) -> Option<Event> {
+        self.packet_num += 1;
+        let InformationElement::LTE(lte_ie) = ie else {
+            return None;
+        };
+        let LteInformationElement::NAS(nas) = &**lte_ie else {
+            return None;
+        };
+        self.analyze_emm(nas.emm.as_ref()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::analyzer::EventType;
+    use telcom_parser::lte_nas::{IdentityRequest, SecurityModeComplete};
+
+    fn identity_request(identity_type: IdentityType) -> EmmMessage {
+        EmmMessage::IdentityRequest(IdentityRequest { identity_type })
+    }
+
+    fn severity(event: Option<Event>) -> Option<Severity> {
+        match event?.event_type {
+            EventType::QualitativeWarning { severity } => Some(severity),
+            EventType::Informational => None,
+        }
+    }
+
+    #[test]
+    fn test_identity_requests() {
+        let mut analyzer = IdentityRequestedAnalyzer::new(10, 20);
+        let imei = identity_request(IdentityType::Imei);
+        let tmsi = identity_request(IdentityType::Tmsi);
+        assert!(matches!(
+            severity(analyzer.analyze_emm(&imei)),
+            Some(Severity::Medium)
+        ));
+        analyzer.packet_num = 10;
+        assert!(matches!(
+            severity(analyzer.analyze_emm(&imei)),
+            Some(Severity::High)
+        ));
+        // TMSI requests have their own threshold
+        assert!(matches!(
+            severity(analyzer.analyze_emm(&tmsi)),
+            Some(Severity::Low)
+        ));
+        analyzer.packet_num = 20;
+        assert!(matches!(
+            severity(analyzer.analyze_emm(&tmsi)),
+            Some(Severity::Medium)
+        ));
+        // left to ImsiRequestedAnalyzer
+        assert!(
+            analyzer
+                .analyze_emm(&identity_request(IdentityType::Imsi))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_security_mode_complete() {
+        let analyzer = IdentityRequestedAnalyzer::new(0, 0);
+        let with_imeisv = EmmMessage::SecurityModeComplete(SecurityModeComplete {
+            includes_imeisv: true,
+        });
+        assert!(matches!(
+            severity(analyzer.analyze_emm(&with_imeisv)),
+            Some(Severity::Medium)
+        ));
+        let without_imeisv = EmmMessage::SecurityModeComplete(SecurityModeComplete {
+            includes_imeisv: false,
+        });
+        assert!(analyzer.analyze_emm(&without_imeisv).is_none());
+    }
+}
@@ -1,11 +1,238 @@
 use std::borrow::Cow;
+use std::collections::VecDeque;
 
+use telcom_parser::gsm_nas::MobileIdentity;
+use telcom_parser::lte_nas::{EmmMessage, EpsMobileIdentity, Guti};
 use telcom_parser::lte_rrc::{PCCH_MessageType, PCCH_MessageType_c1, PagingUE_Identity};
 
 use super::analyzer::{Analyzer, Event, EventType, Severity};
 use super::information_element::{InformationElement, LteInformationElement};
+use crate::diag_events::StateChange;
+use crate::util::bits_to_u32;
 
-pub struct ImsiProvidedAnalyzer {}
+/// How many messages after an IMSI page we'll still count the phone
+/// attaching with its IMSI as its response to that page
+const IMSI_PAGE_RESPONSE_WINDOW: usize = 150;
+
+/// How many IMSI pages we keep around waiting for the phone to respond, so a
+/// cell paging lots of IMSIs can't grow our state without bound
+const MAX_PENDING_IMSI_PAGES: usize = 16;
+
+/// How many of the cell's most recent paging messages we look at, and how
+/// many records each of them has to carry on average for us to call it a
+/// paging storm. A paging message holds at most 16 records, and busy cells
+/// usually only fill a few of them.
+const PAGING_STORM_WINDOW: usize = 32;
+const PAGING_STORM_RECORDS_PER_MESSAGE: usize = 8;
+
+/// The temporary identity the network pages a phone by, made up of the MME
+/// code and M-TMSI of its GUTI (TS 23.003 2.9)
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct STmsi {
+    mme_code: u8,
+    m_tmsi: u32,
+}
+
+impl From<&Guti> for STmsi {
+    fn from(guti: &Guti) -> Self {
+        Self {
+            mme_code: guti.mme_code,
+            m_tmsi: guti.m_tmsi,
+        }
+    }
+}
+
+/// The identity a paging record was for
+#[derive(Clone, PartialEq, Debug)]
+enum PagedIdentity {
+    STmsi(STmsi),
+    Imsi(String),
+    Other,
+}
+
+impl From<&PagingUE_Identity> for PagedIdentity {
+    fn from(identity: &PagingUE_Identity) -> Self {
+        match identity {
+            PagingUE_Identity::S_TMSI(s_tmsi) => Self::STmsi(STmsi {
+                mme_code: bits_to_u32(s_tmsi.mmec.0.iter().map(|bit| *bit)) as u8,
+                m_tmsi: bits_to_u32(s_tmsi.m_tmsi.0.iter().map(|bit| *bit)),
+            }),
+            PagingUE_Identity::Imsi(imsi) => Self::Imsi(
+                imsi.0
+                    .iter()
+                    .filter_map(|digit| char::from_digit(digit.0 as u32, 10))
+                    .collect(),
+            ),
+            _ => Self::Other,
+        }
+    }
+}
+
+// Real networks page phones by their S-TMSI, and only fall back to the IMSI
+// when they've lost track of it. IMSI catchers don't know the phone's S-TMSI,
+// so they page by IMSI to find out whether it's nearby. The phone answers an
+// IMSI page by attaching again with its IMSI, which tells us the page was for
+// us even if we didn't know our IMSI before.
+pub struct ImsiProvidedAnalyzer {
+    packet_num: usize,
+    /// Our own S-TMSI, learned from the last GUTI the network gave us
+    s_tmsi: Option<STmsi>,
+    /// When the cell last paged us by our S-TMSI
+    s_tmsi_paged_at: Option<usize>,
+    /// Our own IMSI, if the phone has sent it
+    imsi: Option<String>,
+    /// IMSI pages we don't know to be ours yet, with when we saw them
+    pending_imsi_pages: VecDeque<(String, usize)>,
+    /// How many records each of the current cell's recent paging messages
+    /// carried
+    paging_records: VecDeque<usize>,
+    storm_reported: bool,
+}
+
+impl Default for ImsiProvidedAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ImsiProvidedAnalyzer {
+    pub fn new() -> Self {
+        Self {
+            packet_num: 0,
+            s_tmsi: None,
+            s_tmsi_paged_at: None,
+            imsi: None,
+            pending_imsi_pages: VecDeque::new(),
+            paging_records: VecDeque::new(),
+            storm_reported: false,
+        }
+    }
+
+    fn imsi_paged_event(&self) -> Event {
+        let paged_by_s_tmsi = self
+            .s_tmsi_paged_at
+            .is_some_and(|paged_at| self.packet_num - paged_at <= IMSI_PAGE_RESPONSE_WINDOW);
+        let (severity, context) = match self.s_tmsi {
+            // a network which has lost track of the phone's S-TMSI pages its
+            // IMSI instead of it, not as well as it
+            Some(s_tmsi) if paged_by_s_tmsi => (
+                Severity::High,
+                format!(
+                    ", right after paging it by its temporary identity {:02x}-{:08x}",
+                    s_tmsi.mme_code, s_tmsi.m_tmsi
+                ),
+            ),
+            Some(s_tmsi) => (
+                Severity::High,
+                format!(
+                    ", even though the network had given it the temporary identity \
+                    {:02x}-{:08x}",
+                    s_tmsi.mme_code, s_tmsi.m_tmsi
+                ),
+            ),
+            None => (Severity::Medium, String::new()),
+        };
+        Event {
+            event_type: EventType::QualitativeWarning { severity },
+            message: format!("Cell paged the phone by its IMSI{context}"),
+        }
+    }
+
+    fn learn_imsi(&mut self, imsi: &str) -> Option<Event> {
+        self.imsi = Some(imsi.to_string());
+        let packet_num = self.packet_num;
+        self.pending_imsi_pages
+            .retain(|(_, paged_at)| packet_num - paged_at <= IMSI_PAGE_RESPONSE_WINDOW);
+        let paged = self
+            .pending_imsi_pages
+            .iter()
+            .any(|(paged, _)| paged == imsi);
+        self.pending_imsi_pages.clear();
+        paged.then(|| self.imsi_paged_event())
+    }
+
+    fn analyze_emm(&mut self, emm: &EmmMessage) -> Option<Event> {
+        let guti = match emm {
+            EmmMessage::AttachRequest(request) => match &request.identity {
+                EpsMobileIdentity::Imsi(imsi) => return self.learn_imsi(imsi),
+                EpsMobileIdentity::Guti(guti) => Some(guti),
+                _ => None,
+            },
+            EmmMessage::IdentityResponse(response) => match &response.mobile_identity {
+                MobileIdentity::Imsi(imsi) => return self.learn_imsi(imsi),
+                _ => None,
+            },
+            EmmMessage::AttachAccept(accept) => accept.guti.as_ref(),
+            EmmMessage::TrackingAreaUpdateAccept(accept) => accept.guti.as_ref(),
+            EmmMessage::GutiReallocationCommand(command) => Some(&command.guti),
+            _ => None,
+        };
+        if let Some(guti) = guti {
+            self.s_tmsi = Some(guti.into());
+        }
+        None
+    }
+
+    fn analyze_paging_records(&mut self, records: &[PagedIdentity]) -> Option<Event> {
+        if self.paging_records.len() == PAGING_STORM_WINDOW {
+            self.paging_records.pop_front();
+        }
+        self.paging_records.push_back(records.len());
+
+        let mut imsi_paged = false;
+        for record in records {
+            match record {
+                // pages by S-TMSI are how the network normally reaches a
+                // phone, so we only note when it's reached us
+                PagedIdentity::STmsi(s_tmsi) => {
+                    if self.s_tmsi == Some(*s_tmsi) {
+                        self.s_tmsi_paged_at = Some(self.packet_num);
+                    }
+                }
+                PagedIdentity::Imsi(imsi) => match &self.imsi {
+                    Some(ours) => imsi_paged |= ours == imsi,
+                    None => {
+                        if self.pending_imsi_pages.len() == MAX_PENDING_IMSI_PAGES {
+                            self.pending_imsi_pages.pop_front();
+                        }
+                        self.pending_imsi_pages
+                            .push_back((imsi.clone(), self.packet_num));
+                    }
+                },
+                PagedIdentity::Other => {}
+            }
+        }
+
+        // a page for us says more than how busy the cell's paging is. The
+        // storm, if there is one, is still going on, so it's reported with
+        // the next paging message instead.
+        if imsi_paged {
+            return Some(self.imsi_paged_event());
+        }
+        self.check_paging_storm()
+    }
+
+    fn check_paging_storm(&mut self) -> Option<Event> {
+        let total: usize = self.paging_records.iter().sum();
+        if self.storm_reported
+            || self.paging_records.len() < PAGING_STORM_WINDOW
+            || total < PAGING_STORM_WINDOW * PAGING_STORM_RECORDS_PER_MESSAGE
+        {
+            return None;
+        }
+        self.storm_reported = true;
+        Some(Event {
+            event_type: EventType::QualitativeWarning {
+                severity: Severity::Medium,
+            },
+            message: format!(
+                "Paging storm: the cell sent {total} paging records in its last \
+                {PAGING_STORM_WINDOW} paging messages, which can mean it's paging phones \
+                en masse to find out who's nearby"
+            ),
+        })
+    }
+}
 
 impl Analyzer for ImsiProvidedAnalyzer {
     fn get_name(&self) -> Cow<str> {
@@ -13,30 +240,194 @@ impl Analyzer for ImsiProvidedAnalyzer {
     }
 
     fn get_description(&self) -> Cow<str> {
-        Cow::from("Tests whether the UE's IMSI was ever provided to the cell")
+        Cow::from(
+            "Tests whether the cell pages the phone by its IMSI rather than its temporary \
+            identity, and whether a cell sends an abnormal number of paging records.",
+        )
     }
 
     fn analyze_information_element(&mut self, ie: &InformationElement) -> Option<Event> {
-        let pcch_msg = match ie {
-            InformationElement::LTE(lte_ie) => match &**lte_ie {
-                LteInformationElement::PCCH(pcch_msg) => pcch_msg,
-                _ => return None,
-            },
-            _ => return None,
-        };
-        let PCCH_MessageType::C1(PCCH_MessageType_c1::Paging(paging)) = &pcch_msg.message else {
+        self.packet_num += 1;
+        let InformationElement::LTE(lte_ie) = ie else {
             return None;
         };
-        for record in &paging.paging_record_list.as_ref()?.0 {
-            if let PagingUE_Identity::Imsi(_) = record.ue_identity {
-                return Some(Event {
-                    event_type: EventType::QualitativeWarning {
-                        severity: Severity::High,
-                    },
-                    message: "IMSI was provided to cell".to_string(),
-                });
+        match &**lte_ie {
+            LteInformationElement::NAS(nas) => self.analyze_emm(nas.emm.as_ref()?),
+            LteInformationElement::PCCH(pcch_msg) => {
+                let PCCH_MessageType::C1(PCCH_MessageType_c1::Paging(paging)) = &pcch_msg.message
+                else {
+                    return None;
+                };
+                let records: Vec<PagedIdentity> = paging
+                    .paging_record_list
+                    .iter()
+                    .flat_map(|list| &list.0)
+                    .map(|record| PagedIdentity::from(&record.ue_identity))
+                    .collect();
+                self.analyze_paging_records(&records)
             }
+            _ => None,
+        }
+    }
+
+    fn analyze_state_change(&mut self, change: &StateChange) -> Option<Event> {
+        // paging rates are per cell
+        if let StateChange::LteRrcNewCell = change {
+            self.paging_records.clear();
+            self.storm_reported = false;
         }
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use telcom_parser::gsm_rr::Plmn;
+    use telcom_parser::lte_nas::{AttachAccept, AttachRequest, IdentityResponse};
+
+    const OUR_IMSI: &str = "001010123456789";
+
+    fn guti() -> Guti {
+        Guti {
+            plmn: Plmn {
+                mcc: 1,
+                mnc: 1,
+                three_digit_mnc: false,
+            },
+            mme_group_id: 0x8001,
+            mme_code: 0x05,
+            m_tmsi: 0x12345678,
+        }
+    }
+
+    fn imsi_attach() -> EmmMessage {
+        EmmMessage::AttachRequest(AttachRequest {
+            identity: EpsMobileIdentity::Imsi(OUR_IMSI.to_string()),
+        })
+    }
+
+    fn severity(event: Option<Event>) -> Option<Severity> {
+        match event?.event_type {
+            EventType::QualitativeWarning { severity } => Some(severity),
+            EventType::Informational => None,
+        }
+    }
+
+    #[test]
+    fn test_learns_s_tmsi() {
+        let mut analyzer = ImsiProvidedAnalyzer::new();
+        analyzer.analyze_emm(&EmmMessage::AttachAccept(AttachAccept {
+            guti: Some(guti()),
+        }));
+        assert_eq!(
+            analyzer.s_tmsi,
+            Some(STmsi {
+                mme_code: 0x05,
+                m_tmsi: 0x12345678
+            })
+        );
+    }
+
+    #[test]
+    fn test_imsi_page_for_us() {
+        let mut analyzer = ImsiProvidedAnalyzer::new();
+        analyzer.analyze_emm(&EmmMessage::IdentityResponse(IdentityResponse {
+            mobile_identity: MobileIdentity::Imsi(OUR_IMSI.to_string()),
+        }));
+        analyzer.analyze_emm(&EmmMessage::AttachAccept(AttachAccept {
+            guti: Some(guti()),
+        }));
+        let page = [PagedIdentity::Imsi(OUR_IMSI.to_string())];
+        assert!(matches!(
+            severity(analyzer.analyze_paging_records(&page)),
+            Some(Severity::High)
+        ));
+    }
+
+    #[test]
+    fn test_pages_for_other_ues() {
+        let mut analyzer = ImsiProvidedAnalyzer::new();
+        analyzer.analyze_emm(&imsi_attach());
+        let page = [
+            PagedIdentity::Imsi("001010987654321".to_string()),
+            PagedIdentity::STmsi(STmsi {
+                mme_code: 0x01,
+                m_tmsi: 0xdeadbeef,
+            }),
+        ];
+        assert!(analyzer.analyze_paging_records(&page).is_none());
+        assert_eq!(analyzer.s_tmsi_paged_at, None);
+    }
+
+    #[test]
+    fn test_imsi_page_after_s_tmsi_page() {
+        let mut analyzer = ImsiProvidedAnalyzer::new();
+        analyzer.analyze_emm(&imsi_attach());
+        analyzer.analyze_emm(&EmmMessage::AttachAccept(AttachAccept {
+            guti: Some(guti()),
+        }));
+        let s_tmsi_page = [PagedIdentity::STmsi(STmsi::from(&guti()))];
+        assert!(analyzer.analyze_paging_records(&s_tmsi_page).is_none());
+        let imsi_page = [PagedIdentity::Imsi(OUR_IMSI.to_string())];
+        let event = analyzer.analyze_paging_records(&imsi_page).unwrap();
+        assert!(event.message.contains("right after paging it"));
+        assert!(matches!(severity(Some(event)), Some(Severity::High)));
+    }
+
+    #[test]
+    fn test_imsi_page_answered() {
+        // we don't know our IMSI until the phone attaches with it in
+        // response to the page
+        let mut analyzer = ImsiProvidedAnalyzer::new();
+        let page = [PagedIdentity::Imsi(OUR_IMSI.to_string())];
+        assert!(analyzer.analyze_paging_records(&page).is_none());
+        assert!(matches!(
+            severity(analyzer.analyze_emm(&imsi_attach())),
+            Some(Severity::Medium)
+        ));
+
+        // but not if the attach came long after
+        let mut analyzer = ImsiProvidedAnalyzer::new();
+        analyzer.analyze_paging_records(&page);
+        analyzer.packet_num += IMSI_PAGE_RESPONSE_WINDOW + 1;
+        assert!(analyzer.analyze_emm(&imsi_attach()).is_none());
+    }
+
+    #[test]
+    fn test_paging_storm() {
+        let mut analyzer = ImsiProvidedAnalyzer::new();
+        let quiet = vec![PagedIdentity::Other; 2];
+        for _ in 0..PAGING_STORM_WINDOW * 2 {
+            assert!(analyzer.analyze_paging_records(&quiet).is_none());
+        }
+
+        let busy = vec![PagedIdentity::Other; 16];
+        let events: Vec<Event> = (0..PAGING_STORM_WINDOW * 2)
+            .filter_map(|_| analyzer.analyze_paging_records(&busy))
+            .collect();
+        assert_eq!(events.len(), 1);
+
+        // a new cell gets a clean slate
+        analyzer.analyze_state_change(&StateChange::LteRrcNewCell);
+        assert!(analyzer.analyze_paging_records(&busy).is_none());
+    }
+
+    #[test]
+    fn test_imsi_page_during_paging_storm() {
+        let mut analyzer = ImsiProvidedAnalyzer::new();
+        analyzer.analyze_emm(&imsi_attach());
+        let busy = vec![PagedIdentity::Other; 16];
+        for _ in 1..PAGING_STORM_WINDOW {
+            assert!(analyzer.analyze_paging_records(&busy).is_none());
+        }
+
+        // the page for us comes first, and the storm with the next message
+        let mut busy_with_us = busy.clone();
+        busy_with_us[0] = PagedIdentity::Imsi(OUR_IMSI.to_string());
+        let event = analyzer.analyze_paging_records(&busy_with_us).unwrap();
+        assert!(event.message.contains("IMSI"));
+        let event = analyzer.analyze_paging_records(&busy).unwrap();
+        assert!(event.message.contains("Paging storm"));
+    }
+}
@@ -1,13 +1,12 @@
 use std::borrow::Cow;
 
-use pycrate_rs::nas::NASMessage;
-use pycrate_rs::nas::emm::EMMMessage;
-use pycrate_rs::nas::generated::emm::emm_identity_request::IDTypeV;
+use telcom_parser::lte_nas::{EmmMessage, IdentityType};
 
-use super::analyzer::{Analyzer, Event, EventType, Severity};
+use super::analyzer::{Analyzer, Event, Severity};
 use super::information_element::{InformationElement, LteInformationElement};
+use super::util::identity_request_event;
 
-const PACKET_THRESHHOLD: usize = 150;
+pub const PACKET_THRESHHOLD: usize = 150;
 
 pub struct ImsiRequestedAnalyzer {
     packet_num: usize,
@@ -38,39 +37,55 @@ impl Analyzer for ImsiRequestedAnalyzer {
         self.packet_num += 1;
         let payload = match ie {
             InformationElement::LTE(inner) => match &**inner {
-                LteInformationElement::NAS(payload) => payload,
+                LteInformationElement::NAS(nas) => &nas.emm,
                 _ => return None,
             },
             _ => return None,
         };
 
-        if let NASMessage::EMMMessage(EMMMessage::EMMIdentityRequest(req)) = payload {
-            if req.id_type.inner == IDTypeV::IMSI {
-                if self.packet_num < PACKET_THRESHHOLD {
-                    return Some(Event {
-                        event_type: EventType::QualitativeWarning {
-                            severity: Severity::Medium,
-                        },
-                        message: format!(
-                            "NAS IMSI identity request detected, however it was within \
-                            the first {PACKET_THRESHHOLD} packets of this analysis. If you just \
-                            turned your device on, this is likely a \
-                            false-positive."
-                        ),
-                    });
-                } else {
-                    return Some(Event {
-                        event_type: EventType::QualitativeWarning {
-                            severity: Severity::High,
-                        },
-                        message: format!(
-                            "NAS IMSI identity request detected (packet {})",
-                            self.packet_num
-                        ),
-                    });
-                }
+        if let Some(EmmMessage::IdentityRequest(req)) = payload {
+            if req.identity_type == IdentityType::Imsi {
+                return Some(identity_request_event(
+                    "NAS IMSI identity request",
+                    self.packet_num,
+                    PACKET_THRESHHOLD,
+                    Severity::High,
+                    Severity::Medium,
+                ));
             }
         }
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::information_element::LteNasMessage;
+    use telcom_parser::lte_nas::IdentityRequest;
+
+    fn identity_request(identity_type: IdentityType) -> InformationElement {
+        InformationElement::LTE(Box::new(LteInformationElement::NAS(LteNasMessage {
+            security_header: None,
+            emm: Some(EmmMessage::IdentityRequest(IdentityRequest {
+                identity_type,
+            })),
+        })))
+    }
+
+    #[test]
+    fn test_imsi_requests() {
+        let mut analyzer = ImsiRequestedAnalyzer::new();
+        assert!(
+            analyzer
+                .analyze_information_element(&identity_request(IdentityType::Imsi))
+                .is_some()
+        );
+        // left to IdentityRequestedAnalyzer
+        assert!(
+            analyzer
+                .analyze_information_element(&identity_request(IdentityType::Imei))
+                .is_none()
+        );
+    }
+}
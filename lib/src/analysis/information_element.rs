@@ -3,9 +3,15 @@
 //! the term to refer to a structured, fully parsed message in any telcom
 //! standard.
 
-use crate::gsmtap::{GsmtapMessage, GsmtapType, LteNasSubtype, LteRrcSubtype};
-use pycrate_rs::nas::NASMessage;
-use telcom_parser::{decode, lte_rrc};
+use crate::gsmtap::{
+    GsmtapMessage, GsmtapType, LteNasSubtype, LteRrcSubtype, NrRrcSubtype, UmSubtype,
+    UmtsRrcSubtype,
+};
+use telcom_parser::gprs_rlcmac::{self, RlcMacControlMessage};
+use telcom_parser::gsm_nas::{self, GsmNasMessage};
+use telcom_parser::gsm_rr::{self, GsmL3Message};
+use telcom_parser::lte_nas::{self, EmmMessage, SecurityHeader};
+use telcom_parser::{decode, lte_rrc, nb_iot_rrc, nr_rrc, umts_rrc};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -13,19 +19,82 @@ pub enum InformationElementError {
     #[error("Failed decoding RRC message")]
     RRCDecodingError(#[from] telcom_parser::ParsingError),
     #[error("Failed decoding NAS message")]
-    NASDecodingError(#[from] pycrate_rs::nas::ParseError),
+    NASDecodingError(#[source] telcom_parser::ParsingError),
     #[error("Unsupported LTE RRC subtype {0:?}")]
     UnsupportedGsmtapType(GsmtapType),
 }
 
 #[derive(Debug, Clone)]
 pub enum InformationElement {
-    GSM,
-    UMTS,
+    GSM(Box<GsmInformationElement>),
+    UMTS(Box<UmtsInformationElement>),
     // This element of the enum is substantially larger than the others,
     // so we box it to prevent the size of the enum (any variant) from blowing up.
     LTE(Box<LteInformationElement>),
-    FiveG,
+    FiveG(Box<NrInformationElement>),
+}
+
+#[derive(Debug, Clone)]
+pub enum GsmInformationElement {
+    SystemInformationType1(gsm_rr::SystemInformationType1),
+    SystemInformationType2(gsm_rr::SystemInformationType2),
+    SystemInformationType3(gsm_rr::SystemInformationType3),
+    SystemInformationType4(gsm_rr::SystemInformationType4),
+    SystemInformationType13(gsm_rr::SystemInformationType13),
+    ImmediateAssignment(gsm_rr::ImmediateAssignment),
+    CipheringModeCommand(gsm_rr::CipheringModeCommand),
+    ChannelRelease(gsm_rr::ChannelRelease),
+    Nas(GsmNasMessage),
+    PacketCellChangeOrder(gprs_rlcmac::PacketCellChangeOrder),
+    PacketDownlinkAssignment(gprs_rlcmac::PacketDownlinkAssignment),
+    PacketUplinkAssignment(gprs_rlcmac::PacketUplinkAssignment),
+    /// Any other RLC/MAC control message, identified by its message type
+    OtherRlcMac(RlcMacControlMessage),
+    /// Any other RR message, identified by its message type
+    OtherRr(u8),
+}
+
+impl From<RlcMacControlMessage> for GsmInformationElement {
+    fn from(msg: RlcMacControlMessage) -> Self {
+        use GsmInformationElement as G;
+        match msg {
+            RlcMacControlMessage::PacketCellChangeOrder(pcco) => G::PacketCellChangeOrder(pcco),
+            RlcMacControlMessage::PacketDownlinkAssignment(pda) => G::PacketDownlinkAssignment(pda),
+            RlcMacControlMessage::PacketUplinkAssignment(pua) => G::PacketUplinkAssignment(pua),
+            other => G::OtherRlcMac(other),
+        }
+    }
+}
+
+impl From<GsmL3Message> for GsmInformationElement {
+    fn from(msg: GsmL3Message) -> Self {
+        use GsmInformationElement as G;
+        match msg {
+            GsmL3Message::SystemInformationType1(si) => G::SystemInformationType1(si),
+            GsmL3Message::SystemInformationType2(si) => G::SystemInformationType2(si),
+            GsmL3Message::SystemInformationType3(si) => G::SystemInformationType3(si),
+            GsmL3Message::SystemInformationType4(si) => G::SystemInformationType4(si),
+            GsmL3Message::SystemInformationType13(si) => G::SystemInformationType13(si),
+            GsmL3Message::ImmediateAssignment(ia) => G::ImmediateAssignment(ia),
+            GsmL3Message::CipheringModeCommand(cmd) => G::CipheringModeCommand(cmd),
+            GsmL3Message::ChannelRelease(release) => G::ChannelRelease(release),
+            GsmL3Message::Nas(nas) => G::Nas(nas),
+            GsmL3Message::Other(message_type) => G::OtherRr(message_type),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum UmtsInformationElement {
+    DlCcch(umts_rrc::DlCcchMessage),
+    DlDcch(umts_rrc::DlDcchMessage),
+    UlCcch(umts_rrc::UlCcchMessage),
+    UlDcch(umts_rrc::UlDcchMessage),
+    BcchBch(umts_rrc::BcchBchMessage),
+    BcchFach(umts_rrc::BcchFachMessage),
+    Pcch(umts_rrc::PcchMessage),
+    /// 2G/3G NAS messages, which the modem logs for both RATs
+    Nas(GsmNasMessage),
 }
 
 #[derive(Debug, Clone)]
@@ -47,17 +116,56 @@ pub enum LteInformationElement {
     SbcchSlBch(lte_rrc::SBCCH_SL_BCH_Message),
     SbcchSlBchV2x(lte_rrc::SBCCH_SL_BCH_Message_V2X_r14),
 
-    NAS(NASMessage),
-    // FIXME: unclear which message these "NB" types map to
-    //DlCcchNb(),
-    //DlDcchNb(),
-    //UlCcchNb(),
-    //UlDcchNb(),
-    //BcchBchNb(),
-    //BcchBchTddNb(),
-    //BcchDlSchNb(),
-    //PcchNb(),
-    //ScMcchNb(),
+    NAS(LteNasMessage),
+    /// A security protected NAS message whose contents are ciphered, so all
+    /// we can see is its security header
+    CipheredNAS(SecurityHeader),
+    DlCcchNb(nb_iot_rrc::DlCcchMessage),
+    DlDcchNb(nb_iot_rrc::DlDcchMessage),
+    UlCcchNb(nb_iot_rrc::UlCcchMessage),
+    UlDcchNb(nb_iot_rrc::UlDcchMessage),
+    BcchBchNb(nb_iot_rrc::BcchBchMessage),
+    BcchBchTddNb(nb_iot_rrc::BcchBchTddMessage),
+    BcchDlSchNb(nb_iot_rrc::BcchDlSchMessage),
+    PcchNb(nb_iot_rrc::PcchMessage),
+    ScMcchNb(nb_iot_rrc::ScMcchMessage),
+}
+
+/// A decoded LTE NAS message, along with the security header it was wrapped in
+/// if it was integrity protected
+#[derive(Debug, Clone)]
+pub struct LteNasMessage {
+    pub security_header: Option<SecurityHeader>,
+    /// The message's EMM fields that our analyzers check, or None if it's an
+    /// ESM message
+    pub emm: Option<EmmMessage>,
+}
+
+impl LteNasMessage {
+    fn parse(
+        security_header: Option<SecurityHeader>,
+        data: &[u8],
+    ) -> Result<Self, InformationElementError> {
+        Ok(Self {
+            security_header,
+            emm: lte_nas::decode(data).map_err(InformationElementError::NASDecodingError)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum NrInformationElement {
+    BcchBch(nr_rrc::BcchBchMessage),
+    BcchDlSch(nr_rrc::BcchDlSchMessage),
+    DlCcch(nr_rrc::DlCcchMessage),
+    DlDcch(nr_rrc::DlDcchMessage),
+    UlCcch(nr_rrc::UlCcchMessage),
+    UlCcch1(nr_rrc::UlCcch1Message),
+    UlDcch(nr_rrc::UlDcchMessage),
+    PCCH(nr_rrc::PcchMessage),
+    // in EN-DC, these are carried inside LTE RRC messages
+    RrcReconfiguration(nr_rrc::RrcReconfiguration),
+    RrcReconfigurationComplete(nr_rrc::RrcReconfigurationComplete),
 }
 
 impl TryFrom<&GsmtapMessage> for InformationElement {
@@ -68,6 +176,7 @@ impl TryFrom<&GsmtapMessage> for InformationElement {
             GsmtapType::LteRrc(lte_rrc_subtype) => {
                 use LteInformationElement as R;
                 use LteRrcSubtype as L;
+                let payload = &gsmtap_msg.payload;
                 let lte = match lte_rrc_subtype {
                     L::DlCcch => R::DlCcch(decode(&gsmtap_msg.payload)?),
                     L::DlDcch => R::DlDcch(Box::new(decode(&gsmtap_msg.payload)?)),
@@ -83,20 +192,111 @@ impl TryFrom<&GsmtapMessage> for InformationElement {
                     L::BcchDlSchMbms => R::BcchDlSchMbms(decode(&gsmtap_msg.payload)?),
                     L::SbcchSlBch => R::SbcchSlBch(decode(&gsmtap_msg.payload)?),
                     L::SbcchSlBchV2x => R::SbcchSlBchV2x(decode(&gsmtap_msg.payload)?),
+                    L::DlCcchNb => R::DlCcchNb(nb_iot_rrc::DlCcchMessage::decode(payload)?),
+                    L::DlDcchNb => R::DlDcchNb(nb_iot_rrc::DlDcchMessage::decode(payload)?),
+                    L::UlCcchNb => R::UlCcchNb(nb_iot_rrc::UlCcchMessage::decode(payload)?),
+                    L::UlDcchNb => R::UlDcchNb(nb_iot_rrc::UlDcchMessage::decode(payload)?),
+                    L::BcchBchNb => R::BcchBchNb(nb_iot_rrc::BcchBchMessage::decode(payload)?),
+                    L::BcchBchTddNb => {
+                        R::BcchBchTddNb(nb_iot_rrc::BcchBchTddMessage::decode(payload)?)
+                    }
+                    L::BcchDlSchNb => {
+                        R::BcchDlSchNb(nb_iot_rrc::BcchDlSchMessage::decode(payload)?)
+                    }
+                    L::PcchNb => R::PcchNb(nb_iot_rrc::PcchMessage::decode(payload)?),
+                    L::ScMcchNb => R::ScMcchNb(nb_iot_rrc::ScMcchMessage::decode(payload)?),
+                };
+                Ok(InformationElement::LTE(Box::new(lte)))
+            }
+            GsmtapType::UmtsRrc(umts_rrc_subtype) => {
+                use UmtsInformationElement as R;
+                use UmtsRrcSubtype as U;
+                let payload = &gsmtap_msg.payload;
+                let umts = match umts_rrc_subtype {
+                    U::DlCcch => R::DlCcch(umts_rrc::DlCcchMessage::decode(payload)?),
+                    U::DlDcch => R::DlDcch(umts_rrc::DlDcchMessage::decode(payload)?),
+                    U::UlCcch => R::UlCcch(umts_rrc::UlCcchMessage::decode(payload)?),
+                    U::UlDcch => R::UlDcch(umts_rrc::UlDcchMessage::decode(payload)?),
+                    U::BcchBch => R::BcchBch(umts_rrc::BcchBchMessage::decode(payload)?),
+                    U::BcchFach => R::BcchFach(umts_rrc::BcchFachMessage::decode(payload)?),
+                    U::Pcch => R::Pcch(umts_rrc::PcchMessage::decode(payload)?),
                     _ => {
                         return Err(InformationElementError::UnsupportedGsmtapType(
                             gsmtap_msg.header.gsmtap_type,
                         ));
                     }
                 };
-                Ok(InformationElement::LTE(Box::new(lte)))
+                Ok(InformationElement::UMTS(Box::new(umts)))
+            }
+            GsmtapType::NrRrc(nr_rrc_subtype) => {
+                use NrInformationElement as R;
+                use NrRrcSubtype as N;
+                let payload = &gsmtap_msg.payload;
+                let nr = match nr_rrc_subtype {
+                    N::BcchBch => R::BcchBch(nr_rrc::BcchBchMessage::decode(payload)?),
+                    N::BcchDlSch => R::BcchDlSch(nr_rrc::BcchDlSchMessage::decode(payload)?),
+                    N::DlCcch => R::DlCcch(nr_rrc::DlCcchMessage::decode(payload)?),
+                    N::DlDcch => R::DlDcch(nr_rrc::DlDcchMessage::decode(payload)?),
+                    N::UlCcch => R::UlCcch(nr_rrc::UlCcchMessage::decode(payload)?),
+                    N::UlCcch1 => R::UlCcch1(nr_rrc::UlCcch1Message::decode(payload)?),
+                    N::UlDcch => R::UlDcch(nr_rrc::UlDcchMessage::decode(payload)?),
+                    N::Pcch => R::PCCH(nr_rrc::PcchMessage::decode(payload)?),
+                    N::RrcReconfiguration => R::RrcReconfiguration(
+                        nr_rrc::RrcReconfiguration::decode_standalone(payload)?,
+                    ),
+                    N::RrcReconfigurationComplete => R::RrcReconfigurationComplete(
+                        nr_rrc::RrcReconfigurationComplete::decode_standalone(payload)?,
+                    ),
+                };
+                Ok(InformationElement::FiveG(Box::new(nr)))
+            }
+            GsmtapType::Abis => {
+                let msg = gsm_nas::decode(&gsmtap_msg.payload)?;
+                Ok(InformationElement::UMTS(Box::new(
+                    UmtsInformationElement::Nas(msg),
+                )))
+            }
+            GsmtapType::Um(UmSubtype::Pacch) => {
+                let msg = gprs_rlcmac::decode(&gsmtap_msg.payload, gsmtap_msg.header.uplink)?;
+                Ok(InformationElement::GSM(Box::new(msg.into())))
+            }
+            GsmtapType::Um(um_subtype) => {
+                // strip the L2 framing added by gsmtap_parser to get at the L3 message
+                let l3_offset = match um_subtype {
+                    UmSubtype::Bcch | UmSubtype::Ccch => 1,
+                    UmSubtype::Sdcch | UmSubtype::TchF | UmSubtype::TchH => 3,
+                    UmSubtype::Sacch => 5,
+                    _ => {
+                        return Err(InformationElementError::UnsupportedGsmtapType(
+                            gsmtap_msg.header.gsmtap_type,
+                        ));
+                    }
+                };
+                let l3 = gsmtap_msg.payload.get(l3_offset..).unwrap_or_default();
+                let msg = gsm_rr::decode(l3)?;
+                Ok(InformationElement::GSM(Box::new(msg.into())))
             }
             GsmtapType::LteNas(LteNasSubtype::Plain) => {
-                let msg = NASMessage::parse(&gsmtap_msg.payload)?;
+                let msg = LteNasMessage::parse(None, &gsmtap_msg.payload)?;
                 Ok(InformationElement::LTE(Box::new(
                     LteInformationElement::NAS(msg),
                 )))
             }
+            GsmtapType::LteNas(LteNasSubtype::Secure) => {
+                let Some((header, inner)) = SecurityHeader::decode(&gsmtap_msg.payload)? else {
+                    return Err(InformationElementError::UnsupportedGsmtapType(
+                        gsmtap_msg.header.gsmtap_type,
+                    ));
+                };
+                let lte = if header.security_header_type.is_ciphered() {
+                    LteInformationElement::CipheredNAS(header)
+                } else {
+                    // integrity protected messages are sent in the clear, so
+                    // we can unwrap them and decode the inner message
+                    LteInformationElement::NAS(LteNasMessage::parse(Some(header), inner)?)
+                };
+                Ok(InformationElement::LTE(Box::new(lte)))
+            }
             _ => Err(InformationElementError::UnsupportedGsmtapType(
                 gsmtap_msg.header.gsmtap_type,
             )),
@@ -1,7 +1,10 @@
 pub mod analyzer;
+pub mod auth_skipped;
 pub mod cellular_network;
 pub mod connection_redirect_downgrade;
+pub mod emm_reject;
 pub mod gps_correlation;
+pub mod identity_requested;
 pub mod imsi_provided;
 pub mod imsi_requested;
 pub mod information_element;
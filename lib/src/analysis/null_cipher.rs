@@ -1,9 +1,11 @@
 use std::borrow::Cow;
 
+use telcom_parser::lte_nas::{CipheringAlgorithm, EmmMessage, IntegrityAlgorithm};
 use telcom_parser::lte_rrc::{
     CipheringAlgorithm_r12, DL_DCCH_MessageType, DL_DCCH_MessageType_c1,
     RRCConnectionReconfiguration, RRCConnectionReconfigurationCriticalExtensions,
     RRCConnectionReconfigurationCriticalExtensions_c1, SCG_Configuration_r12,
+    SecurityAlgorithmConfig, SecurityAlgorithmConfigIntegrityProtAlgorithm,
     SecurityConfigHO_v1530HandoverType_v1530, SecurityModeCommand,
     SecurityModeCommandCriticalExtensions, SecurityModeCommandCriticalExtensions_c1,
 };
@@ -13,20 +15,45 @@ use super::information_element::{InformationElement, LteInformationElement};
 
 pub struct NullCipherAnalyzer {}
 
+/// Which of the security algorithms a message selected were null
+#[derive(Default, Clone, Copy)]
+struct NullAlgorithms {
+    ciphering: bool,
+    integrity: bool,
+}
+
+impl NullAlgorithms {
+    fn merge(&mut self, other: NullAlgorithms) {
+        self.ciphering |= other.ciphering;
+        self.integrity |= other.integrity;
+    }
+}
+
+impl From<&SecurityAlgorithmConfig> for NullAlgorithms {
+    fn from(config: &SecurityAlgorithmConfig) -> Self {
+        Self {
+            ciphering: config.ciphering_algorithm.0 == CipheringAlgorithm_r12::EEA0,
+            integrity: config.integrity_prot_algorithm.0
+                == SecurityAlgorithmConfigIntegrityProtAlgorithm::EIA0_V920,
+        }
+    }
+}
+
 impl NullCipherAnalyzer {
-    fn check_rrc_connection_reconfiguration_cipher(
+    fn check_rrc_connection_reconfiguration(
         &self,
         reconfiguration: &RRCConnectionReconfiguration,
-    ) -> bool {
+    ) -> NullAlgorithms {
+        let mut result = NullAlgorithms::default();
         let RRCConnectionReconfigurationCriticalExtensions::C1(c1) =
             &reconfiguration.critical_extensions
         else {
-            return false;
+            return result;
         };
         let RRCConnectionReconfigurationCriticalExtensions_c1::RrcConnectionReconfiguration_r8(c1) =
             c1
         else {
-            return false;
+            return result;
         };
         if let Some(handover) = &c1.security_config_ho {
             let maybe_security_config = match &handover.handover_type {
@@ -38,9 +65,7 @@ impl NullCipherAnalyzer {
                 }
             };
             if let Some(security_config) = maybe_security_config {
-                if security_config.ciphering_algorithm.0 == CipheringAlgorithm_r12::EEA0 {
-                    return true;
-                }
+                result.merge(security_config.into());
             }
         }
         // Use map/flatten to dig into a long chain of nested Option types
@@ -52,9 +77,11 @@ impl NullCipherAnalyzer {
             .and_then(|v1020| v1020.non_critical_extension.as_ref())
             .and_then(|v1130| v1130.non_critical_extension.as_ref());
         let Some(v1250) = maybe_v1250 else {
-            return false;
+            return result;
         };
 
+        // the SCG only gets a ciphering algorithm, since user plane traffic
+        // isn't integrity protected
         if let Some(SCG_Configuration_r12::Setup(scg_setup)) = v1250.scg_configuration_r12.as_ref()
         {
             let maybe_cipher = scg_setup
@@ -62,10 +89,10 @@ impl NullCipherAnalyzer {
                 .as_ref()
                 .and_then(|scg| scg.mobility_control_info_scg_r12.as_ref())
                 .and_then(|mci| mci.ciphering_algorithm_scg_r12.as_ref());
-            if let Some(cipher) = maybe_cipher {
-                if cipher.0 == CipheringAlgorithm_r12::EEA0 {
-                    return true;
-                }
+            if let Some(cipher) = maybe_cipher
+                && cipher.0 == CipheringAlgorithm_r12::EEA0
+            {
+                result.ciphering = true;
             }
         }
 
@@ -77,7 +104,7 @@ impl NullCipherAnalyzer {
             .and_then(|v1510| v1510.non_critical_extension.as_ref())
             .and_then(|v1530| v1530.security_config_ho_v1530.as_ref());
         let Some(v1530_security_config) = maybe_v1530_security_config else {
-            return false;
+            return result;
         };
         let maybe_security_algorithm = match &v1530_security_config.handover_type_v1530 {
             SecurityConfigHO_v1530HandoverType_v1530::Intra5GC(intra_5gc) => {
@@ -91,31 +118,83 @@ impl NullCipherAnalyzer {
             }
         };
         if let Some(security_algorithm) = maybe_security_algorithm {
-            if security_algorithm.ciphering_algorithm.0 == CipheringAlgorithm_r12::EEA0 {
-                return true;
-            }
+            result.merge(security_algorithm.into());
         }
-        false
+        result
     }
 
-    fn check_security_mode_command_cipher(&self, command: &SecurityModeCommand) -> bool {
+    fn check_security_mode_command(&self, command: &SecurityModeCommand) -> NullAlgorithms {
         let SecurityModeCommandCriticalExtensions::C1(c1) = &command.critical_extensions else {
-            return false;
+            return NullAlgorithms::default();
         };
         let SecurityModeCommandCriticalExtensions_c1::SecurityModeCommand_r8(r8) = &c1 else {
-            return false;
+            return NullAlgorithms::default();
         };
-        if r8
-            .security_config_smc
-            .security_algorithm_config
-            .ciphering_algorithm
-            .0
-            == CipheringAlgorithm_r12::EEA0
-        {
-            return true;
+        (&r8.security_config_smc.security_algorithm_config).into()
+    }
+
+    fn check_rrc(&self, ie: &LteInformationElement) -> Option<NullAlgorithms> {
+        let LteInformationElement::DlDcch(dcch_msg) = ie else {
+            return None;
+        };
+        let DL_DCCH_MessageType::C1(c1) = &dcch_msg.message else {
+            return None;
+        };
+        match c1 {
+            DL_DCCH_MessageType_c1::RrcConnectionReconfiguration(reconfiguration) => {
+                Some(self.check_rrc_connection_reconfiguration(reconfiguration))
+            }
+            DL_DCCH_MessageType_c1::SecurityModeCommand(command) => {
+                Some(self.check_security_mode_command(command))
+            }
+            _ => None,
         }
-        false
     }
+
+    fn check_nas(&self, ie: &LteInformationElement) -> Option<NullAlgorithms> {
+        let LteInformationElement::NAS(nas) = ie else {
+            return None;
+        };
+        let Some(EmmMessage::SecurityModeCommand(command)) = &nas.emm else {
+            return None;
+        };
+        Some(NullAlgorithms {
+            ciphering: command.selected_algorithms.ciphering == CipheringAlgorithm::Eea0,
+            integrity: command.selected_algorithms.integrity == IntegrityAlgorithm::Eia0,
+        })
+    }
+}
+
+// Skipping integrity protection lets a fake base station send commands the
+// phone can't tell apart from the real network's, so it's a stronger signal
+// than skipping ciphering, which some real networks do
+fn null_algorithms_event(layer: &str, null: NullAlgorithms) -> Option<Event> {
+    let (severity, message) = match (null.ciphering, null.integrity) {
+        (_, true) => (
+            Severity::High,
+            format!(
+                "Cell suggested use of null integrity protection (EIA0){} in {layer}, so the \
+                phone can't verify that messages come from the real network",
+                if null.ciphering {
+                    " and null cipher (EEA0)"
+                } else {
+                    ""
+                }
+            ),
+        ),
+        (true, false) => (
+            Severity::Medium,
+            format!(
+                "Cell suggested use of null cipher (EEA0) in {layer}, so traffic is sent \
+                unencrypted. Some networks do this legitimately"
+            ),
+        ),
+        (false, false) => return None,
+    };
+    Some(Event {
+        event_type: EventType::QualitativeWarning { severity },
+        message,
+    })
 }
 
 impl Analyzer for NullCipherAnalyzer {
@@ -124,36 +203,22 @@ impl Analyzer for NullCipherAnalyzer {
     }
 
     fn get_description(&self) -> Cow<str> {
-        Cow::from("Tests whether the cell suggests using a null cipher (EEA0)")
+        Cow::from(
+            "Tests whether the cell suggests using a null cipher (EEA0) or null integrity \
+            protection (EIA0) in RRC or NAS security mode commands and handovers. Null \
+            integrity is only legitimately used for emergency calls without a SIM.",
+        )
     }
 
     fn analyze_information_element(&mut self, ie: &InformationElement) -> Option<Event> {
-        let dcch_msg = match ie {
-            InformationElement::LTE(lte_ie) => match &**lte_ie {
-                LteInformationElement::DlDcch(dcch_msg) => dcch_msg,
-                _ => return None,
-            },
-            _ => return None,
-        };
-        let DL_DCCH_MessageType::C1(c1) = &dcch_msg.message else {
+        let InformationElement::LTE(lte_ie) = ie else {
             return None;
         };
-        let null_cipher_detected = match c1 {
-            DL_DCCH_MessageType_c1::RrcConnectionReconfiguration(reconfiguration) => {
-                self.check_rrc_connection_reconfiguration_cipher(reconfiguration)
-            }
-            DL_DCCH_MessageType_c1::SecurityModeCommand(command) => {
-                self.check_security_mode_command_cipher(command)
-            }
-            _ => return None,
-        };
-        if null_cipher_detected {
-            return Some(Event {
-                event_type: EventType::QualitativeWarning {
-                    severity: Severity::High,
-                },
-                message: "Cell suggested use of null cipher".to_string(),
-            });
+        if let Some(null) = self.check_rrc(lte_ie) {
+            return null_algorithms_event("RRC", null);
+        }
+        if let Some(null) = self.check_nas(lte_ie) {
+            return null_algorithms_event("NAS", null);
         }
         None
     }
@@ -1,3 +1,5 @@
+use super::analyzer::{Event, EventType, Severity};
+
 // Unpacks a pattern, or returns None.
 //
 // # Examples
@@ -31,3 +33,33 @@ macro_rules! unpack {
 
 // this is apparently how you make a macro publicly usable from this module
 pub(crate) use unpack;
+
+// Builds the event for an identity request seen `packet_num` packets into an
+// analysis. Ones within the first `packet_threshold` packets are likely from
+// the phone attaching after being turned on, so they get `early_severity` and
+// say as much.
+pub(crate) fn identity_request_event(
+    what: &str,
+    packet_num: usize,
+    packet_threshold: usize,
+    severity: Severity,
+    early_severity: Severity,
+) -> Event {
+    if packet_num < packet_threshold {
+        Event {
+            event_type: EventType::QualitativeWarning {
+                severity: early_severity,
+            },
+            message: format!(
+                "{what} detected, however it was within the first {packet_threshold} packets \
+                of this analysis. If you just turned your device on, this is likely a \
+                false-positive."
+            ),
+        }
+    } else {
+        Event {
+            event_type: EventType::QualitativeWarning { severity },
+            message: format!("{what} detected (packet {packet_num})"),
+        }
+    }
+}
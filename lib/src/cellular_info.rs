@@ -8,6 +8,11 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, FixedOffset};
 
+use crate::diag::LogBody;
+use crate::lte_ml1;
+use crate::util::bits_to_u32;
+use telcom_parser::lte_rrc::{MCC, MCC_MNC_Digit, MNC, SystemInformationBlockType1};
+
 /// Comprehensive cellular network information
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct CellularNetworkInfo {
@@ -133,6 +138,98 @@ impl CellularInfoExtractor {
         }
     }
 
+    /// Extract cellular information from a parsed log body. LTE ML1 and PHY
+    /// measurement logs are read from their parsed packets, and everything
+    /// else falls back to [Self::extract_from_log_data].
+    pub fn extract_from_log_body(
+        &mut self,
+        log_type: u16,
+        body: &LogBody,
+        timestamp: DateTime<FixedOffset>,
+    ) -> Option<CellularNetworkInfo> {
+        match body {
+            LogBody::LteML1ServingCellInfo { packet, .. } => {
+                self.extract_lte_serving_cell_info(packet, timestamp)
+            }
+            LogBody::LteML1ServingCellMeasAndEval { packet, .. } => self
+                .extract_lte_serving_cell_measurements(
+                    packet.get_earfcn(),
+                    packet.get_pci(),
+                    lte_signal_info(
+                        packet.rsrp_dbm(),
+                        packet.rsrq_db(),
+                        Some(packet.rssi_dbm()),
+                    ),
+                    Vec::new(),
+                    timestamp,
+                ),
+            LogBody::LteML1IntraFreqMeas { packet, .. } => {
+                let earfcn = packet.get_earfcn();
+                let neighbor_cells = packet
+                    .get_cells()
+                    .iter()
+                    .map(|cell| lte_neighbor_cell(earfcn, cell))
+                    .collect();
+                self.extract_lte_serving_cell_measurements(
+                    earfcn,
+                    packet.get_serving_pci(),
+                    lte_signal_info(packet.serving_rsrp_dbm(), packet.serving_rsrq_db(), None),
+                    neighbor_cells,
+                    timestamp,
+                )
+            }
+            LogBody::LtePhyServCellMeasurement { packet, .. } => {
+                let cell = packet.get_cell();
+                self.extract_lte_serving_cell_measurements(
+                    packet.get_earfcn(),
+                    cell.pci,
+                    lte_signal_info(cell.rsrp_dbm(), cell.rsrq_db(), Some(cell.rssi_dbm())),
+                    Vec::new(),
+                    timestamp,
+                )
+            }
+            LogBody::LteML1NeighborMeasurements { packet, .. } => {
+                let earfcn = packet.get_earfcn();
+                let neighbor_cells = packet
+                    .get_cells()
+                    .iter()
+                    .map(|cell| NeighborCellInfo {
+                        physical_cell_id: Some(cell.get_pci()),
+                        earfcn: Some(earfcn),
+                        rsrp: Some(cell.rsrp_dbm().round() as i16),
+                        rsrq: Some(cell.rsrq_db().round() as i16),
+                        plmn_info: None,
+                    })
+                    .collect();
+                self.extract_lte_neighbor_info(neighbor_cells, timestamp)
+            }
+            LogBody::LteML1InterFreqMeas { packet, .. } => {
+                let neighbor_cells = packet
+                    .get_cells()
+                    .into_iter()
+                    .map(|(earfcn, cell)| lte_neighbor_cell(earfcn, cell))
+                    .collect();
+                self.extract_lte_neighbor_info(neighbor_cells, timestamp)
+            }
+            LogBody::LtePhyNeighCellMeasurement { packet, .. } => {
+                let earfcn = packet.get_earfcn();
+                let neighbor_cells = packet
+                    .get_cells()
+                    .iter()
+                    .map(|cell| lte_neighbor_cell(earfcn, cell))
+                    .collect();
+                self.extract_lte_neighbor_info(neighbor_cells, timestamp)
+            }
+            LogBody::GsmL1CellId { data }
+            | LogBody::GsmRrCellInformation { data }
+            | LogBody::WcdmaCellId { data }
+            | LogBody::WcdmaServingCellInfo { data } => {
+                self.extract_from_log_data(log_type, data, timestamp)
+            }
+            _ => None,
+        }
+    }
+
     /// Extract cellular information from raw log data
     pub fn extract_from_log_data(
         &mut self,
@@ -141,15 +238,6 @@ impl CellularInfoExtractor {
         timestamp: DateTime<FixedOffset>,
     ) -> Option<CellularNetworkInfo> {
         match log_type {
-            // LTE RRC messages - extract PLMN and cell info from SIBs
-            0xb0c0 => self.extract_lte_rrc_info(log_data, timestamp),
-            
-            // LTE ML1 serving cell info
-            0xb0e4 => self.extract_lte_serving_cell_info(log_data, timestamp),
-            
-            // LTE ML1 neighbor measurements
-            0xb0e1 => self.extract_lte_neighbor_info(log_data, timestamp),
-            
             // GSM cell information
             0x513a => self.extract_gsm_cell_id(log_data, timestamp),
             0x513b => self.extract_gsm_cell_info(log_data, timestamp),
@@ -158,119 +246,134 @@ impl CellularInfoExtractor {
             0x4127 => self.extract_wcdma_cell_id(log_data, timestamp),
             0x412a => self.extract_wcdma_serving_cell_info(log_data, timestamp),
             
-            // NAS messages - extract PLMN and location info
-            0xb0ec | 0xb0ed => self.extract_nas_info(log_data, timestamp),
-            
             _ => None,
         }
     }
 
-    /// Extract information from LTE RRC messages
-    fn extract_lte_rrc_info(&mut self, data: &[u8], timestamp: DateTime<FixedOffset>) -> Option<CellularNetworkInfo> {
-        // Parse LTE RRC OTA message structure
-        if data.len() < 20 {
-            return None;
+    /// Extract the PLMN, TAC and cell identity broadcast in an LTE SIB1, and
+    /// remember them so that later measurements of the same cell carry them
+    /// too
+    pub fn extract_from_lte_sib1(
+        &mut self,
+        earfcn: u32,
+        pci: u16,
+        sib1: &SystemInformationBlockType1,
+        timestamp: DateTime<FixedOffset>,
+    ) -> Option<CellularNetworkInfo> {
+        let access_info = &sib1.cell_access_related_info;
+        // only the first PLMN is required to include its MCC, the rest
+        // inherit it from the entry before them
+        let mut mcc: Option<&MCC> = None;
+        let mut plmn_info = None;
+        for plmn in &access_info.plmn_identity_list.0 {
+            mcc = plmn.plmn_identity.mcc.as_ref().or(mcc);
+            if plmn_info.is_none() {
+                plmn_info = mcc.map(|mcc| lte_plmn_info(mcc, &plmn.plmn_identity.mnc));
+            }
         }
+        let cell_identity = bits_to_u32(access_info.cell_identity.0.iter().map(|bit| *bit));
+        let tac = bits_to_u32(access_info.tracking_area_code.0.iter().map(|bit| *bit)) as u16;
 
-        // Extract basic info from RRC header
-        let phy_cell_id = u16::from_le_bytes([data[7], data[8]]);
-        let _earfcn = if data.len() > 12 {
-            Some(u32::from_le_bytes([data[9], data[10], data[11], data[12]]))
-        } else {
-            Some(u16::from_le_bytes([data[9], data[10]]) as u32)
-        };
-
-        Some(CellularNetworkInfo {
+        let info = CellularNetworkInfo {
             timestamp,
             rat: RadioAccessTechnology::LTE,
-            plmn_info: None, // Will be populated from SIB parsing
+            plmn_info,
             cell_info: Some(CellInfo {
-                physical_cell_id: Some(phy_cell_id),
-                global_cell_id: None,
-                cell_identity: None,
-                enodeb_id: None,
-                sector_id: None,
+                physical_cell_id: Some(pci),
+                global_cell_id: Some(cell_identity),
+                cell_identity: Some(cell_identity),
+                enodeb_id: Some(cell_identity >> 8),
+                sector_id: Some((cell_identity & 0xff) as u8),
+            }),
+            location_info: Some(LocationInfo {
+                lac: None,
+                rac: None,
+                tac: Some(tac),
+                tracking_area_id: None,
             }),
-            location_info: None,
             signal_info: None,
             neighbor_cells: Vec::new(),
-        })
+        };
+        self.current_info.insert(lte_cell_key(earfcn, pci), info.clone());
+        Some(info)
     }
 
-    /// Extract LTE serving cell information
-    fn extract_lte_serving_cell_info(&mut self, data: &[u8], timestamp: DateTime<FixedOffset>) -> Option<CellularNetworkInfo> {
-        // Parse LTE ML1 serving cell info structure
-        if data.len() < 16 {
-            return None;
+    /// Returns the LTE cell on the given EARFCN and PCI, with its PLMN, TAC
+    /// and cell identity filled in if we've seen its SIB1
+    fn lte_serving_cell(
+        &self,
+        earfcn: u32,
+        pci: u16,
+        timestamp: DateTime<FixedOffset>,
+    ) -> CellularNetworkInfo {
+        match self.current_info.get(&lte_cell_key(earfcn, pci)) {
+            Some(info) => CellularNetworkInfo {
+                timestamp,
+                ..info.clone()
+            },
+            None => CellularNetworkInfo {
+                timestamp,
+                rat: RadioAccessTechnology::LTE,
+                plmn_info: None,
+                cell_info: Some(CellInfo {
+                    physical_cell_id: Some(pci),
+                    global_cell_id: None,
+                    cell_identity: None,
+                    enodeb_id: None,
+                    sector_id: None,
+                }),
+                location_info: None,
+                signal_info: None,
+                neighbor_cells: Vec::new(),
+            },
         }
+    }
 
-        // Extract EARFCN and PCI (positions may vary based on log version)
-        let _earfcn = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
-        let phy_cell_id = u16::from_le_bytes([data[4], data[5]]);
-        
-        // Extract signal measurements if available
-        let rsrp = if data.len() > 8 {
-            Some(i16::from_le_bytes([data[6], data[7]]))
-        } else {
-            None
-        };
-        
-        let rsrq = if data.len() > 10 {
-            Some(i16::from_le_bytes([data[8], data[9]]))
-        } else {
-            None
-        };
-
+    /// Extract LTE serving cell information from an ML1 Serving Cell Info
+    /// log
+    fn extract_lte_serving_cell_info(
+        &mut self,
+        packet: &lte_ml1::ServingCellInfo,
+        timestamp: DateTime<FixedOffset>,
+    ) -> Option<CellularNetworkInfo> {
         Some(CellularNetworkInfo {
-            timestamp,
-            rat: RadioAccessTechnology::LTE,
-            plmn_info: None,
-            cell_info: Some(CellInfo {
-                physical_cell_id: Some(phy_cell_id),
-                global_cell_id: None,
-                cell_identity: None,
-                enodeb_id: None,
-                sector_id: None,
-            }),
-            location_info: None,
             signal_info: Some(SignalInfo {
-                rsrp,
-                rsrq,
+                rsrp: None,
+                rsrq: None,
                 rssi: None,
                 sinr: None,
                 cqi: None,
-                bandwidth: None,
+                bandwidth: packet.dl_bandwidth_mhz().map(|mhz| mhz as u8),
             }),
-            neighbor_cells: Vec::new(),
+            ..self.lte_serving_cell(packet.get_earfcn(), packet.get_pci(), timestamp)
+        })
+    }
+
+    /// Extract LTE serving cell signal measurements
+    fn extract_lte_serving_cell_measurements(
+        &mut self,
+        earfcn: u32,
+        pci: u16,
+        signal_info: SignalInfo,
+        neighbor_cells: Vec<NeighborCellInfo>,
+        timestamp: DateTime<FixedOffset>,
+    ) -> Option<CellularNetworkInfo> {
+        Some(CellularNetworkInfo {
+            signal_info: Some(signal_info),
+            neighbor_cells,
+            ..self.lte_serving_cell(earfcn, pci, timestamp)
         })
     }
 
     /// Extract LTE neighbor cell information
-    fn extract_lte_neighbor_info(&mut self, data: &[u8], timestamp: DateTime<FixedOffset>) -> Option<CellularNetworkInfo> {
-        if data.len() < 8 {
+    fn extract_lte_neighbor_info(
+        &mut self,
+        neighbor_cells: Vec<NeighborCellInfo>,
+        timestamp: DateTime<FixedOffset>,
+    ) -> Option<CellularNetworkInfo> {
+        if neighbor_cells.is_empty() {
             return None;
         }
-
-        let mut neighbor_cells = Vec::new();
-        
-        // Parse neighbor cell measurements (format varies by log version)
-        let mut offset = 4; // Skip header
-        while offset + 8 <= data.len() {
-            let phy_cell_id = u16::from_le_bytes([data[offset], data[offset + 1]]);
-            let earfcn = u32::from_le_bytes([data[offset + 2], data[offset + 3], data[offset + 4], data[offset + 5]]);
-            let rsrp = i16::from_le_bytes([data[offset + 6], data[offset + 7]]);
-            
-            neighbor_cells.push(NeighborCellInfo {
-                physical_cell_id: Some(phy_cell_id),
-                earfcn: Some(earfcn),
-                rsrp: Some(rsrp),
-                rsrq: None,
-                plmn_info: None,
-            });
-            
-            offset += 8;
-        }
-
         Some(CellularNetworkInfo {
             timestamp,
             rat: RadioAccessTechnology::LTE,
@@ -417,77 +520,6 @@ impl CellularInfoExtractor {
         })
     }
 
-    /// Extract information from NAS messages
-    fn extract_nas_info(&mut self, data: &[u8], timestamp: DateTime<FixedOffset>) -> Option<CellularNetworkInfo> {
-        if data.len() < 10 {
-            return None;
-        }
-
-        // Skip NAS header and extract PLMN and location info from EMM messages
-        // This is a simplified extraction - full NAS parsing would require more complex logic
-        
-        // Look for PLMN ID in common NAS message positions
-        if data.len() > 15 {
-            // Try to extract PLMN from various NAS message types
-            let plmn_bytes = &data[10..13]; // Common PLMN position in many NAS messages
-            if let Some(plmn_info) = self.decode_plmn_from_bytes(plmn_bytes) {
-                return Some(CellularNetworkInfo {
-                    timestamp,
-                    rat: RadioAccessTechnology::LTE,
-                    plmn_info: Some(plmn_info),
-                    cell_info: None,
-                    location_info: None, // TAC would need more sophisticated parsing
-                    signal_info: None,
-                    neighbor_cells: Vec::new(),
-                });
-            }
-        }
-
-        None
-    }
-
-    /// Decode PLMN from 3-byte BCD format
-    fn decode_plmn_from_bytes(&self, plmn_bytes: &[u8]) -> Option<PlmnInfo> {
-        if plmn_bytes.len() < 3 {
-            return None;
-        }
-
-        // PLMN is encoded in BCD format across 3 bytes
-        // Byte 0: MCC digit 2 | MCC digit 1
-        // Byte 1: MNC digit 3 | MCC digit 3  
-        // Byte 2: MNC digit 2 | MNC digit 1
-        
-        let mcc_digit1 = plmn_bytes[0] & 0x0f;
-        let mcc_digit2 = (plmn_bytes[0] >> 4) & 0x0f;
-        let mcc_digit3 = plmn_bytes[1] & 0x0f;
-        
-        let mnc_digit3 = (plmn_bytes[1] >> 4) & 0x0f;
-        let mnc_digit1 = plmn_bytes[2] & 0x0f;
-        let mnc_digit2 = (plmn_bytes[2] >> 4) & 0x0f;
-        
-        // Check for valid BCD digits
-        if mcc_digit1 > 9 || mcc_digit2 > 9 || mcc_digit3 > 9 ||
-           mnc_digit1 > 9 || mnc_digit2 > 9 || (mnc_digit3 > 9 && mnc_digit3 != 0xf) {
-            return None;
-        }
-        
-        let mcc = mcc_digit1 as u16 + (mcc_digit2 as u16) * 10 + (mcc_digit3 as u16) * 100;
-        
-        let mnc = if mnc_digit3 == 0xf {
-            // 2-digit MNC
-            mnc_digit1 as u16 + (mnc_digit2 as u16) * 10
-        } else {
-            // 3-digit MNC
-            mnc_digit1 as u16 + (mnc_digit2 as u16) * 10 + (mnc_digit3 as u16) * 100
-        };
-        
-        Some(PlmnInfo {
-            mcc: Some(mcc),
-            mnc: Some(mnc),
-            plmn_id: Some(format!("{:03}{:02}", mcc, mnc)),
-        })
-    }
-
     /// Get all collected cellular information
     pub fn get_all_info(&self) -> Vec<&CellularNetworkInfo> {
         self.current_info.values().collect()
@@ -499,6 +531,44 @@ impl CellularInfoExtractor {
     }
 }
 
+fn lte_cell_key(earfcn: u32, pci: u16) -> String {
+    format!("lte:{earfcn}:{pci}")
+}
+
+fn lte_plmn_info(mcc: &MCC, mnc: &MNC) -> PlmnInfo {
+    let digits = |digits: &[MCC_MNC_Digit]| -> String {
+        digits.iter().map(|digit| digit.0.to_string()).collect()
+    };
+    let mcc = digits(&mcc.0);
+    let mnc = digits(&mnc.0);
+    PlmnInfo {
+        mcc: mcc.parse().ok(),
+        mnc: mnc.parse().ok(),
+        plmn_id: Some(format!("{mcc}{mnc}")),
+    }
+}
+
+fn lte_signal_info(rsrp: f32, rsrq: f32, rssi: Option<f32>) -> SignalInfo {
+    SignalInfo {
+        rsrp: Some(rsrp.round() as i16),
+        rsrq: Some(rsrq.round() as i16),
+        rssi: rssi.map(|rssi| rssi.round() as i16),
+        sinr: None,
+        cqi: None,
+        bandwidth: None,
+    }
+}
+
+fn lte_neighbor_cell(earfcn: u32, cell: &lte_ml1::MeasuredCell) -> NeighborCellInfo {
+    NeighborCellInfo {
+        physical_cell_id: Some(cell.pci),
+        earfcn: Some(earfcn),
+        rsrp: Some(cell.rsrp_dbm().round() as i16),
+        rsrq: Some(cell.rsrq_db().round() as i16),
+        plmn_info: None,
+    }
+}
+
 impl Default for CellularInfoExtractor {
     fn default() -> Self {
         Self::new()
@@ -511,16 +581,65 @@ mod tests {
 
     #[test]
     fn test_plmn_decoding() {
-        let extractor = CellularInfoExtractor::new();
-        
-        // Test 3-digit MNC (MCC=310, MNC=410)
-        let plmn_bytes = [0x13, 0x00, 0x14]; 
-        let plmn_info = extractor.decode_plmn_from_bytes(&plmn_bytes);
-        assert!(plmn_info.is_some());
-        
-        // Test 2-digit MNC (MCC=310, MNC=41)
-        let plmn_bytes = [0x13, 0xf0, 0x14];
-        let plmn_info = extractor.decode_plmn_from_bytes(&plmn_bytes);
-        assert!(plmn_info.is_some());
+        let digits = |digits: &[u8]| digits.iter().map(|d| MCC_MNC_Digit(*d)).collect();
+
+        // 3-digit MNC (MCC=310, MNC=410)
+        let plmn_info = lte_plmn_info(&MCC(digits(&[3, 1, 0])), &MNC(digits(&[4, 1, 0])));
+        assert_eq!(plmn_info.mcc, Some(310));
+        assert_eq!(plmn_info.mnc, Some(410));
+        assert_eq!(plmn_info.plmn_id.as_deref(), Some("310410"));
+
+        // 2-digit MNC (MCC=310, MNC=41)
+        let plmn_info = lte_plmn_info(&MCC(digits(&[3, 1, 0])), &MNC(digits(&[4, 1])));
+        assert_eq!(plmn_info.mnc, Some(41));
+        assert_eq!(plmn_info.plmn_id.as_deref(), Some("31041"));
+
+        // 3-digit MNC with a leading zero (MCC=310, MNC=010)
+        let plmn_info = lte_plmn_info(&MCC(digits(&[3, 1, 0])), &MNC(digits(&[0, 1, 0])));
+        assert_eq!(plmn_info.mnc, Some(10));
+        assert_eq!(plmn_info.plmn_id.as_deref(), Some("310010"));
+    }
+
+    #[test]
+    fn test_lte_cell_identity_by_pci_and_earfcn() {
+        let mut extractor = CellularInfoExtractor::new();
+        let timestamp = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap();
+        let packet = lte_ml1::ServingCellInfo::V2 {
+            dl_bandwidth: 5,
+            sfn: 0,
+            earfcn: 66876,
+            pci_info: 260,
+            reserved: 0,
+        };
+
+        // before we've seen a SIB1, all we know is the PCI
+        let info = extractor
+            .extract_lte_serving_cell_info(&packet, timestamp)
+            .unwrap();
+        assert_eq!(info.cell_info.unwrap().physical_cell_id, Some(260));
+        assert_eq!(info.plmn_info, None);
+
+        let mut sib1_info = extractor.lte_serving_cell(66876, 260, timestamp);
+        sib1_info.cell_info.as_mut().unwrap().cell_identity = Some(0x1234501);
+        sib1_info.location_info = Some(LocationInfo {
+            lac: None,
+            rac: None,
+            tac: Some(0x1234),
+            tracking_area_id: None,
+        });
+        extractor
+            .current_info
+            .insert(lte_cell_key(66876, 260), sib1_info);
+
+        let info = extractor
+            .extract_lte_serving_cell_info(&packet, timestamp)
+            .unwrap();
+        assert_eq!(info.cell_info.unwrap().cell_identity, Some(0x1234501));
+        assert_eq!(info.location_info.unwrap().tac, Some(0x1234));
+        assert_eq!(info.signal_info.unwrap().bandwidth, Some(20));
+
+        // the same PCI on another EARFCN is a different cell
+        let info = extractor.lte_serving_cell(1300, 260, timestamp);
+        assert_eq!(info.cell_info.unwrap().cell_identity, None);
     }
 }
@@ -4,8 +4,10 @@ use chrono::{DateTime, FixedOffset};
 use crc::{Algorithm, Crc};
 use deku::prelude::*;
 
-use crate::hdlc::{self, hdlc_decapsulate};
+use crate::hdlc::{self, HdlcFrameAssembler, HdlcFrameStats, hdlc_decapsulate_borrowed};
+use crate::lte_ml1;
 use log::{error, warn};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 pub const MESSAGE_TERMINATOR: u8 = 0x7e;
@@ -25,10 +27,27 @@ pub struct RequestContainer {
 }
 
 #[derive(Debug, Clone, PartialEq, DekuWrite)]
-#[deku(id_type = "u32")]
+#[deku(id_type = "u8")]
 pub enum Request {
     #[deku(id = "115")]
-    LogConfig(LogConfigRequest),
+    LogConfig(#[deku(pad_bytes_before = "3")] LogConfigRequest),
+
+    /// Turns the modem's unsolicited [Message::EventReport]s on or off
+    #[deku(id = "0x60")]
+    EventReportControl { enable: u8 },
+
+    /// Asks for the modem's [VersionInfoResponse]
+    #[deku(id = "0x00")]
+    VersionInfo,
+
+    /// Asks for the modem's [ExtendedBuildIdResponse]
+    #[deku(id = "0x7c")]
+    ExtendedBuildId,
+
+    /// Configures which [Message::ExtendedMessage]s (F3 debug messages) the
+    /// modem sends
+    #[deku(id = "0x7d")]
+    ExtendedMessageConfig(ExtendedMessageConfigRequest),
 }
 
 #[derive(Debug, Clone, PartialEq, DekuWrite)]
@@ -45,6 +64,18 @@ pub enum LogConfigRequest {
     },
 }
 
+#[derive(Debug, Clone, PartialEq, DekuWrite)]
+#[deku(id_type = "u8", endian = "little")]
+pub enum ExtendedMessageConfigRequest {
+    /// Sets the runtime mask of every subsystem at once, so 0xffffffff turns
+    /// all F3 messages on and 0 turns them all off
+    #[deku(id = "5")]
+    SetAllRtMasks {
+        #[deku(pad_bytes_before = "2")]
+        rt_mask: u32,
+    },
+}
+
 #[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
 #[deku(id_type = "u32", endian = "little")]
 pub enum DataType {
@@ -82,34 +113,99 @@ pub struct MessagesContainer {
     pub num_messages: u32,
     #[deku(count = "num_messages")]
     pub messages: Vec<HdlcEncapsulatedMessage>,
+    /// Set by [MessagesContainer::reassemble]: how many of these messages
+    /// were pieced together from earlier containers, and how many partial
+    /// ones had to be dropped
+    #[deku(skip)]
+    pub frame_stats: HdlcFrameStats,
 }
 
 impl MessagesContainer {
+    /// Re-splits this container's messages into whole HDLC frames, one per
+    /// message. Any frame left unfinished by the previous container is
+    /// completed, and any left unfinished by this one is kept by the
+    /// assembler for the next.
+    pub fn reassemble(self, assembler: &mut HdlcFrameAssembler) -> MessagesContainer {
+        if self.data_type != DataType::UserSpace {
+            return self;
+        }
+        let mut frames = Vec::new();
+        let mut frame_stats = self.frame_stats;
+        for msg in &self.messages {
+            frame_stats += assembler.push(&msg.data, &mut frames);
+        }
+        MessagesContainer::from_frames(frames, frame_stats)
+    }
+
+    /// Builds a userspace container out of whole HDLC frames
+    pub fn from_frames(frames: Vec<Vec<u8>>, frame_stats: HdlcFrameStats) -> MessagesContainer {
+        let messages: Vec<HdlcEncapsulatedMessage> = frames
+            .into_iter()
+            .map(|data| HdlcEncapsulatedMessage {
+                len: data.len() as u32,
+                data,
+            })
+            .collect();
+        MessagesContainer {
+            data_type: DataType::UserSpace,
+            num_messages: messages.len() as u32,
+            messages,
+            frame_stats,
+        }
+    }
+
+    /// Decapsulates and parses this container's messages one at a time, as
+    /// they're iterated over
+    pub fn messages(&self) -> impl Iterator<Item = Result<Message, DiagParsingError>> + '_ {
+        self.messages
+            .iter()
+            .flat_map(|msg| msg.data.split_inclusive(|&b| b == MESSAGE_TERMINATOR))
+            .map(parse_frame)
+    }
+
     pub fn into_messages(self) -> Vec<Result<Message, DiagParsingError>> {
-        let mut result = Vec::new();
-        for msg in self.messages {
-            for sub_msg in msg.data.split_inclusive(|&b| b == MESSAGE_TERMINATOR) {
-                match hdlc_decapsulate(sub_msg, &CRC_CCITT) {
-                    Ok(data) => match Message::from_bytes((&data, 0)) {
-                        Ok(((leftover_bytes, _), res)) => {
-                            if !leftover_bytes.is_empty() {
-                                warn!(
-                                    "warning: {} leftover bytes when parsing Message",
-                                    leftover_bytes.len()
-                                );
-                            }
-                            result.push(Ok(res));
-                        }
-                        Err(e) => result.push(Err(DiagParsingError::MessageParsingError(e, data))),
-                    },
-                    Err(err) => result.push(Err(DiagParsingError::HdlcDecapsulationError(
-                        err,
-                        sub_msg.to_vec(),
-                    ))),
+        self.messages().collect()
+    }
+
+    /// Removes the first message for which `is_match` returns true from this
+    /// container and returns it, leaving the rest of the messages (including
+    /// any that fail to parse) where they were
+    pub fn take_message(&mut self, is_match: impl Fn(&Message) -> bool) -> Option<Message> {
+        let (index, range, msg) = self.messages.iter().enumerate().find_map(|(index, msg)| {
+            let mut start = 0;
+            for frame in msg.data.split_inclusive(|&b| b == MESSAGE_TERMINATOR) {
+                let end = start + frame.len();
+                if let Ok(msg) = parse_frame(frame)
+                    && is_match(&msg)
+                {
+                    return Some((index, start..end, msg));
                 }
+                start = end;
             }
+            None
+        })?;
+        let encapsulated = &mut self.messages[index];
+        encapsulated.data.drain(range);
+        encapsulated.len = encapsulated.data.len() as u32;
+        if encapsulated.data.is_empty() {
+            self.messages.remove(index);
+            self.num_messages -= 1;
         }
-        result
+        Some(msg)
+    }
+}
+
+fn parse_frame(frame: &[u8]) -> Result<Message, DiagParsingError> {
+    let data = hdlc_decapsulate_borrowed(frame, &CRC_CCITT)
+        .map_err(|err| DiagParsingError::HdlcDecapsulationError(err, frame.to_vec()))?;
+    match Message::from_multi_sim_bytes(&data) {
+        Ok((leftover_bytes, msg)) => {
+            if leftover_bytes > 0 {
+                warn!("warning: {leftover_bytes} leftover bytes when parsing Message");
+            }
+            Ok(msg)
+        }
+        Err(e) => Err(DiagParsingError::MessageParsingError(e, data.into_owned())),
     }
 }
 
@@ -133,6 +229,64 @@ pub enum Message {
         // pass the log type and log length (inner_length - (sizeof(log_type) + sizeof(timestamp)))
         #[deku(ctx = "*log_type, *inner_length - 12")]
         body: LogBody,
+        /// Which SIM this log is for, on dual-SIM modems which wrap logs in
+        /// a multi-SIM container
+        #[deku(skip)]
+        subscription_id: Option<u32>,
+    },
+
+    #[deku(id = "0x60")]
+    EventReport {
+        #[deku(endian = "little")]
+        length: u16,
+        #[deku(bytes_read = "*length")]
+        events: Vec<DiagEvent>,
+    },
+
+    /// An F3 debug message, with its format string and source file inline
+    #[deku(id = "0x79")]
+    ExtendedMessage {
+        header: ExtendedMessageHeader,
+        #[deku(count = "header.num_args", endian = "little")]
+        args: Vec<u32>,
+        #[deku(until = "|b: &u8| *b == 0")]
+        format: Vec<u8>,
+        #[deku(until = "|b: &u8| *b == 0")]
+        file: Vec<u8>,
+    },
+
+    /// A QSHRINK'd F3 debug message, whose format string and source file
+    /// have been replaced by a hash that's only resolvable with the
+    /// firmware's message hash database
+    #[deku(id = "0x92")]
+    QsrExtendedMessage {
+        header: ExtendedMessageHeader,
+        #[deku(endian = "little")]
+        hash: u32,
+        #[deku(count = "header.num_args", endian = "little")]
+        args: Vec<u32>,
+    },
+
+    #[deku(id = "0x00")]
+    VersionInfo(VersionInfoResponse),
+
+    #[deku(id = "0x7c")]
+    ExtendedBuildId(ExtendedBuildIdResponse),
+
+    /// The modem's reply to a [Request::ExtendedMessageConfig]
+    #[deku(id = "0x7d")]
+    ExtendedMessageConfig {
+        subcommand: u8,
+        #[deku(read_all)]
+        data: Vec<u8>,
+    },
+
+    /// The modem's reply to a request it doesn't support, echoing the
+    /// request back
+    #[deku(id = "0x13")]
+    BadCommand {
+        #[deku(read_all)]
+        request: Vec<u8>,
     },
 
     // kinda unpleasant deku hackery here. deku expects an enum's variant to be
@@ -201,7 +355,9 @@ pub enum LogBody {
     },
     #[deku(id = "0x11eb")]
     IpTraffic {
-        // is this right?? based on https://github.com/P1sec/QCSuper/blob/81dbaeee15ec7747e899daa8e3495e27cdcc1264/src/modules/pcap_dump.py#L378
+        // the raw IP packet is preceded by an 8 byte header, which we skip
+        // like QCSuper does: https://github.com/P1sec/QCSuper/blob/81dbaeee15ec7747e899daa8e3495e27cdcc1264/src/modules/pcap_dump.py#L378
+        header: [u8; 8],
         #[deku(count = "hdr_len - 8")]
         msg: Vec<u8>,
     },
@@ -214,35 +370,41 @@ pub enum LogBody {
     },
     #[deku(id = "0xb821")]
     NrRrcOtaMessage {
-        #[deku(count = "hdr_len")]
-        msg: Vec<u8>,
+        header_version: u32,
+        #[deku(ctx = "*header_version")]
+        packet: NrRrcOtaPacket,
     },
     
     // Enhanced cellular information log types
     #[deku(id = "0xb0e0")]
     LteML1ServingCellMeasAndEval {
-        #[deku(count = "hdr_len")]
-        data: Vec<u8>,
+        version: u8,
+        #[deku(ctx = "*version")]
+        packet: lte_ml1::ServingCellMeasAndEval,
     },
     #[deku(id = "0xb0e1")]
     LteML1NeighborMeasurements {
-        #[deku(count = "hdr_len")]
-        data: Vec<u8>,
+        version: u8,
+        #[deku(ctx = "*version")]
+        packet: lte_ml1::NeighborMeasurements,
     },
     #[deku(id = "0xb0e4")]
     LteML1ServingCellInfo {
-        #[deku(count = "hdr_len")]
-        data: Vec<u8>,
+        version: u8,
+        #[deku(ctx = "*version")]
+        packet: lte_ml1::ServingCellInfo,
     },
     #[deku(id = "0xb0e5")]
     LteML1IntraFreqMeas {
-        #[deku(count = "hdr_len")]
-        data: Vec<u8>,
+        version: u8,
+        #[deku(ctx = "*version")]
+        packet: lte_ml1::IntraFreqMeas,
     },
     #[deku(id = "0xb0e6")]
     LteML1InterFreqMeas {
-        #[deku(count = "hdr_len")]
-        data: Vec<u8>,
+        version: u8,
+        #[deku(ctx = "*version")]
+        packet: lte_ml1::InterFreqMeas,
     },
     #[deku(id = "0xb0e7")]
     LteML1InterRatMeas {
@@ -357,13 +519,15 @@ pub enum LogBody {
     // Measurement Reports and Cell Quality
     #[deku(id = "0xb0f0")]
     LtePhyServCellMeasurement {
-        #[deku(count = "hdr_len")]
-        data: Vec<u8>,
+        version: u8,
+        #[deku(ctx = "*version")]
+        packet: lte_ml1::PhyServCellMeasurement,
     },
     #[deku(id = "0xb0f1")]
     LtePhyNeighCellMeasurement {
-        #[deku(count = "hdr_len")]
-        data: Vec<u8>,
+        version: u8,
+        #[deku(ctx = "*version")]
+        packet: lte_ml1::PhyNeighCellMeasurement,
     },
 }
 
@@ -468,6 +632,15 @@ impl LteRrcOtaPacket {
         }
     }
 
+    pub fn get_phy_cell_id(&self) -> u16 {
+        match self {
+            LteRrcOtaPacket::V0 { phy_cell_id, .. } => *phy_cell_id,
+            LteRrcOtaPacket::V5 { phy_cell_id, .. } => *phy_cell_id,
+            LteRrcOtaPacket::V8 { phy_cell_id, .. } => *phy_cell_id,
+            LteRrcOtaPacket::V25 { phy_cell_id, .. } => *phy_cell_id,
+        }
+    }
+
     pub fn get_earfcn(&self) -> u32 {
         match self {
             LteRrcOtaPacket::V0 { earfcn, .. } => *earfcn as u32,
@@ -477,6 +650,15 @@ impl LteRrcOtaPacket {
         }
     }
 
+    pub fn payload(&self) -> &[u8] {
+        match self {
+            LteRrcOtaPacket::V0 { packet, .. } => packet,
+            LteRrcOtaPacket::V5 { packet, .. } => packet,
+            LteRrcOtaPacket::V8 { packet, .. } => packet,
+            LteRrcOtaPacket::V25 { packet, .. } => packet,
+        }
+    }
+
     pub fn take_payload(self) -> Vec<u8> {
         match self {
             LteRrcOtaPacket::V0 { packet, .. } => packet,
@@ -487,6 +669,227 @@ impl LteRrcOtaPacket {
     }
 }
 
+// based on https://github.com/fgsect/scat/blob/master/src/scat/parsers/qualcomm/diagnrlogparser.py
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(ctx = "header_version: u32", id = "header_version")]
+pub enum NrRrcOtaPacket {
+    #[deku(id_pat = "0..=16")]
+    V0 {
+        rrc_rel_maj: u8,
+        rrc_rel_min: u8,
+        bearer_id: u8,
+        phy_cell_id: u16,
+        nr_arfcn: u32,
+        #[deku(bytes = 3)]
+        sfn_subfn: u32,
+        pdu_num: u8,
+        sib_mask: u32,
+        len: u16,
+        #[deku(count = "len")]
+        packet: Vec<u8>,
+    },
+    #[deku(id_pat = "17..")]
+    V17 {
+        rrc_rel_maj: u8,
+        rrc_rel_min: u8,
+        bearer_id: u8,
+        phy_cell_id: u16,
+        // not decoded
+        unknown: u64,
+        nr_arfcn: u32,
+        #[deku(bytes = 3)]
+        sfn_subfn: u32,
+        pdu_num: u8,
+        sib_mask: u32,
+        len: u16,
+        #[deku(count = "len")]
+        packet: Vec<u8>,
+    },
+}
+
+impl NrRrcOtaPacket {
+    pub fn get_pdu_num(&self) -> u8 {
+        match self {
+            NrRrcOtaPacket::V0 { pdu_num, .. } => *pdu_num,
+            NrRrcOtaPacket::V17 { pdu_num, .. } => *pdu_num,
+        }
+    }
+
+    pub fn get_nr_arfcn(&self) -> u32 {
+        match self {
+            NrRrcOtaPacket::V0 { nr_arfcn, .. } => *nr_arfcn,
+            NrRrcOtaPacket::V17 { nr_arfcn, .. } => *nr_arfcn,
+        }
+    }
+
+    pub fn get_phy_cell_id(&self) -> u16 {
+        match self {
+            NrRrcOtaPacket::V0 { phy_cell_id, .. } => *phy_cell_id,
+            NrRrcOtaPacket::V17 { phy_cell_id, .. } => *phy_cell_id,
+        }
+    }
+
+    pub fn take_payload(self) -> Vec<u8> {
+        match self {
+            NrRrcOtaPacket::V0 { packet, .. } => packet,
+            NrRrcOtaPacket::V17 { packet, .. } => packet,
+        }
+    }
+}
+
+/// A single event out of an [Message::EventReport]
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+pub struct DiagEvent {
+    // bits 0-11 are the event ID, bits 13-14 the payload length (or 3 if a
+    // length byte follows) and bit 15 is set if the timestamp is truncated
+    #[deku(endian = "little")]
+    pub id_and_flags: u16,
+    #[deku(cond = "*id_and_flags & 0x8000 == 0")]
+    pub timestamp: Option<Timestamp>,
+    // lower bytes of the timestamp, to be combined with the last full one
+    #[deku(cond = "*id_and_flags & 0x8000 != 0", endian = "little")]
+    pub truncated_timestamp: Option<u16>,
+    #[deku(cond = "(*id_and_flags >> 13) & 0x3 == 0x3")]
+    pub payload_len: Option<u8>,
+    #[deku(count = "event_payload_len(*id_and_flags, *payload_len)")]
+    pub payload: Vec<u8>,
+}
+
+fn event_payload_len(id_and_flags: u16, payload_len: Option<u8>) -> usize {
+    match (id_and_flags >> 13) & 0x3 {
+        0x3 => payload_len.unwrap_or(0) as usize,
+        len => len as usize,
+    }
+}
+
+impl DiagEvent {
+    pub fn id(&self) -> u16 {
+        self.id_and_flags & 0xfff
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+pub struct ExtendedMessageHeader {
+    pub ts_type: u8,
+    pub num_args: u8,
+    pub drop_count: u8,
+    pub timestamp: Timestamp,
+    #[deku(endian = "little")]
+    pub line: u16,
+    #[deku(endian = "little")]
+    pub subsystem_id: u16,
+    #[deku(endian = "little")]
+    pub subsystem_mask: u32,
+}
+
+// the multi-SIM container's command code, 3 reserved bytes and the
+// subscription ID, followed by the actual message
+const DIAG_MULTI_RADIO_CMD_F: u8 = 0x98;
+
+impl Message {
+    /// Parses a decapsulated message, unwrapping it first if it's in a
+    /// multi-SIM container. Returns the number of leftover bytes alongside
+    /// the message.
+    pub fn from_multi_sim_bytes(data: &[u8]) -> Result<(usize, Message), DekuError> {
+        let (data, multi_sim_id) = match data {
+            [DIAG_MULTI_RADIO_CMD_F, _, _, _, a, b, c, d, inner @ ..] => {
+                (inner, Some(u32::from_le_bytes([*a, *b, *c, *d])))
+            }
+            _ => (data, None),
+        };
+        let ((leftover_bytes, _), mut msg) = Message::from_bytes((data, 0))?;
+        if let Message::Log {
+            subscription_id, ..
+        } = &mut msg
+        {
+            *subscription_id = multi_sim_id;
+        }
+        Ok((leftover_bytes.len(), msg))
+    }
+
+    /// For an [Message::ExtendedMessage], returns its format string and
+    /// source file
+    pub fn f3_strings(&self) -> Option<(String, String)> {
+        let Message::ExtendedMessage { format, file, .. } = self else {
+            return None;
+        };
+        Some((c_string(format), c_string(file)))
+    }
+}
+
+/// What the modem tells us about itself, so that captures from different
+/// basebands can be told apart
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ModemInfo {
+    /// The baseband firmware build
+    pub firmware: Option<String>,
+    /// The model the firmware was built for
+    pub model: Option<String>,
+}
+
+/// The reply to [Request::VersionInfo]. The dates and times are when the
+/// firmware was compiled and released, in ASCII.
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "little")]
+pub struct VersionInfoResponse {
+    pub comp_date: [u8; 11],
+    pub comp_time: [u8; 8],
+    pub rel_date: [u8; 11],
+    pub rel_time: [u8; 8],
+    pub ver_dir: [u8; 8],
+    pub scm: u8,
+    pub mob_cai_rev: u8,
+    pub mob_model: u8,
+    pub mob_firm_rev: u16,
+    pub slot_cycle_index: u8,
+    pub hw_maj_ver: u8,
+    pub hw_min_ver: u8,
+}
+
+impl VersionInfoResponse {
+    /// Describes the firmware by its version directory and release date,
+    /// which is all older modems tell us about it
+    pub fn firmware(&self) -> String {
+        format!(
+            "{} ({} {})",
+            c_string(&self.ver_dir).trim(),
+            c_string(&self.rel_date).trim(),
+            c_string(&self.rel_time).trim()
+        )
+    }
+}
+
+/// The reply to [Request::ExtendedBuildId], naming the baseband firmware
+/// build and the model it was built for
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(endian = "little")]
+pub struct ExtendedBuildIdResponse {
+    pub msm_hw_version_format: u8,
+    pub reserved: u16,
+    pub msm_hw_version: u32,
+    pub mobile_model_id: u32,
+    #[deku(until = "|b: &u8| *b == 0")]
+    pub build_id: Vec<u8>,
+    #[deku(until = "|b: &u8| *b == 0")]
+    pub model: Vec<u8>,
+}
+
+impl ExtendedBuildIdResponse {
+    pub fn build_id(&self) -> String {
+        c_string(&self.build_id)
+    }
+
+    pub fn model(&self) -> String {
+        c_string(&self.model)
+    }
+}
+
+// reads a string which may be NUL-terminated or NUL-padded
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
 #[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
 #[deku(endian = "little")]
 pub struct Timestamp {
@@ -556,6 +959,7 @@ pub fn build_log_mask_request(
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::log_codes;
 
     // Just about all of these test cases from manually parsing diag packets w/ QCSuper
 
@@ -573,17 +977,120 @@ mod test {
             req.to_bytes().unwrap(),
             vec![115, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,]
         );
+
+        let req = Request::EventReportControl { enable: 1 };
+        assert_eq!(req.to_bytes().unwrap(), vec![0x60, 1]);
+
+        let req = Request::ExtendedMessageConfig(ExtendedMessageConfigRequest::SetAllRtMasks {
+            rt_mask: 0xffffffff,
+        });
+        assert_eq!(
+            req.to_bytes().unwrap(),
+            vec![0x7d, 5, 0, 0, 0xff, 0xff, 0xff, 0xff]
+        );
+    }
+
+    #[test]
+    fn test_f3_messages() {
+        let mut data = vec![
+            0x79, // extended message
+            0x00, 0x02, 0x00, // ts_type, num_args, drop_count
+            0x00, 0x00, 0xe0, 0x4b, 0x3c, 0x1b, 0x0d, 0x01, // timestamp
+            0x2a, 0x00, // line
+            0x0a, 0x26, // subsystem ID
+            0x04, 0x00, 0x00, 0x00, // subsystem mask
+            0x05, 0x00, 0x00, 0x00, 0x01, 0x01, 0x00, 0x00, // args
+        ];
+        data.extend(b"RRC state %d -> %d\0lte_rrc_controller.c\0");
+        let (_, msg) = Message::from_bytes((&data, 0)).unwrap();
+        let Message::ExtendedMessage { header, args, .. } = &msg else {
+            panic!("expected extended message, got {msg:?}");
+        };
+        assert_eq!(header.line, 42);
+        assert_eq!(header.subsystem_id, 0x260a);
+        assert_eq!(args, &vec![5, 0x101]);
+        assert_eq!(
+            msg.f3_strings(),
+            Some((
+                "RRC state %d -> %d".to_string(),
+                "lte_rrc_controller.c".to_string()
+            ))
+        );
+
+        let data = vec![
+            0x92, // QSR extended message
+            0x00, 0x01, 0x00, // ts_type, num_args, drop_count
+            0x00, 0x00, 0xe0, 0x4b, 0x3c, 0x1b, 0x0d, 0x01, // timestamp
+            0x2a, 0x00, // line
+            0x0a, 0x26, // subsystem ID
+            0x04, 0x00, 0x00, 0x00, // subsystem mask
+            0xef, 0xbe, 0xad, 0xde, // hash
+            0x07, 0x00, 0x00, 0x00, // args
+        ];
+        let (_, msg) = Message::from_bytes((&data, 0)).unwrap();
+        let Message::QsrExtendedMessage { hash, args, .. } = &msg else {
+            panic!("expected QSR extended message, got {msg:?}");
+        };
+        assert_eq!(*hash, 0xdeadbeef);
+        assert_eq!(args, &vec![7]);
+        assert_eq!(msg.f3_strings(), None);
+    }
+
+    #[test]
+    fn test_modem_identity_responses() {
+        assert_eq!(Request::VersionInfo.to_bytes().unwrap(), vec![0x00]);
+        assert_eq!(Request::ExtendedBuildId.to_bytes().unwrap(), vec![0x7c]);
+
+        let mut data = vec![0x00]; // version info
+        data.extend(b"Nov 01 202322:41:07Nov 02 202303:15:00MPSS_2_0");
+        data.extend([0x02, 0x06, 0x31, 0x34, 0x12, 0x01, 0x01, 0x00]);
+        let (_, msg) = Message::from_bytes((&data, 0)).unwrap();
+        let Message::VersionInfo(version) = &msg else {
+            panic!("expected version info, got {msg:?}");
+        };
+        assert_eq!(version.mob_model, 0x31);
+        assert_eq!(version.mob_firm_rev, 0x1234);
+        assert_eq!(version.firmware(), "MPSS_2_0 (Nov 02 2023 03:15:00)");
+
+        let mut data = vec![
+            0x7c, // extended build ID
+            0x01, 0x00, 0x00, // hw version format, reserved
+            0xe1, 0x00, 0x0c, 0x20, // MSM hw version
+            0x02, 0x10, 0x00, 0x00, // mobile model ID
+        ];
+        data.extend(b"MPSS.JO.2.0.c1.4-00047-M9607\0RC400L\0");
+        let (_, msg) = Message::from_bytes((&data, 0)).unwrap();
+        let Message::ExtendedBuildId(build) = &msg else {
+            panic!("expected extended build ID, got {msg:?}");
+        };
+        assert_eq!(build.mobile_model_id, 0x1002);
+        assert_eq!(build.build_id(), "MPSS.JO.2.0.c1.4-00047-M9607");
+        assert_eq!(build.model(), "RC400L");
+
+        let data = vec![0x13, 0x7c];
+        let (_, msg) = Message::from_bytes((&data, 0)).unwrap();
+        assert_eq!(
+            msg,
+            Message::BadCommand {
+                request: vec![0x7c]
+            }
+        );
     }
 
     #[test]
     fn test_build_log_mask_request() {
         let log_type = 11;
         let bitsize = 513;
-        let req = build_log_mask_request(
-            log_type,
-            bitsize,
-            &crate::diag_device::LOG_CODES_FOR_RAW_PACKET_LOGGING,
-        );
+        let log_codes = [
+            log_codes::LOG_LTE_RRC_OTA_MSG_LOG_C,
+            log_codes::LOG_LTE_NAS_ESM_OTA_IN_MSG_LOG_C,
+            log_codes::LOG_LTE_NAS_ESM_OTA_OUT_MSG_LOG_C,
+            log_codes::LOG_LTE_NAS_EMM_OTA_IN_MSG_LOG_C,
+            log_codes::LOG_LTE_NAS_EMM_OTA_OUT_MSG_LOG_C,
+            // not in log type 11, so shouldn't show up in the mask
+            log_codes::LOG_GSM_RR_SIGNALING_MESSAGE_C,
+        ];
+        let req = build_log_mask_request(log_type, bitsize, &log_codes);
         assert_eq!(
             req,
             Request::LogConfig(LogConfigRequest::SetMask {
@@ -653,15 +1160,104 @@ mod test {
                         packet: vec![0x40, 0x1, 0xee, 0xad, 0xd5, 0x4d, 0xd0],
                     },
                 },
+                subscription_id: None,
             }
         );
     }
 
+    #[test]
+    fn test_multi_sim_container() {
+        let log = vec![
+            16, 0, 38, 0, 38, 0, 192, 176, 26, 165, 245, 135, 118, 35, 2, 1, 20, 14, 48, 0, 160, 0,
+            2, 8, 0, 0, 217, 15, 5, 0, 0, 0, 0, 7, 0, 64, 1, 238, 173, 213, 77, 208,
+        ];
+        let mut data = vec![0x98, 0, 0, 0, 1, 0, 0, 0];
+        data.extend(&log);
+        let container = make_container(
+            DataType::UserSpace,
+            HdlcEncapsulatedMessage {
+                len: 0,
+                data: hdlc::hdlc_encapsulate(&data, &CRC_CCITT),
+            },
+        );
+        let mut messages = container.into_messages();
+        assert_eq!(messages.len(), 1);
+        let msg = messages.remove(0).unwrap();
+        let Message::Log {
+            log_type,
+            subscription_id,
+            ..
+        } = &msg
+        else {
+            panic!("expected log message, got {msg:?}");
+        };
+        assert_eq!(*log_type, 0xb0c0);
+        assert_eq!(*subscription_id, Some(1));
+
+        let (_, gsmtap_msg) = crate::gsmtap_parser::parse(msg).unwrap().unwrap();
+        assert_eq!(gsmtap_msg.header.radio_id, 1);
+
+        // without the container, there's no telling which SIM it's for
+        let (_, msg) = Message::from_multi_sim_bytes(&log).unwrap();
+        assert!(matches!(
+            msg,
+            Message::Log {
+                subscription_id: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_ip_traffic_log() {
+        let mut data = vec![16, 0, 40, 0, 40, 0, 0xeb, 0x11, 0, 0, 0, 0, 0, 0, 0, 0];
+        data.extend([0x01, 0x00, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x00]);
+        let packet = vec![
+            0x45, 0x00, 0x00, 0x14, 0x00, 0x00, 0x40, 0x00, 0x40, 0x01, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x01, 0x08, 0x08, 0x08, 0x08,
+        ];
+        data.extend(&packet);
+        let (_, msg) = Message::from_bytes((&data, 0)).unwrap();
+        let Message::Log {
+            body: LogBody::IpTraffic { msg, .. },
+            ..
+        } = msg
+        else {
+            panic!("expected IpTraffic log");
+        };
+        assert_eq!(msg, packet);
+    }
+
+    #[test]
+    fn test_nr_rrc_ota_log() {
+        let data = vec![
+            16, 0, 38, 0, 38, 0, 0x21, 0xb8, 26, 165, 245, 135, 118, 35, 2, 1, 9, 0, 0, 0, 15, 64,
+            1, 1, 0, 0x34, 0xa7, 0x09, 0, 0, 0, 0, 1, 0, 0, 0, 0, 3, 0, 0x54, 0x60, 0x04,
+        ];
+        let msg = Message::from_bytes((&data, 0)).unwrap().1;
+        let Message::Log {
+            body: LogBody::NrRrcOtaMessage {
+                header_version,
+                packet,
+            },
+            ..
+        } = msg
+        else {
+            panic!("expected NR RRC OTA message");
+        };
+        assert_eq!(header_version, 9);
+        assert_eq!(packet.get_pdu_num(), 1);
+        assert_eq!(packet.get_phy_cell_id(), 1);
+        assert_eq!(packet.get_nr_arfcn(), 632628);
+        assert_eq!(packet.take_payload(), vec![0x54, 0x60, 0x04]);
+    }
+
     fn make_container(data_type: DataType, message: HdlcEncapsulatedMessage) -> MessagesContainer {
         MessagesContainer {
             data_type,
             num_messages: 1,
             messages: vec![message],
+            frame_stats: HdlcFrameStats::default(),
         }
     }
 
@@ -693,6 +1289,7 @@ mod test {
                     packet: payload.to_vec(),
                 },
             },
+            subscription_id: None,
         };
         let serialized = message
             .to_bytes()
@@ -725,6 +1322,33 @@ mod test {
         assert_eq!(container.into_messages(), vec![Ok(message1), Ok(message2)]);
     }
 
+    #[test]
+    fn test_take_message() {
+        let (mut encapsulated1, message1) = get_test_message(&[1]);
+        let (encapsulated2, message2) = get_test_message(&[2]);
+        let (encapsulated3, message3) = get_test_message(&[3]);
+        encapsulated1.data.extend(encapsulated2.data);
+        encapsulated1.len += encapsulated2.len;
+        let mut container = make_container(DataType::UserSpace, encapsulated1);
+        container.messages.push(encapsulated3);
+        container.num_messages += 1;
+
+        // from the middle of a concatenated message
+        assert_eq!(
+            container.take_message(|msg| *msg == message2),
+            Some(message2.clone())
+        );
+        assert_eq!(container.num_messages, 2);
+        assert_eq!(container.take_message(|msg| *msg == message2), None);
+        // a whole message
+        assert_eq!(
+            container.take_message(|msg| *msg == message3),
+            Some(message3)
+        );
+        assert_eq!(container.num_messages, 1);
+        assert_eq!(container.into_messages(), vec![Ok(message1)]);
+    }
+
     #[test]
     fn test_handles_parsing_errors() {
         let (encapsulated1, message1) = get_test_message(&[1]);
@@ -761,4 +1385,31 @@ mod test {
             Err(DiagParsingError::HdlcDecapsulationError(_, _))
         ));
     }
+
+    #[test]
+    fn test_reassembling_containers() {
+        let (encapsulated1, message1) = get_test_message(&[1]);
+        let (encapsulated2, message2) = get_test_message(&[2]);
+        let mut data = encapsulated1.data;
+        data.extend(encapsulated2.data);
+        // the second message straddles the two containers
+        let (first, second) = data.split_at(data.len() - 10);
+        let containers = [first, second].map(|data| {
+            make_container(
+                DataType::UserSpace,
+                HdlcEncapsulatedMessage {
+                    len: data.len() as u32,
+                    data: data.to_vec(),
+                },
+            )
+        });
+
+        let mut assembler = HdlcFrameAssembler::new();
+        let [first, second] = containers.map(|container| container.reassemble(&mut assembler));
+        assert_eq!(first.num_messages, 1);
+        assert!(first.frame_stats.is_empty());
+        assert_eq!(first.into_messages(), vec![Ok(message1)]);
+        assert_eq!(second.frame_stats.reassembled, 1);
+        assert_eq!(second.into_messages(), vec![Ok(message2)]);
+    }
 }
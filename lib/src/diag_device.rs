@@ -1,13 +1,19 @@
 use crate::diag::{
-    CRC_CCITT, DataType, DiagParsingError, LogConfigRequest, LogConfigResponse, Message,
-    MessagesContainer, Request, RequestContainer, ResponsePayload, build_log_mask_request,
+    CRC_CCITT, DataType, DiagParsingError, ExtendedMessageConfigRequest, LogConfigRequest,
+    LogConfigResponse, Message, MessagesContainer, Request, RequestContainer, ResponsePayload,
+    build_log_mask_request,
 };
-use crate::hdlc::hdlc_encapsulate;
+// kept here too, where it was before it was needed on platforms without a
+// diag device
+pub use crate::diag::ModemInfo;
+use crate::hdlc::{HdlcFrameAssembler, hdlc_encapsulate};
 use crate::log_codes;
 
 use deku::prelude::*;
 use futures::TryStream;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::io::ErrorKind;
 use std::os::fd::AsRawFd;
 use std::time::Duration;
@@ -30,6 +36,8 @@ pub enum DiagDeviceError {
     RequestFailed(u32, Request),
     #[error("Didn't receive response for request: {0:?}")]
     NoResponse(Request),
+    #[error("Modem doesn't support diag request: {0:?}")]
+    UnsupportedRequest(Request),
     #[error("Failed to open QMDL file: {0}")]
     OpenQmdlFileError(std::io::Error),
     #[error("Failed to write to QMDL file: {0}")]
@@ -38,64 +46,145 @@ pub enum DiagDeviceError {
     OpenDiagDeviceError(std::io::Error),
     #[error("Failed to parse MessagesContainer: {0}")]
     ParseMessagesContainerError(deku::DekuError),
+    #[error("Failed to read QMDL replay file: {0}")]
+    ReplayReadFailed(std::io::Error),
 }
 
-pub const LOG_CODES_FOR_RAW_PACKET_LOGGING: [u32; 39] = [
-    // Layer 2:
-    log_codes::LOG_GPRS_MAC_SIGNALLING_MESSAGE_C, // 0x5226
-    // Layer 3:
-    log_codes::LOG_GSM_RR_SIGNALING_MESSAGE_C, // 0x512f
-    log_codes::WCDMA_SIGNALLING_MESSAGE,       // 0x412f
-    log_codes::LOG_LTE_RRC_OTA_MSG_LOG_C,      // 0xb0c0
-    log_codes::LOG_NR_RRC_OTA_MSG_LOG_C,       // 0xb821
-    // NAS:
-    log_codes::LOG_UMTS_NAS_OTA_MESSAGE_LOG_PACKET_C, // 0x713a
-    log_codes::LOG_LTE_NAS_ESM_OTA_IN_MSG_LOG_C,      // 0xb0e2
-    log_codes::LOG_LTE_NAS_ESM_OTA_OUT_MSG_LOG_C,     // 0xb0e3
-    log_codes::LOG_LTE_NAS_EMM_OTA_IN_MSG_LOG_C,      // 0xb0ec
-    log_codes::LOG_LTE_NAS_EMM_OTA_OUT_MSG_LOG_C,     // 0xb0ed
-    // User IP traffic:
-    log_codes::LOG_DATA_PROTOCOL_LOGGING_C, // 0x11eb
-    
-    // Enhanced cellular information capture:
-    // LTE/4G Serving Cell and Neighbor Information
-    log_codes::LOG_LTE_ML1_SERVING_CELL_MEAS_AND_EVAL, // 0xb0e0
-    log_codes::LOG_LTE_ML1_NEIGHBOR_MEASUREMENTS,      // 0xb0e1
-    log_codes::LOG_LTE_ML1_SERVING_CELL_INFO,          // 0xb0e4
-    log_codes::LOG_LTE_ML1_INTRA_FREQ_MEAS,           // 0xb0e5
-    log_codes::LOG_LTE_ML1_INTER_FREQ_MEAS,           // 0xb0e6
-    log_codes::LOG_LTE_ML1_INTER_RAT_MEAS,            // 0xb0e7
-    log_codes::LOG_LTE_ML1_CELL_RESEL_CANDIDATES,     // 0xb0e8
-    log_codes::LOG_LTE_ML1_COMMON_DL_CONFIG,          // 0xb0ea
-    log_codes::LOG_LTE_ML1_SERVING_CELL_COM_LOOP,     // 0xb0eb
-    
-    // LTE System Information
-    log_codes::LOG_LTE_RRC_MEAS_CFG,      // 0xb0c1
-    log_codes::LOG_LTE_RRC_CELL_INFO,     // 0xb0c2
-    log_codes::LOG_LTE_RRC_STATE,         // 0xb0c3
-    log_codes::LOG_LTE_RRC_PLMN_SEARCH_INFO, // 0xb0c4
-    
-    // GSM/2G Cell Information  
-    log_codes::LOG_GSM_L1_BURST_METRICS,  // 0x5134
-    log_codes::LOG_GSM_L1_SCELL_BA_LIST,  // 0x5135
-    log_codes::LOG_GSM_L1_NCELL_ACQ,      // 0x5136
-    log_codes::LOG_GSM_L1_NCELL_BA_LIST,  // 0x5137
-    log_codes::LOG_GSM_CELL_OPTIONS,      // 0x5138
-    log_codes::LOG_GSM_POWER_SCAN,        // 0x5139
-    log_codes::LOG_GSM_L1_CELL_ID,        // 0x513a
-    log_codes::LOG_GSM_RR_CELL_INFORMATION, // 0x513b
-    
-    // WCDMA/3G Cell Information
-    log_codes::LOG_WCDMA_CELL_ID,         // 0x4127
-    log_codes::LOG_WCDMA_RRC_STATES,      // 0x4128
-    log_codes::LOG_WCDMA_PLMN_SEARCH,     // 0x4129
-    log_codes::LOG_WCDMA_SERVING_CELL_INFO, // 0x412a
-    log_codes::LOG_WCDMA_NEIGHBOR_CELL_INFO, // 0x412b
-    
-    // Measurement Reports and Cell Quality
-    log_codes::LOG_LTE_PHY_SERV_CELL_MEASUREMENT, // 0xb0f0
-    log_codes::LOG_LTE_PHY_NEIGH_CELL_MEASUREMENT, // 0xb0f1
-];
+/// Groups of related log codes which can be enabled together, so that
+/// captures can trade QMDL size against detail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogCodeGroup {
+    /// Layer 2/3 signalling (RRC and friends) for every RAT
+    L3Signalling,
+    /// 3G and LTE NAS messages
+    Nas,
+    /// LTE RRC state, cell info and measurement config
+    LteRrcInfo,
+    /// LTE ML1 and PHY serving/neighbor cell measurements
+    Ml1Measurements,
+    /// GSM L1 cell information and power scans
+    GsmL1,
+    /// WCDMA cell information and RRC states
+    Wcdma,
+    /// User-plane IP traffic
+    IpTraffic,
+}
+
+impl LogCodeGroup {
+    pub const ALL: [LogCodeGroup; 7] = [
+        LogCodeGroup::L3Signalling,
+        LogCodeGroup::Nas,
+        LogCodeGroup::LteRrcInfo,
+        LogCodeGroup::Ml1Measurements,
+        LogCodeGroup::GsmL1,
+        LogCodeGroup::Wcdma,
+        LogCodeGroup::IpTraffic,
+    ];
+
+    pub fn log_codes(&self) -> &'static [u32] {
+        match self {
+            LogCodeGroup::L3Signalling => &[
+                log_codes::LOG_GPRS_MAC_SIGNALLING_MESSAGE_C, // 0x5226
+                log_codes::LOG_GSM_RR_SIGNALING_MESSAGE_C,    // 0x512f
+                log_codes::WCDMA_SIGNALLING_MESSAGE,          // 0x412f
+                log_codes::LOG_LTE_RRC_OTA_MSG_LOG_C,         // 0xb0c0
+                log_codes::LOG_NR_RRC_OTA_MSG_LOG_C,          // 0xb821
+            ],
+            LogCodeGroup::Nas => &[
+                log_codes::LOG_UMTS_NAS_OTA_MESSAGE_LOG_PACKET_C, // 0x713a
+                log_codes::LOG_LTE_NAS_ESM_OTA_IN_MSG_LOG_C,      // 0xb0e2
+                log_codes::LOG_LTE_NAS_ESM_OTA_OUT_MSG_LOG_C,     // 0xb0e3
+                log_codes::LOG_LTE_NAS_EMM_OTA_IN_MSG_LOG_C,      // 0xb0ec
+                log_codes::LOG_LTE_NAS_EMM_OTA_OUT_MSG_LOG_C,     // 0xb0ed
+            ],
+            LogCodeGroup::LteRrcInfo => &[
+                log_codes::LOG_LTE_RRC_MEAS_CFG,         // 0xb0c1
+                log_codes::LOG_LTE_RRC_CELL_INFO,        // 0xb0c2
+                log_codes::LOG_LTE_RRC_STATE,            // 0xb0c3
+                log_codes::LOG_LTE_RRC_PLMN_SEARCH_INFO, // 0xb0c4
+            ],
+            LogCodeGroup::Ml1Measurements => &[
+                log_codes::LOG_LTE_ML1_SERVING_CELL_MEAS_AND_EVAL, // 0xb0e0
+                log_codes::LOG_LTE_ML1_NEIGHBOR_MEASUREMENTS,      // 0xb0e1
+                log_codes::LOG_LTE_ML1_SERVING_CELL_INFO,          // 0xb0e4
+                log_codes::LOG_LTE_ML1_INTRA_FREQ_MEAS,            // 0xb0e5
+                log_codes::LOG_LTE_ML1_INTER_FREQ_MEAS,            // 0xb0e6
+                log_codes::LOG_LTE_ML1_INTER_RAT_MEAS,             // 0xb0e7
+                log_codes::LOG_LTE_ML1_CELL_RESEL_CANDIDATES,      // 0xb0e8
+                log_codes::LOG_LTE_ML1_COMMON_DL_CONFIG,           // 0xb0ea
+                log_codes::LOG_LTE_ML1_SERVING_CELL_COM_LOOP,      // 0xb0eb
+                log_codes::LOG_LTE_PHY_SERV_CELL_MEASUREMENT,      // 0xb0f0
+                log_codes::LOG_LTE_PHY_NEIGH_CELL_MEASUREMENT,     // 0xb0f1
+            ],
+            LogCodeGroup::GsmL1 => &[
+                log_codes::LOG_GSM_L1_BURST_METRICS,    // 0x5134
+                log_codes::LOG_GSM_L1_SCELL_BA_LIST,    // 0x5135
+                log_codes::LOG_GSM_L1_NCELL_ACQ,        // 0x5136
+                log_codes::LOG_GSM_L1_NCELL_BA_LIST,    // 0x5137
+                log_codes::LOG_GSM_CELL_OPTIONS,        // 0x5138
+                log_codes::LOG_GSM_POWER_SCAN,          // 0x5139
+                log_codes::LOG_GSM_L1_CELL_ID,          // 0x513a
+                log_codes::LOG_GSM_RR_CELL_INFORMATION, // 0x513b
+            ],
+            LogCodeGroup::Wcdma => &[
+                log_codes::LOG_WCDMA_CELL_ID,            // 0x4127
+                log_codes::LOG_WCDMA_RRC_STATES,         // 0x4128
+                log_codes::LOG_WCDMA_PLMN_SEARCH,        // 0x4129
+                log_codes::LOG_WCDMA_SERVING_CELL_INFO,  // 0x412a
+                log_codes::LOG_WCDMA_NEIGHBOR_CELL_INFO, // 0x412b
+            ],
+            LogCodeGroup::IpTraffic => &[
+                log_codes::LOG_DATA_PROTOCOL_LOGGING_C, // 0x11eb
+            ],
+        }
+    }
+}
+
+/// Which log codes the modem is asked to send us
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LogMaskConfig {
+    pub groups: Vec<LogCodeGroup>,
+    /// Log codes to enable on top of `groups`
+    pub extra_log_codes: Vec<u32>,
+    /// Whether the modem should also send event reports, e.g. for RRC state
+    /// changes
+    pub event_reports: bool,
+    /// Whether the modem should also send its F3 debug messages. These are
+    /// chatty, so they're off by default
+    pub f3_messages: bool,
+}
+
+impl Default for LogMaskConfig {
+    fn default() -> Self {
+        LogMaskConfig {
+            groups: LogCodeGroup::ALL.to_vec(),
+            extra_log_codes: Vec::new(),
+            event_reports: true,
+            f3_messages: false,
+        }
+    }
+}
+
+impl LogMaskConfig {
+    pub fn log_codes(&self) -> Vec<u32> {
+        let mut log_codes: Vec<u32> = self
+            .groups
+            .iter()
+            .flat_map(|group| group.log_codes())
+            .chain(&self.extra_log_codes)
+            .copied()
+            .collect();
+        log_codes.sort_unstable();
+        log_codes.dedup();
+        log_codes
+    }
+}
+
+// Once logging is enabled, responses arrive interleaved with log messages, so
+// we may have to read a fair few containers before we get to ours
+const MAX_CONTAINERS_BEFORE_RESPONSE: usize = 100;
 
 const BUFFER_LEN: usize = 1024 * 1024 * 10;
 const MEMORY_DEVICE_MODE: u32 = 2;
@@ -118,53 +207,28 @@ const DIAG_IOCTL_SWITCH_LOGGING: u64 = 7;
 #[cfg(all(not(target_env = "musl"), target_arch = "aarch64"))]
 const DIAG_IOCTL_SWITCH_LOGGING: u64 = 7;
 
-pub struct DiagDevice {
+/// The link a [DiagDevice] exchanges diag messages with the modem over
+pub trait DiagTransport {
+    /// Reads the next batch of messages sent by the modem
+    fn read_messages_container(
+        &mut self,
+    ) -> impl Future<Output = DiagResult<MessagesContainer>> + Send;
+
+    /// Sends a serialized (but not yet HDLC encapsulated) request to the
+    /// modem
+    fn write_request(&mut self, req_bytes: &[u8]) -> impl Future<Output = DiagResult<()>> + Send;
+}
+
+/// Diag over the /dev/diag character device, as found on the devices
+/// rayhunter runs on
+pub struct DevDiagTransport {
     file: File,
     read_buf: Vec<u8>,
     use_mdm: i32,
 }
 
-impl DiagDevice {
-    pub async fn new() -> DiagResult<Self> {
-        Self::new_with_retries(Duration::from_secs(30)).await
-    }
-
-    pub async fn new_with_retries(max_duration: Duration) -> DiagResult<Self> {
-        // For some reason the diag device needs a very long time to become available again with in
-        // the same process, on TP-Link M7350 v3. While process restart would reset it faster.
-
-        let start_time = std::time::Instant::now();
-        let max_delay = Duration::from_secs(5);
-
-        let mut delay = Duration::from_millis(100);
-        let mut num_retries = 0;
-
-        loop {
-            match Self::try_new().await {
-                Ok(device) => {
-                    info!("Diag device initialization succeeded after {num_retries} retries");
-                    return Ok(device);
-                }
-                Err(e) => {
-                    num_retries += 1;
-                    if start_time.elapsed() >= max_duration {
-                        error!("Failed to initialize diag device after {max_duration:?}: {e}");
-                        return Err(e);
-                    }
-
-                    info!(
-                        "Diag device initialization failed {num_retries} times, retrying in {delay:?}: {e}"
-                    );
-                    sleep(delay).await;
-
-                    // Exponential backoff
-                    delay = std::cmp::min(delay * 2, max_delay);
-                }
-            }
-        }
-    }
-
-    async fn try_new() -> DiagResult<Self> {
+impl DevDiagTransport {
+    pub async fn open() -> DiagResult<Self> {
         let diag_file = File::options()
             .read(true)
             .write(true)
@@ -176,23 +240,16 @@ impl DiagDevice {
         enable_frame_readwrite(fd, MEMORY_DEVICE_MODE)?;
         let use_mdm = determine_use_mdm(fd)?;
 
-        Ok(DiagDevice {
+        Ok(DevDiagTransport {
             read_buf: vec![0; BUFFER_LEN],
             file: diag_file,
             use_mdm,
         })
     }
+}
 
-    pub fn as_stream(
-        &mut self,
-    ) -> impl TryStream<Ok = MessagesContainer, Error = DiagDeviceError> + '_ {
-        futures::stream::try_unfold(self, |dev| async {
-            let container = dev.get_next_messages_container().await?;
-            Ok(Some((container, dev)))
-        })
-    }
-
-    async fn get_next_messages_container(&mut self) -> Result<MessagesContainer, DiagDeviceError> {
+impl DiagTransport for DevDiagTransport {
+    async fn read_messages_container(&mut self) -> DiagResult<MessagesContainer> {
         let mut bytes_read = 0;
         // TP-Link M7350 sometimes sends too small messages, we need to be able to deal with short reads.
         while bytes_read <= 8 {
@@ -215,8 +272,7 @@ impl DiagDevice {
         }
     }
 
-    async fn write_request(&mut self, req: &Request) -> DiagResult<()> {
-        let req_bytes = &req.to_bytes().expect("Failed to serialize Request");
+    async fn write_request(&mut self, req_bytes: &[u8]) -> DiagResult<()> {
         let buf = RequestContainer {
             data_type: DataType::UserSpace,
             use_mdm: self.use_mdm > 0,
@@ -241,70 +297,248 @@ impl DiagDevice {
         }
         Ok(())
     }
+}
+
+pub struct DiagDevice<T: DiagTransport = DevDiagTransport> {
+    transport: T,
+    // frames can straddle containers, so hold on to any partial ones
+    assembler: HdlcFrameAssembler,
+    // containers read while waiting for the response to a request, which
+    // still have to be streamed
+    pending: VecDeque<MessagesContainer>,
+    log_codes: Vec<u32>,
+    event_reports: bool,
+    f3_messages: bool,
+}
+
+impl DiagDevice {
+    pub async fn new() -> DiagResult<Self> {
+        Self::new_with_retries(Duration::from_secs(30)).await
+    }
+
+    pub async fn new_with_retries(max_duration: Duration) -> DiagResult<Self> {
+        // For some reason the diag device needs a very long time to become available again with in
+        // the same process, on TP-Link M7350 v3. While process restart would reset it faster.
+
+        let start_time = std::time::Instant::now();
+        let max_delay = Duration::from_secs(5);
+
+        let mut delay = Duration::from_millis(100);
+        let mut num_retries = 0;
 
-    async fn read_response(&mut self) -> DiagResult<Vec<Result<Message, DiagParsingError>>> {
         loop {
-            let container = self.get_next_messages_container().await?;
-            if container.data_type != DataType::UserSpace {
-                continue;
+            match DevDiagTransport::open().await {
+                Ok(transport) => {
+                    info!("Diag device initialization succeeded after {num_retries} retries");
+                    return Ok(DiagDevice::with_transport(transport));
+                }
+                Err(e) => {
+                    num_retries += 1;
+                    if start_time.elapsed() >= max_duration {
+                        error!("Failed to initialize diag device after {max_duration:?}: {e}");
+                        return Err(e);
+                    }
+
+                    info!(
+                        "Diag device initialization failed {num_retries} times, retrying in {delay:?}: {e}"
+                    );
+                    sleep(delay).await;
+
+                    // Exponential backoff
+                    delay = std::cmp::min(delay * 2, max_delay);
+                }
             }
-            return Ok(container.into_messages());
         }
     }
+}
 
-    async fn retrieve_id_ranges(&mut self) -> DiagResult<[u32; 16]> {
-        let req = Request::LogConfig(LogConfigRequest::RetrieveIdRanges);
-        self.write_request(&req).await?;
+impl<T: DiagTransport> DiagDevice<T> {
+    pub fn with_transport(transport: T) -> Self {
+        DiagDevice {
+            transport,
+            assembler: HdlcFrameAssembler::new(),
+            pending: VecDeque::new(),
+            log_codes: LogMaskConfig::default().log_codes(),
+            event_reports: LogMaskConfig::default().event_reports,
+            f3_messages: LogMaskConfig::default().f3_messages,
+        }
+    }
+
+    /// Sets which log codes to enable. Takes effect the next time
+    /// [DiagDevice::config_logs] is called, which can be done at any point
+    /// while reading.
+    pub fn set_log_codes(&mut self, log_codes: Vec<u32>) {
+        self.log_codes = log_codes;
+    }
+
+    pub fn log_codes(&self) -> &[u32] {
+        &self.log_codes
+    }
+
+    pub fn event_reports(&self) -> bool {
+        self.event_reports
+    }
+
+    /// Sets whether the modem should send event reports. Like
+    /// [DiagDevice::set_log_codes], takes effect on the next
+    /// [DiagDevice::config_logs].
+    pub fn set_event_reports(&mut self, enable: bool) {
+        self.event_reports = enable;
+    }
+
+    pub fn f3_messages(&self) -> bool {
+        self.f3_messages
+    }
+
+    /// Sets whether the modem should send F3 debug messages. Like
+    /// [DiagDevice::set_log_codes], takes effect on the next
+    /// [DiagDevice::config_logs].
+    pub fn set_f3_messages(&mut self, enable: bool) {
+        self.f3_messages = enable;
+    }
+
+    /// Streams the containers read from the device, with their messages
+    /// split into whole HDLC frames. A frame which straddles two containers
+    /// is yielded with the second. Containers read while waiting for the
+    /// response to a request come first, less the response itself.
+    pub fn as_stream(
+        &mut self,
+    ) -> impl TryStream<Ok = MessagesContainer, Error = DiagDeviceError> + '_ {
+        futures::stream::try_unfold(self, |dev| async {
+            let container = match dev.pending.pop_front() {
+                Some(container) => container,
+                None => dev.read_messages_container().await?,
+            };
+            Ok(Some((container, dev)))
+        })
+    }
+
+    async fn read_messages_container(&mut self) -> DiagResult<MessagesContainer> {
+        let container = self.transport.read_messages_container().await?;
+        Ok(container.reassemble(&mut self.assembler))
+    }
 
-        for msg in self.read_response().await? {
-            match msg {
-                Ok(Message::Log { .. }) => info!("skipping log response..."),
-                Ok(Message::Response {
-                    payload, status, ..
-                }) => match payload {
-                    ResponsePayload::LogConfig(LogConfigResponse::RetrieveIdRanges {
-                        log_mask_sizes,
-                    }) => {
-                        if status != 0 {
-                            return Err(DiagDeviceError::RequestFailed(status, req));
-                        }
-                        return Ok(log_mask_sizes);
+    async fn write_request(&mut self, req: &Request) -> DiagResult<()> {
+        let req_bytes = &req.to_bytes().expect("Failed to serialize Request");
+        self.transport.write_request(req_bytes).await
+    }
+
+    async fn read_response(&mut self) -> DiagResult<MessagesContainer> {
+        loop {
+            let container = self.read_messages_container().await?;
+            if container.data_type == DataType::UserSpace {
+                return Ok(container);
+            }
+            self.pending.push_back(container);
+        }
+    }
+
+    // Writes a request and reads messages until one for which is_response
+    // returns true. Everything else that's read in the meantime, logs
+    // included, is kept for the stream, since this can be called in the
+    // middle of a recording.
+    async fn request(
+        &mut self,
+        req: &Request,
+        is_response: impl Fn(&Message) -> bool,
+    ) -> DiagResult<Message> {
+        self.write_request(req).await?;
+
+        for _ in 0..MAX_CONTAINERS_BEFORE_RESPONSE {
+            let mut container = self.read_response().await?;
+            let response = container
+                .take_message(|msg| is_response(msg) || matches!(msg, Message::BadCommand { .. }));
+            if container.num_messages > 0 {
+                self.pending.push_back(container);
+            }
+            match response {
+                Some(Message::BadCommand { .. }) => {
+                    return Err(DiagDeviceError::UnsupportedRequest(req.clone()));
+                }
+                Some(msg) => return Ok(msg),
+                None => debug!("skipping unrelated messages..."),
+            }
+        }
+
+        Err(DiagDeviceError::NoResponse(req.clone()))
+    }
+
+    async fn request_log_config(&mut self, req: &Request) -> DiagResult<LogConfigResponse> {
+        let msg = self
+            .request(req, |msg| {
+                matches!(
+                    msg,
+                    Message::Response {
+                        payload: ResponsePayload::LogConfig(_),
+                        ..
                     }
-                    _ => info!("skipping non-LogConfigResponse response..."),
-                },
-                Err(e) => error!("error parsing message: {e:?}"),
+                )
+            })
+            .await?;
+        match msg {
+            Message::Response {
+                payload: ResponsePayload::LogConfig(response),
+                status,
+                ..
+            } => {
+                if status != 0 {
+                    return Err(DiagDeviceError::RequestFailed(status, req.clone()));
+                }
+                Ok(response)
             }
+            _ => Err(DiagDeviceError::NoResponse(req.clone())),
         }
+    }
 
-        Err(DiagDeviceError::NoResponse(req))
+    async fn retrieve_id_ranges(&mut self) -> DiagResult<[u32; 16]> {
+        let req = Request::LogConfig(LogConfigRequest::RetrieveIdRanges);
+        match self.request_log_config(&req).await? {
+            LogConfigResponse::RetrieveIdRanges { log_mask_sizes } => Ok(log_mask_sizes),
+            _ => Err(DiagDeviceError::NoResponse(req)),
+        }
     }
 
     async fn set_log_mask(&mut self, log_type: u32, log_mask_bitsize: u32) -> DiagResult<()> {
-        let req = build_log_mask_request(
-            log_type,
-            log_mask_bitsize,
-            &LOG_CODES_FOR_RAW_PACKET_LOGGING,
-        );
-        self.write_request(&req).await?;
+        let req = build_log_mask_request(log_type, log_mask_bitsize, &self.log_codes);
+        match self.request_log_config(&req).await? {
+            LogConfigResponse::SetMask => Ok(()),
+            _ => Err(DiagDeviceError::NoResponse(req)),
+        }
+    }
 
-        for msg in self.read_response().await? {
-            match msg {
-                Ok(Message::Log { .. }) => info!("skipping log response..."),
-                Ok(Message::Response {
-                    payload, status, ..
-                }) => {
-                    if let ResponsePayload::LogConfig(LogConfigResponse::SetMask) = payload {
-                        if status != 0 {
-                            return Err(DiagDeviceError::RequestFailed(status, req));
-                        }
-                        return Ok(());
-                    }
-                }
-                Err(e) => error!("error parsing message: {e:?}"),
+    /// Asks the modem for its firmware build and model. Newer modems answer
+    /// an extended build ID request; for those that don't, we fall back to
+    /// what the version info request tells us.
+    pub async fn modem_info(&mut self) -> DiagResult<ModemInfo> {
+        let req = Request::ExtendedBuildId;
+        match self
+            .request(&req, |msg| matches!(msg, Message::ExtendedBuildId(_)))
+            .await
+        {
+            Ok(Message::ExtendedBuildId(build)) => {
+                let model = build.model();
+                return Ok(ModemInfo {
+                    firmware: Some(build.build_id()),
+                    model: (!model.is_empty()).then_some(model),
+                });
+            }
+            Ok(_) | Err(DiagDeviceError::UnsupportedRequest(_)) => {
+                info!("modem doesn't support extended build IDs, asking for version info");
             }
+            Err(e) => return Err(e),
         }
 
-        Err(DiagDeviceError::NoResponse(req))
+        let req = Request::VersionInfo;
+        match self
+            .request(&req, |msg| matches!(msg, Message::VersionInfo(_)))
+            .await?
+        {
+            Message::VersionInfo(version) => Ok(ModemInfo {
+                firmware: Some(version.firmware()),
+                model: Some(format!("mobile model {}", version.mob_model)),
+            }),
+            _ => Err(DiagDeviceError::NoResponse(req)),
+        }
     }
 
     pub async fn config_logs(&mut self) -> DiagResult<()> {
@@ -318,6 +552,33 @@ impl DiagDevice {
             }
         }
 
+        // the modem acks this with an empty event report, which there's no
+        // telling apart from a real one, so don't wait around for it
+        let req = Request::EventReportControl {
+            enable: self.event_reports as u8,
+        };
+        self.write_request(&req).await?;
+        info!("event reports enabled: {}", self.event_reports);
+
+        self.config_f3_messages().await
+    }
+
+    async fn config_f3_messages(&mut self) -> DiagResult<()> {
+        let rt_mask = if self.f3_messages { 0xffffffff } else { 0 };
+        let req =
+            Request::ExtendedMessageConfig(ExtendedMessageConfigRequest::SetAllRtMasks { rt_mask });
+        match self
+            .request(&req, |msg| {
+                matches!(msg, Message::ExtendedMessageConfig { .. })
+            })
+            .await
+        {
+            Ok(_) => info!("F3 messages enabled: {}", self.f3_messages),
+            Err(DiagDeviceError::UnsupportedRequest(_)) => {
+                warn!("modem doesn't support configuring F3 messages")
+            }
+            Err(e) => return Err(e),
+        }
         Ok(())
     }
 }
@@ -0,0 +1,175 @@
+//! Enumerates some relevant diag event IDs, and decodes the RRC and EMM state
+//! changes they report. Modem state transitions often only show up as events,
+//! never as OTA messages.
+
+use crate::diag::{DiagEvent, Timestamp};
+
+pub const EVENT_LTE_RRC_STATE_CHANGE: u16 = 1606;
+pub const EVENT_LTE_RRC_OUT_OF_SERVICE: u16 = 1607;
+pub const EVENT_LTE_RRC_RADIO_LINK_FAILURE: u16 = 1608;
+pub const EVENT_LTE_RRC_NEW_CELL_IND: u16 = 1611;
+pub const EVENT_LTE_EMM_INCOMING_MSG: u16 = 1629;
+pub const EVENT_LTE_EMM_OUTGOING_MSG: u16 = 1630;
+pub const EVENT_LTE_EMM_TIMER_START: u16 = 1631;
+pub const EVENT_LTE_EMM_TIMER_EXPIRY: u16 = 1632;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LteRrcState {
+    IdleNotCamped,
+    IdleCamped,
+    Connecting,
+    Connected,
+    Closing,
+    Other(u8),
+}
+
+impl From<u8> for LteRrcState {
+    fn from(state: u8) -> Self {
+        match state {
+            1 => LteRrcState::IdleNotCamped,
+            2 => LteRrcState::IdleCamped,
+            3 => LteRrcState::Connecting,
+            4 => LteRrcState::Connected,
+            7 => LteRrcState::Closing,
+            other => LteRrcState::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateChange {
+    LteRrcState(LteRrcState),
+    LteRrcOutOfService,
+    LteRrcRadioLinkFailure,
+    LteRrcNewCell,
+    /// The modem's EMM layer received a NAS message. The ID is the modem's
+    /// own, and isn't the NAS message type.
+    LteEmmIncomingMessage(u32),
+    /// The modem's EMM layer sent a NAS message
+    LteEmmOutgoingMessage(u32),
+    /// An EMM timer (e.g. T3410 for attach) was started
+    LteEmmTimerStart(u8),
+    /// An EMM timer ran out, usually because the network didn't answer
+    LteEmmTimerExpiry(u8),
+}
+
+impl StateChange {
+    pub fn from_event(event: &DiagEvent) -> Option<Self> {
+        match event.id() {
+            EVENT_LTE_RRC_STATE_CHANGE => event
+                .payload
+                .first()
+                .map(|&state| StateChange::LteRrcState(state.into())),
+            EVENT_LTE_RRC_OUT_OF_SERVICE => Some(StateChange::LteRrcOutOfService),
+            EVENT_LTE_RRC_RADIO_LINK_FAILURE => Some(StateChange::LteRrcRadioLinkFailure),
+            EVENT_LTE_RRC_NEW_CELL_IND => Some(StateChange::LteRrcNewCell),
+            EVENT_LTE_EMM_INCOMING_MSG => {
+                message_id(&event.payload).map(StateChange::LteEmmIncomingMessage)
+            }
+            EVENT_LTE_EMM_OUTGOING_MSG => {
+                message_id(&event.payload).map(StateChange::LteEmmOutgoingMessage)
+            }
+            EVENT_LTE_EMM_TIMER_START => event
+                .payload
+                .first()
+                .map(|&timer| StateChange::LteEmmTimerStart(timer)),
+            EVENT_LTE_EMM_TIMER_EXPIRY => event
+                .payload
+                .first()
+                .map(|&timer| StateChange::LteEmmTimerExpiry(timer)),
+            _ => None,
+        }
+    }
+}
+
+fn message_id(payload: &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(payload.get(..4)?.try_into().ok()?))
+}
+
+/// Returns every state change in an event report along with when it
+/// happened. Events with a truncated timestamp are given the timestamp of the
+/// last full one before them.
+pub fn state_changes(events: &[DiagEvent]) -> Vec<(Option<Timestamp>, StateChange)> {
+    let mut last_timestamp = None;
+    let mut changes = Vec::new();
+    for event in events {
+        if let Some(timestamp) = &event.timestamp {
+            last_timestamp = Some(timestamp.clone());
+        }
+        if let Some(change) = StateChange::from_event(event) {
+            changes.push((last_timestamp.clone(), change));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diag::Message;
+    use deku::prelude::*;
+
+    #[test]
+    fn test_event_report_state_changes() {
+        let data = vec![
+            0x60, // event report
+            0x17, 0x00, // length
+            // LTE RRC state change with a full timestamp and a 1 byte payload
+            0x46, 0x26, 0x00, 0x00, 0xe0, 0x4b, 0x3c, 0x1b, 0x0d, 0x01, 0x04,
+            // some other event with a 2 byte payload and truncated timestamp
+            0x01, 0xc1, 0x34, 0x12, 0xaa, 0xbb,
+            // radio link failure with a truncated timestamp and a length byte
+            0x48, 0xe6, 0x00, 0x00, 0x01, 0xff,
+        ];
+        let (_, msg) = Message::from_bytes((&data, 0)).unwrap();
+        let Message::EventReport { events, .. } = msg else {
+            panic!("expected event report, got {msg:?}");
+        };
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].id(), EVENT_LTE_RRC_STATE_CHANGE);
+        assert_eq!(events[1].id(), 0x101);
+        assert_eq!(events[1].truncated_timestamp, Some(0x1234));
+        assert_eq!(events[1].payload, vec![0xaa, 0xbb]);
+        assert_eq!(events[2].payload, vec![0xff]);
+
+        let changes = state_changes(&events);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes[0].1,
+            StateChange::LteRrcState(LteRrcState::Connected)
+        );
+        assert_eq!(changes[1].1, StateChange::LteRrcRadioLinkFailure);
+        assert_eq!(changes[1].0, changes[0].0);
+        assert!(changes[0].0.is_some());
+    }
+
+    #[test]
+    fn test_emm_state_changes() {
+        let data = vec![
+            0x60, // event report
+            0x13, 0x00, // length
+            // EMM outgoing message with a truncated timestamp and a 4 byte
+            // payload behind a length byte
+            0x5e, 0xe6, 0x00, 0x00, 0x04, 0x41, 0x07, 0x00, 0x00,
+            // EMM timer expiry with a truncated timestamp and a 1 byte payload
+            0x60, 0xa6, 0x00, 0x00, 0x10,
+            // EMM incoming message that's too short for its ID
+            0x5d, 0xa6, 0x00, 0x00, 0x42,
+        ];
+        let (_, msg) = Message::from_bytes((&data, 0)).unwrap();
+        let Message::EventReport { events, .. } = msg else {
+            panic!("expected event report, got {msg:?}");
+        };
+        let changes: Vec<_> = state_changes(&events)
+            .into_iter()
+            .map(|(_, change)| change)
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                StateChange::LteEmmOutgoingMessage(0x0741),
+                StateChange::LteEmmTimerExpiry(0x10),
+            ]
+        );
+    }
+}
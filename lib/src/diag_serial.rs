@@ -0,0 +1,94 @@
+//! Diag over a serial tty, the way QCSuper talks to Qualcomm phones and USB
+//! modems through /dev/ttyUSB*. Unlike /dev/diag there are no ioctls or
+//! container headers: requests and responses are just HDLC frames written to
+//! and read from the tty.
+
+use std::os::fd::AsRawFd;
+use std::path::Path;
+
+use crate::diag::{CRC_CCITT, MessagesContainer};
+use crate::diag_device::{DiagDeviceError, DiagResult, DiagTransport};
+use crate::hdlc::{HdlcFrameAssembler, HdlcFrameStats, hdlc_encapsulate};
+
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const READ_BUFFER_LEN: usize = 1024 * 64;
+
+pub struct SerialTransport {
+    file: File,
+    read_buf: Vec<u8>,
+    // holds on to frames which haven't been terminated yet
+    assembler: HdlcFrameAssembler,
+}
+
+impl SerialTransport {
+    pub async fn open<P: AsRef<Path>>(path: P) -> DiagResult<Self> {
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .open(path)
+            .await
+            .map_err(DiagDeviceError::OpenDiagDeviceError)?;
+        set_raw_mode(file.as_raw_fd())?;
+        Ok(SerialTransport {
+            file,
+            read_buf: vec![0; READ_BUFFER_LEN],
+            assembler: HdlcFrameAssembler::new(),
+        })
+    }
+}
+
+impl DiagTransport for SerialTransport {
+    async fn read_messages_container(&mut self) -> DiagResult<MessagesContainer> {
+        // return every frame we've got the end of, keeping the rest around
+        // until the next read
+        let mut frames = Vec::new();
+        let mut frame_stats = HdlcFrameStats::default();
+        while frames.is_empty() {
+            let bytes_read = self
+                .file
+                .read(&mut self.read_buf)
+                .await
+                .map_err(DiagDeviceError::DeviceReadFailed)?;
+            if bytes_read == 0 {
+                return Err(DiagDeviceError::DeviceReadFailed(
+                    std::io::ErrorKind::UnexpectedEof.into(),
+                ));
+            }
+            frame_stats += self
+                .assembler
+                .push(&self.read_buf[..bytes_read], &mut frames);
+        }
+        Ok(MessagesContainer::from_frames(frames, frame_stats))
+    }
+
+    async fn write_request(&mut self, req_bytes: &[u8]) -> DiagResult<()> {
+        self.file
+            .write_all(&hdlc_encapsulate(req_bytes, &CRC_CCITT))
+            .await
+            .map_err(DiagDeviceError::DeviceWriteFailed)?;
+        self.file
+            .flush()
+            .await
+            .map_err(DiagDeviceError::DeviceWriteFailed)
+    }
+}
+
+// Stops the tty from echoing, translating newlines or otherwise mangling our
+// binary frames
+fn set_raw_mode(fd: i32) -> DiagResult<()> {
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) < 0 {
+            let msg = format!("tcgetattr failed: {}", std::io::Error::last_os_error());
+            return Err(DiagDeviceError::InitializationFailed(msg));
+        }
+        libc::cfmakeraw(&mut termios);
+        if libc::tcsetattr(fd, libc::TCSANOW, &termios) < 0 {
+            let msg = format!("tcsetattr failed: {}", std::io::Error::last_os_error());
+            return Err(DiagDeviceError::InitializationFailed(msg));
+        }
+    }
+    Ok(())
+}
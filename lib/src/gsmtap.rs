@@ -1,5 +1,6 @@
 //! The spec for GSMTAP is here: https://github.com/osmocom/libosmocore/blob/master/include/osmocom/core/gsmtap.h
 
+use bytes::Bytes;
 use deku::prelude::*;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -24,6 +25,7 @@ pub enum GsmtapType {
     LteNas(LteNasSubtype), /* LTE Non-Access Stratum */
     E1T1,                  /* E1/T1 Lines */
     GsmRlp,                /* GSM RLP frames as per 3GPP TS 24.022 */
+    NrRrc(NrRrcSubtype),   /* 5G NR RRC, see NrRrcSubtype */
 }
 
 // based on https://github.com/fgsect/scat/blob/97442580e628de414c9f7c2a185f4e28d0ee7523/src/scat/parsers/qualcomm/diagltelogparser.py#L1337
@@ -53,6 +55,10 @@ pub enum UmSubtype {
     Pdch = 0x0d,
     Ptcch = 0x0e,
     Cbch51 = 0x0f,
+    /// The SACCH that goes with an SDCCH, i.e. [UmSubtype::Sdcch] with
+    /// GSMTAP's ACCH flag (0x80) set. The diag log doesn't say which
+    /// dedicated channel a SACCH message went with, so we assume an SDCCH.
+    Sacch = 0x86,
 }
 
 #[repr(u8)]
@@ -150,6 +156,43 @@ pub enum LteRrcSubtype {
     ScMcchNb = 22,
 }
 
+// GSMTAP v2 has no type allocated for NR RRC, so these are never written as
+// GSMTAP. The pcap writer wraps them in a Wireshark exported PDU naming the
+// nr-rrc dissector for their channel instead.
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NrRrcSubtype {
+    BcchBch = 0,
+    BcchDlSch = 1,
+    DlCcch = 2,
+    DlDcch = 3,
+    Pcch = 4,
+    UlCcch = 5,
+    UlCcch1 = 6,
+    UlDcch = 7,
+    RrcReconfiguration = 8,
+    RrcReconfigurationComplete = 9,
+}
+
+impl NrRrcSubtype {
+    /// The name Wireshark registers the nr-rrc dissector for this channel
+    /// under
+    pub fn dissector_name(&self) -> &'static str {
+        match self {
+            NrRrcSubtype::BcchBch => "nr-rrc.bcch.bch",
+            NrRrcSubtype::BcchDlSch => "nr-rrc.bcch.dl.sch",
+            NrRrcSubtype::DlCcch => "nr-rrc.dl.ccch",
+            NrRrcSubtype::DlDcch => "nr-rrc.dl.dcch",
+            NrRrcSubtype::Pcch => "nr-rrc.pcch",
+            NrRrcSubtype::UlCcch => "nr-rrc.ul.ccch",
+            NrRrcSubtype::UlCcch1 => "nr-rrc.ul.ccch1",
+            NrRrcSubtype::UlDcch => "nr-rrc.ul.dcch",
+            NrRrcSubtype::RrcReconfiguration => "nr-rrc.rrc_reconf_msg",
+            NrRrcSubtype::RrcReconfigurationComplete => "nr-rrc.rrc_reconf_compl_msg",
+        }
+    }
+}
+
 impl GsmtapType {
     pub fn get_type(&self) -> u8 {
         match self {
@@ -173,6 +216,8 @@ impl GsmtapType {
             GsmtapType::LteNas(_) => 0x12,
             GsmtapType::E1T1 => 0x13,
             GsmtapType::GsmRlp => 0x14,
+            // not a GSMTAP type, see NrRrcSubtype
+            GsmtapType::NrRrc(_) => 0x00,
         }
     }
 
@@ -182,6 +227,7 @@ impl GsmtapType {
             GsmtapType::UmtsRrc(subtype) => *subtype as u8,
             GsmtapType::LteRrc(subtype) => *subtype as u8,
             GsmtapType::LteNas(subtype) => *subtype as u8,
+            GsmtapType::NrRrc(subtype) => *subtype as u8,
             _ => 0,
         }
     }
@@ -192,6 +238,10 @@ impl GsmtapType {
 pub struct GsmtapHeader {
     #[deku(skip)]
     pub gsmtap_type: GsmtapType,
+    /// Which SIM the message is for. GSMTAP has no field for this, so it's
+    /// up to the pcap writer to carry it (e.g. in the destination address)
+    #[deku(skip)]
+    pub radio_id: u8,
 
     #[deku(assert_eq = "2")]
     pub version: u8,
@@ -221,6 +271,7 @@ impl GsmtapHeader {
     pub fn new(gsmtap_type: GsmtapType) -> Self {
         GsmtapHeader {
             gsmtap_type,
+            radio_id: 0,
             version: 2,
             header_len: 4,
             packet_type: gsmtap_type.get_type(),
@@ -239,8 +290,18 @@ impl GsmtapHeader {
     }
 }
 
-#[derive(Debug, PartialEq, Clone, DekuWrite)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct GsmtapMessage {
     pub header: GsmtapHeader,
-    pub payload: Vec<u8>,
+    /// Usually the very buffer the diag log's payload was parsed into, so
+    /// cloning a message doesn't copy it
+    pub payload: Bytes,
+}
+
+impl GsmtapMessage {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DekuError> {
+        let mut data = self.header.to_bytes()?;
+        data.extend_from_slice(&self.payload);
+        Ok(data)
+    }
 }
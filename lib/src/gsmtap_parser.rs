@@ -1,8 +1,12 @@
 use crate::diag::*;
 use crate::gsmtap::*;
 use crate::cellular_info::{CellularInfoExtractor, CellularNetworkInfo};
+use crate::log_codes;
+
+use bytes::Bytes;
 
 use log::error;
+use telcom_parser::lte_nas::SecurityHeaderType;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -11,15 +15,31 @@ pub enum GsmtapParserError {
     InvalidLteRrcOtaExtHeaderVersion(u8),
     #[error("Invalid LteRrcOtaMessage header/PDU number combination: {0}/{1}")]
     InvalidLteRrcOtaHeaderPduNum(u8, u8),
+    #[error("Invalid GsmRrSignallingMessage channel type {0:#04x}")]
+    InvalidGsmRrChannelType(u8),
+    #[error("Invalid WcdmaSignallingMessage channel type {0:#04x}")]
+    InvalidWcdmaChannelType(u8),
+    #[error("GsmRrSignallingMessage of {0} bytes is too long for its L2 length field")]
+    GsmRrMessageTooLong(usize),
+    #[error("Invalid GprsMacSignallingMessage channel type {0:#04x}")]
+    InvalidGprsMacChannelType(u8),
+    #[error("Invalid NrRrcOtaMessage PDU number {0}")]
+    InvalidNrRrcOtaPduNum(u8),
 }
 
 pub fn parse(msg: Message) -> Result<Option<(Timestamp, GsmtapMessage)>, GsmtapParserError> {
     if let Message::Log {
-        timestamp, body, ..
+        timestamp,
+        body,
+        subscription_id,
+        ..
     } = msg
     {
         match log_to_gsmtap(body)? {
-            Some(msg) => Ok(Some((timestamp, msg))),
+            Some(mut msg) => {
+                msg.header.radio_id = radio_id(subscription_id);
+                Ok(Some((timestamp, msg)))
+            }
             None => Ok(None),
         }
     } else {
@@ -27,62 +47,59 @@ pub fn parse(msg: Message) -> Result<Option<(Timestamp, GsmtapMessage)>, GsmtapP
     }
 }
 
-/// Enhanced parsing function that also extracts cellular network information
-pub fn parse_with_cellular_info(
-    msg: Message,
+/// Extracts cellular network information from a diag message without
+/// consuming it, so analyzers can inspect messages as they're decoded.
+pub fn parse_cellular_info(
+    msg: &Message,
     cellular_extractor: &mut CellularInfoExtractor,
-) -> Result<Option<(Timestamp, GsmtapMessage, Option<CellularNetworkInfo>)>, GsmtapParserError> {
-    if let Message::Log {
-        timestamp, body, log_type, ..
+) -> Option<CellularNetworkInfo> {
+    let Message::Log {
+        timestamp,
+        body,
+        log_type,
+        ..
     } = msg
-    {
-        // Extract cellular information from the log message
-        let cellular_info = cellular_extractor.extract_from_log_data(
-            log_type,
-            &match &body {
-                LogBody::LteRrcOtaMessage { packet, .. } => packet.clone().take_payload(),
-                LogBody::LteML1ServingCellInfo { data } => data.clone(),
-                LogBody::LteML1NeighborMeasurements { data } => data.clone(),
-                LogBody::GsmL1CellId { data } => data.clone(),
-                LogBody::GsmRrCellInformation { data } => data.clone(),
-                LogBody::WcdmaCellId { data } => data.clone(),
-                LogBody::WcdmaServingCellInfo { data } => data.clone(),
-                LogBody::Nas4GMessage { msg, .. } => msg.clone(),
-                _ => Vec::new(),
-            },
-            timestamp.to_datetime(),
-        );
+    else {
+        return None;
+    };
+    cellular_extractor.extract_from_log_body(*log_type, body, timestamp.to_datetime())
+}
 
-        match log_to_gsmtap(body)? {
-            Some(gsmtap_msg) => Ok(Some((timestamp, gsmtap_msg, cellular_info))),
-            None => {
-                // Even if we can't create a GSMTAP message, we might have cellular info
-                if cellular_info.is_some() {
-                    // Create a dummy GSMTAP message for cellular info only
-                    Ok(Some((
-                        timestamp,
-                        GsmtapMessage {
-                            header: GsmtapHeader::new(GsmtapType::QcDiag),
-                            payload: Vec::new(),
-                        },
-                        cellular_info,
-                    )))
-                } else {
-                    Ok(None)
-                }
-            }
-        }
-    } else {
-        Ok(None)
-    }
+// logs from single-SIM modems don't say which SIM they're for, so they go
+// with the first one
+fn radio_id(subscription_id: Option<u32>) -> u8 {
+    subscription_id.unwrap_or(0) as u8
 }
 
 fn log_to_gsmtap(value: LogBody) -> Result<Option<GsmtapMessage>, GsmtapParserError> {
+    let Some(header) = gsmtap_header(&value)? else {
+        return Ok(None);
+    };
+    // the header has already been validated, so this only has to move the
+    // message bytes out of the log body
+    let payload = match value {
+        LogBody::LteRrcOtaMessage { packet, .. } => packet.take_payload().into(),
+        LogBody::NrRrcOtaMessage { packet, .. } => packet.take_payload().into(),
+        LogBody::Nas4GMessage { msg, .. }
+        | LogBody::WcdmaSignallingMessage { msg, .. }
+        | LogBody::UmtsNasOtaMessage { msg, .. }
+        | LogBody::GprsMacSignallingMessage { msg, .. } => msg.into(),
+        LogBody::GsmRrSignallingMessage { msg, .. } => match header.gsmtap_type {
+            GsmtapType::Um(subtype) => gsm_um_payload(subtype, msg).into(),
+            _ => msg.into(),
+        },
+        _ => Bytes::new(),
+    };
+    Ok(Some(GsmtapMessage { header, payload }))
+}
+
+fn gsmtap_header(value: &LogBody) -> Result<Option<GsmtapHeader>, GsmtapParserError> {
     match value {
         LogBody::LteRrcOtaMessage {
             ext_header_version,
             packet,
         } => {
+            let ext_header_version = *ext_header_version;
             let gsmtap_type = match ext_header_version {
                 0x02 | 0x03 | 0x04 | 0x06 | 0x07 | 0x08 | 0x0d | 0x16 => match packet.get_pdu_num()
                 {
@@ -189,19 +206,101 @@ fn log_to_gsmtap(value: LogBody) -> Result<Option<GsmtapMessage>, GsmtapParserEr
             header.arfcn = packet.get_earfcn().try_into().unwrap_or(0);
             header.frame_number = packet.get_sfn();
             header.subslot = packet.get_subfn();
-            Ok(Some(GsmtapMessage {
-                header,
-                payload: packet.take_payload(),
-            }))
+            Ok(Some(header))
+        }
+        LogBody::NrRrcOtaMessage { packet, .. } => {
+            let subtype = match packet.get_pdu_num() {
+                1 => NrRrcSubtype::BcchBch,
+                2 => NrRrcSubtype::BcchDlSch,
+                3 => NrRrcSubtype::DlCcch,
+                4 => NrRrcSubtype::DlDcch,
+                5 => NrRrcSubtype::Pcch,
+                6 => NrRrcSubtype::UlCcch,
+                7 => NrRrcSubtype::UlCcch1,
+                8 => NrRrcSubtype::UlDcch,
+                9 => NrRrcSubtype::RrcReconfiguration,
+                10 => NrRrcSubtype::RrcReconfigurationComplete,
+                pdu => return Err(GsmtapParserError::InvalidNrRrcOtaPduNum(pdu)),
+            };
+            let mut header = GsmtapHeader::new(GsmtapType::NrRrc(subtype));
+            header.uplink = matches!(
+                subtype,
+                NrRrcSubtype::UlCcch
+                    | NrRrcSubtype::UlCcch1
+                    | NrRrcSubtype::UlDcch
+                    | NrRrcSubtype::RrcReconfigurationComplete
+            );
+            header.arfcn = packet.get_nr_arfcn().try_into().unwrap_or(0);
+            Ok(Some(header))
         }
         LogBody::Nas4GMessage { msg, direction, .. } => {
-            // currently we only handle "plain" (i.e. non-secure) NAS messages
-            let mut header = GsmtapHeader::new(GsmtapType::LteNas(LteNasSubtype::Plain));
+            let first_octet = msg.first().copied().unwrap_or_default();
+            let subtype = match SecurityHeaderType::from_first_octet(first_octet) {
+                Some(_) => LteNasSubtype::Secure,
+                None => LteNasSubtype::Plain,
+            };
+            let mut header = GsmtapHeader::new(GsmtapType::LteNas(subtype));
             header.uplink = matches!(direction, Nas4GMessageDirection::Uplink);
-            Ok(Some(GsmtapMessage {
-                header,
-                payload: msg,
-            }))
+            Ok(Some(header))
+        }
+        LogBody::WcdmaSignallingMessage { channel_type, .. } => {
+            let channel_type = *channel_type;
+            let subtype = match channel_type as u32 {
+                log_codes::RRCLOG_SIG_UL_CCCH => UmtsRrcSubtype::UlCcch,
+                log_codes::RRCLOG_SIG_UL_DCCH => UmtsRrcSubtype::UlDcch,
+                log_codes::RRCLOG_SIG_DL_CCCH => UmtsRrcSubtype::DlCcch,
+                log_codes::RRCLOG_SIG_DL_DCCH => UmtsRrcSubtype::DlDcch,
+                log_codes::RRCLOG_SIG_DL_BCCH_BCH => UmtsRrcSubtype::BcchBch,
+                log_codes::RRCLOG_SIG_DL_BCCH_FACH => UmtsRrcSubtype::BcchFach,
+                log_codes::RRCLOG_SIG_DL_PCCH => UmtsRrcSubtype::Pcch,
+                log_codes::RRCLOG_SIG_DL_MCCH => UmtsRrcSubtype::Mcch,
+                log_codes::RRCLOG_SIG_DL_MSCH => UmtsRrcSubtype::Msch,
+                log_codes::RRCLOG_SIB_CONTAINER => UmtsRrcSubtype::SystemInformationContainer,
+                _ => return Err(GsmtapParserError::InvalidWcdmaChannelType(channel_type)),
+            };
+            let mut header = GsmtapHeader::new(GsmtapType::UmtsRrc(subtype));
+            header.uplink = matches!(subtype, UmtsRrcSubtype::UlCcch | UmtsRrcSubtype::UlDcch);
+            Ok(Some(header))
+        }
+        LogBody::UmtsNasOtaMessage { is_uplink, .. } => {
+            // like scat, we use the Abis type for 2G/3G NAS, since Wireshark
+            // dissects its payload as a bare 24.008 DTAP message
+            let mut header = GsmtapHeader::new(GsmtapType::Abis);
+            header.uplink = *is_uplink != 0;
+            Ok(Some(header))
+        }
+        LogBody::GsmRrSignallingMessage {
+            channel_type, msg, ..
+        } => {
+            let channel_type = *channel_type;
+            // the high bit of the channel type is set for downlink messages
+            let subtype = match (channel_type & 0x7f) as u32 {
+                log_codes::DCCH | log_codes::SDCCH => UmSubtype::Sdcch,
+                log_codes::SACCH => UmSubtype::Sacch,
+                log_codes::BCCH => UmSubtype::Bcch,
+                log_codes::CCCH => UmSubtype::Ccch,
+                log_codes::L2_RACH | log_codes::L2_RACH_WITH_NO_DELAY => UmSubtype::Rach,
+                log_codes::FACCH_F => UmSubtype::TchF,
+                log_codes::FACCH_H => UmSubtype::TchH,
+                _ => return Err(GsmtapParserError::InvalidGsmRrChannelType(channel_type)),
+            };
+            if subtype != UmSubtype::Rach && msg.len() > MAX_L2_LENGTH {
+                return Err(GsmtapParserError::GsmRrMessageTooLong(msg.len()));
+            }
+            let mut header = GsmtapHeader::new(GsmtapType::Um(subtype));
+            header.uplink = channel_type & 0x80 == 0;
+            Ok(Some(header))
+        }
+        LogBody::GprsMacSignallingMessage { channel_type, .. } => {
+            let channel_type = *channel_type;
+            let uplink = match channel_type as u32 {
+                log_codes::UL_PACCH_CHANNEL | log_codes::PACCH_RRBP_CHANNEL => true,
+                log_codes::DL_PACCH_CHANNEL => false,
+                _ => return Err(GsmtapParserError::InvalidGprsMacChannelType(channel_type)),
+            };
+            let mut header = GsmtapHeader::new(GsmtapType::Um(UmSubtype::Pacch));
+            header.uplink = uplink;
+            Ok(Some(header))
         }
         _ => {
             error!("gsmtap_sink: ignoring unhandled log type: {value:?}");
@@ -209,3 +308,28 @@ fn log_to_gsmtap(value: LogBody) -> Result<Option<GsmtapMessage>, GsmtapParserEr
         }
     }
 }
+
+/// The largest L3 message the 6 bit length indicator of an L2 header can
+/// describe (TS 44.006 5.6)
+const MAX_L2_LENGTH: usize = 63;
+
+/// The diag log only contains the L3 message, but Wireshark expects the layer
+/// 2 framing of the channel it was sent on: an L2 pseudo length octet for
+/// BCCH/CCCH messages, and a LAPDm header for dedicated channels, which on the
+/// SACCH follows a 2 octet L1 header (power level and timing advance, which
+/// the log doesn't give us). Random access bursts have no L3 message and are
+/// passed through as-is.
+fn gsm_um_payload(subtype: UmSubtype, msg: Vec<u8>) -> Vec<u8> {
+    let l2_length = ((msg.len() as u8) << 2) | 0x01;
+    let header: &[u8] = match subtype {
+        UmSubtype::Bcch | UmSubtype::Ccch => &[l2_length],
+        // SAPI 0 command, UI frame
+        UmSubtype::Sdcch | UmSubtype::TchF | UmSubtype::TchH => &[0x01, 0x03, l2_length],
+        UmSubtype::Sacch => &[0x00, 0x00, 0x01, 0x03, l2_length],
+        _ => &[],
+    };
+    let mut payload = Vec::with_capacity(header.len() + msg.len());
+    payload.extend_from_slice(header);
+    payload.extend(msg);
+    payload
+}
@@ -3,8 +3,12 @@
 //! here:
 //! https://github.com/P1sec/QCSuper/blob/master/docs/The%20Diag%20protocol.md#the-diag-protocol-over-usb
 
+use std::borrow::Cow;
+use std::ops::AddAssign;
+
 use bytes::Buf;
 use crc::Crc;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::diag::{
@@ -54,6 +58,16 @@ pub fn hdlc_encapsulate(data: &[u8], crc: &Crc<u16>) -> Vec<u8> {
 }
 
 pub fn hdlc_decapsulate(data: &[u8], crc: &Crc<u16>) -> Result<Vec<u8>, HdlcError> {
+    hdlc_decapsulate_borrowed(data, crc).map(Cow::into_owned)
+}
+
+/// Like [hdlc_decapsulate], but only copies the frame if it has escape
+/// sequences to undo. Most frames don't, which saves an allocation per
+/// message.
+pub fn hdlc_decapsulate_borrowed<'a>(
+    data: &'a [u8],
+    crc: &Crc<u16>,
+) -> Result<Cow<'a, [u8]>, HdlcError> {
     if data.len() < 3 {
         return Err(HdlcError::TooShort);
     }
@@ -62,9 +76,36 @@ pub fn hdlc_decapsulate(data: &[u8], crc: &Crc<u16>) -> Result<Vec<u8>, HdlcErro
         return Err(HdlcError::NoTrailingCharacter(data[data.len() - 1]));
     }
 
+    let escaped = &data[..data.len() - 1];
+    let unescaped = if escaped.contains(&MESSAGE_ESCAPE_CHAR) {
+        Cow::Owned(unescape(escaped)?)
+    } else {
+        Cow::Borrowed(escaped)
+    };
+
+    // split off the u16 checksum, check it against what we calculated
+    let Some(msg_len) = unescaped.len().checked_sub(2) else {
+        return Err(HdlcError::MissingChecksum);
+    };
+    let checksum = (&unescaped[msg_len..]).get_u16_le();
+    let calculated = crc.checksum(&unescaped[..msg_len]);
+    if checksum != calculated {
+        return Err(HdlcError::InvalidChecksum(checksum, calculated));
+    }
+
+    Ok(match unescaped {
+        Cow::Borrowed(unescaped) => Cow::Borrowed(&unescaped[..msg_len]),
+        Cow::Owned(mut unescaped) => {
+            unescaped.truncate(msg_len);
+            Cow::Owned(unescaped)
+        }
+    })
+}
+
+fn unescape(data: &[u8]) -> Result<Vec<u8>, HdlcError> {
     let mut unescaped = Vec::with_capacity(data.len());
     let mut escaping = false;
-    for &b in &data[..data.len() - 1] {
+    for &b in data {
         if escaping {
             match b {
                 ESCAPED_MESSAGE_TERMINATOR => unescaped.push(MESSAGE_TERMINATOR),
@@ -78,19 +119,95 @@ pub fn hdlc_decapsulate(data: &[u8], crc: &Crc<u16>) -> Result<Vec<u8>, HdlcErro
             unescaped.push(b);
         }
     }
+    Ok(unescaped)
+}
 
-    // pop off the u16 checksum, check it against what we calculated
-    let checksum_hi = unescaped.pop().ok_or(HdlcError::MissingChecksum)?;
-    let checksum_lo = unescaped.pop().ok_or(HdlcError::MissingChecksum)?;
-    let checksum = [checksum_lo, checksum_hi].as_slice().get_u16_le();
-    if checksum != crc.checksum(&unescaped) {
-        return Err(HdlcError::InvalidChecksum(
-            checksum,
-            crc.checksum(&unescaped),
-        ));
+// the longest we'll let a frame get before assuming its terminator was lost
+pub const MAX_FRAME_LEN: usize = 64 * 1024;
+
+/// How many frames a [HdlcFrameAssembler] had to piece together from more
+/// than one read, and how many it had to give up on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HdlcFrameStats {
+    pub reassembled: u32,
+    pub dropped: u32,
+}
+
+impl HdlcFrameStats {
+    pub fn is_empty(&self) -> bool {
+        self.reassembled == 0 && self.dropped == 0
     }
+}
 
-    Ok(unescaped)
+impl AddAssign for HdlcFrameStats {
+    fn add_assign(&mut self, other: Self) {
+        self.reassembled += other.reassembled;
+        self.dropped += other.dropped;
+    }
+}
+
+/// Splits a stream of bytes into HDLC frames. Diag doesn't line frames up
+/// with reads, so a frame can start at the end of one read and finish in the
+/// next; the assembler holds on to the start of any unterminated frame until
+/// the rest of it arrives.
+#[derive(Debug, Default)]
+pub struct HdlcFrameAssembler {
+    pending: Vec<u8>,
+    // whether we're throwing away the rest of an overlong frame
+    discarding: bool,
+}
+
+impl HdlcFrameAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends every frame completed by `data` to `frames`, each including
+    /// its trailing terminator
+    pub fn push(&mut self, data: &[u8], frames: &mut Vec<Vec<u8>>) -> HdlcFrameStats {
+        let mut stats = HdlcFrameStats::default();
+        for chunk in data.split_inclusive(|&b| b == MESSAGE_TERMINATOR) {
+            let terminated = chunk.last() == Some(&MESSAGE_TERMINATOR);
+            if self.discarding {
+                self.discarding = !terminated;
+                continue;
+            }
+
+            let continued = !self.pending.is_empty();
+            self.pending.extend_from_slice(chunk);
+            if !terminated {
+                if self.pending.len() > MAX_FRAME_LEN {
+                    self.pending.clear();
+                    self.discarding = true;
+                    stats.dropped += 1;
+                }
+                continue;
+            }
+
+            let frame = std::mem::take(&mut self.pending);
+            // some modems send a terminator before each frame too
+            if frame.len() <= 1 {
+                continue;
+            }
+            if continued {
+                stats.reassembled += 1;
+            }
+            frames.push(frame);
+        }
+        stats
+    }
+
+    /// Gives up on any unterminated frame, e.g. once the end of a file has
+    /// been reached
+    pub fn finish(&mut self) -> HdlcFrameStats {
+        let mut stats = HdlcFrameStats::default();
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            stats.dropped += 1;
+        }
+        self.discarding = false;
+        stats
+    }
 }
 
 #[cfg(test)]
@@ -105,5 +222,50 @@ mod tests {
         let encapsulated = hdlc_encapsulate(&data, &crc);
         assert_eq!(&encapsulated, &expected);
         assert_eq!(hdlc_decapsulate(&encapsulated, &crc), Ok(data));
+        assert!(matches!(
+            hdlc_decapsulate_borrowed(&encapsulated, &crc),
+            Ok(Cow::Borrowed(_))
+        ));
+
+        // escaped bytes have to be copied out
+        let data = vec![0x7e, 0x01, 0x7d, 0x02];
+        let encapsulated = hdlc_encapsulate(&data, &crc);
+        let decapsulated = hdlc_decapsulate_borrowed(&encapsulated, &crc).unwrap();
+        assert!(matches!(decapsulated, Cow::Owned(_)));
+        assert_eq!(decapsulated, data);
+    }
+
+    #[test]
+    fn test_frame_assembler() {
+        let crc = Crc::<u16>::new(&crate::diag::CRC_CCITT_ALG);
+        let frame1 = hdlc_encapsulate(&[1, 2, 3, 4], &crc);
+        let frame2 = hdlc_encapsulate(&[5, 6, 7, 8], &crc);
+        let mut data = vec![MESSAGE_TERMINATOR];
+        data.extend(&frame1);
+        data.extend(&frame2);
+
+        let mut assembler = HdlcFrameAssembler::new();
+        let mut frames = Vec::new();
+        let (first, second) = data.split_at(frame1.len() + 3);
+        let stats = assembler.push(first, &mut frames);
+        assert_eq!(frames, vec![frame1.clone()]);
+        assert!(stats.is_empty());
+        let stats = assembler.push(second, &mut frames);
+        assert_eq!(frames, vec![frame1, frame2.clone()]);
+        assert_eq!(stats.reassembled, 1);
+
+        // a frame that never gets terminated is dropped
+        assembler.push(&frame2[..3], &mut frames);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(assembler.finish().dropped, 1);
+        assert!(assembler.finish().is_empty());
+
+        // as is one that's too long to be believable, up to its terminator
+        let stats = assembler.push(&vec![0; MAX_FRAME_LEN + 1], &mut frames);
+        assert_eq!(stats.dropped, 1);
+        assembler.push(&[0, MESSAGE_TERMINATOR], &mut frames);
+        assembler.push(&frame2, &mut frames);
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[2], frame2);
     }
 }
@@ -1,10 +1,12 @@
 pub mod analysis;
 pub mod cellular_info;
 pub mod diag;
+pub mod diag_events;
 pub mod gsmtap;
 pub mod gsmtap_parser;
 pub mod hdlc;
 pub mod log_codes;
+pub mod lte_ml1;
 pub mod pcap;
 pub mod qmdl;
 pub mod util;
@@ -12,6 +14,8 @@ pub mod util;
 // bin/check.rs may target windows and does not use this mod
 #[cfg(target_family = "unix")]
 pub mod diag_device;
+#[cfg(target_family = "unix")]
+pub mod diag_serial;
 
 // re-export telcom_parser, since we use its types in our API
 pub use telcom_parser;
@@ -0,0 +1,655 @@
+//! LTE ML1 (layer 1) and PHY measurement log packets.
+//!
+//! Like [crate::diag::LteRrcOtaPacket], the layout of each of these depends
+//! on a version number at the start of the log, so each packet type is an
+//! enum with one variant per layout. The main difference between versions is
+//! that newer ones widen the EARFCN from 16 to 32 bits, to fit the EARFCNs of
+//! bands above 64.
+//!
+//! Measurements are logged as unsigned raw values in 1/16 dB steps above a
+//! per-quantity floor. Use the accessor methods (or [rsrp_dbm], [rsrq_db] and
+//! [rssi_dbm]) rather than the raw fields to get real units.
+
+use deku::prelude::*;
+
+/// Converts a raw RSRP value to dBm
+pub fn rsrp_dbm(raw: u16) -> f32 {
+    raw as f32 / 16.0 - 180.0
+}
+
+/// Converts a raw RSRQ value to dB
+pub fn rsrq_db(raw: u16) -> f32 {
+    raw as f32 / 16.0 - 30.0
+}
+
+/// Converts a raw RSSI value to dBm
+pub fn rssi_dbm(raw: u16) -> f32 {
+    raw as f32 / 16.0 - 110.0
+}
+
+// the PCI is the lower 9 bits of a word whose upper bits vary by log
+fn pci(pci_info: u32) -> u16 {
+    (pci_info & 0x1ff) as u16
+}
+
+/// LTE ML1 Serving Cell Measurement and Evaluation (0xb0e0)
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(ctx = "version: u8", id = "version")]
+pub enum ServingCellMeasAndEval {
+    #[deku(id_pat = "0..=4")]
+    V4 {
+        rrc_release: u8,
+        reserved: u16,
+        earfcn: u16,
+        /// PCI in bits 0-8, serving layer priority in bits 9-11
+        pci_info: u16,
+        measurements: ServingCellMeasurements,
+    },
+    #[deku(id_pat = "5..")]
+    V5 {
+        rrc_release: u8,
+        reserved: u16,
+        earfcn: u32,
+        /// PCI in bits 0-8, serving layer priority in bits 9-11
+        pci_info: u16,
+        measurements: ServingCellMeasurements,
+    },
+}
+
+/// The measurement words shared by all versions of
+/// [ServingCellMeasAndEval]
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+pub struct ServingCellMeasurements {
+    /// Measured RSRP in bits 0-11
+    pub rsrp: u32,
+    /// Average RSRP in bits 0-11
+    pub avg_rsrp: u32,
+    /// Measured RSRQ in bits 0-9
+    pub rsrq: u32,
+    /// Measured RSSI in bits 10-20
+    pub rssi: u32,
+}
+
+impl ServingCellMeasAndEval {
+    pub fn get_earfcn(&self) -> u32 {
+        match self {
+            ServingCellMeasAndEval::V4 { earfcn, .. } => *earfcn as u32,
+            ServingCellMeasAndEval::V5 { earfcn, .. } => *earfcn,
+        }
+    }
+
+    pub fn get_pci(&self) -> u16 {
+        match self {
+            ServingCellMeasAndEval::V4 { pci_info, .. } => pci(*pci_info as u32),
+            ServingCellMeasAndEval::V5 { pci_info, .. } => pci(*pci_info as u32),
+        }
+    }
+
+    fn measurements(&self) -> &ServingCellMeasurements {
+        match self {
+            ServingCellMeasAndEval::V4 { measurements, .. } => measurements,
+            ServingCellMeasAndEval::V5 { measurements, .. } => measurements,
+        }
+    }
+
+    pub fn rsrp_dbm(&self) -> f32 {
+        rsrp_dbm((self.measurements().rsrp & 0xfff) as u16)
+    }
+
+    pub fn avg_rsrp_dbm(&self) -> f32 {
+        rsrp_dbm((self.measurements().avg_rsrp & 0xfff) as u16)
+    }
+
+    pub fn rsrq_db(&self) -> f32 {
+        rsrq_db((self.measurements().rsrq & 0x3ff) as u16)
+    }
+
+    pub fn rssi_dbm(&self) -> f32 {
+        rssi_dbm(((self.measurements().rssi >> 10) & 0x7ff) as u16)
+    }
+}
+
+/// LTE ML1 Neighbor Measurements (0xb0e1)
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(ctx = "version: u8", id = "version")]
+pub enum NeighborMeasurements {
+    #[deku(id_pat = "0..=4")]
+    V4 {
+        rrc_release: u8,
+        reserved: u16,
+        earfcn: u16,
+        /// Number of cells in bits 0-5
+        num_cells_info: u16,
+        reserved2: u16,
+        #[deku(count = "*num_cells_info & 0x3f")]
+        cells: Vec<NeighborCellMeasurement>,
+    },
+    #[deku(id_pat = "5..")]
+    V5 {
+        rrc_release: u8,
+        reserved: u16,
+        earfcn: u32,
+        /// Number of cells in bits 0-5
+        num_cells_info: u16,
+        reserved2: u16,
+        #[deku(count = "*num_cells_info & 0x3f")]
+        cells: Vec<NeighborCellMeasurement>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+pub struct NeighborCellMeasurement {
+    /// PCI in bits 0-8
+    pub pci_info: u32,
+    /// Measured RSRP in bits 0-11, average RSRP in bits 12-23
+    pub rsrp: u32,
+    /// Measured RSRQ in bits 0-9
+    pub rsrq: u32,
+    /// Measured RSSI in bits 10-20
+    pub rssi: u32,
+}
+
+impl NeighborCellMeasurement {
+    pub fn get_pci(&self) -> u16 {
+        pci(self.pci_info)
+    }
+
+    pub fn rsrp_dbm(&self) -> f32 {
+        rsrp_dbm((self.rsrp & 0xfff) as u16)
+    }
+
+    pub fn avg_rsrp_dbm(&self) -> f32 {
+        rsrp_dbm(((self.rsrp >> 12) & 0xfff) as u16)
+    }
+
+    pub fn rsrq_db(&self) -> f32 {
+        rsrq_db((self.rsrq & 0x3ff) as u16)
+    }
+
+    pub fn rssi_dbm(&self) -> f32 {
+        rssi_dbm(((self.rssi >> 10) & 0x7ff) as u16)
+    }
+}
+
+impl NeighborMeasurements {
+    pub fn get_earfcn(&self) -> u32 {
+        match self {
+            NeighborMeasurements::V4 { earfcn, .. } => *earfcn as u32,
+            NeighborMeasurements::V5 { earfcn, .. } => *earfcn,
+        }
+    }
+
+    pub fn get_cells(&self) -> &[NeighborCellMeasurement] {
+        match self {
+            NeighborMeasurements::V4 { cells, .. } => cells,
+            NeighborMeasurements::V5 { cells, .. } => cells,
+        }
+    }
+}
+
+/// LTE ML1 Serving Cell Info (0xb0e4)
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(ctx = "version: u8", id = "version")]
+pub enum ServingCellInfo {
+    #[deku(id_pat = "0..=1")]
+    V1 {
+        /// Index into 1.4, 3, 5, 10, 15 and 20 MHz
+        dl_bandwidth: u8,
+        sfn: u16,
+        earfcn: u16,
+        /// PCI in bits 0-8
+        pci_info: u16,
+        reserved: u16,
+    },
+    #[deku(id_pat = "2..")]
+    V2 {
+        /// Index into 1.4, 3, 5, 10, 15 and 20 MHz
+        dl_bandwidth: u8,
+        sfn: u16,
+        earfcn: u32,
+        /// PCI in bits 0-8
+        pci_info: u16,
+        reserved: u16,
+    },
+}
+
+impl ServingCellInfo {
+    pub fn get_earfcn(&self) -> u32 {
+        match self {
+            ServingCellInfo::V1 { earfcn, .. } => *earfcn as u32,
+            ServingCellInfo::V2 { earfcn, .. } => *earfcn,
+        }
+    }
+
+    pub fn get_pci(&self) -> u16 {
+        match self {
+            ServingCellInfo::V1 { pci_info, .. } => pci(*pci_info as u32),
+            ServingCellInfo::V2 { pci_info, .. } => pci(*pci_info as u32),
+        }
+    }
+
+    /// The downlink bandwidth in MHz, or None for reserved values
+    pub fn dl_bandwidth_mhz(&self) -> Option<f32> {
+        let dl_bandwidth = match self {
+            ServingCellInfo::V1 { dl_bandwidth, .. } => *dl_bandwidth,
+            ServingCellInfo::V2 { dl_bandwidth, .. } => *dl_bandwidth,
+        };
+        [1.4, 3.0, 5.0, 10.0, 15.0, 20.0]
+            .get(dl_bandwidth as usize)
+            .copied()
+    }
+}
+
+/// A cell's measurements as logged by the intra/inter-frequency and PHY
+/// neighbor measurement logs. Unlike the ML1 serving cell and neighbor
+/// measurement logs, each quantity has its own 16 bit field.
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+pub struct MeasuredCell {
+    pub pci: u16,
+    pub rsrp: u16,
+    pub rsrq: u16,
+    pub rssi: u16,
+}
+
+impl MeasuredCell {
+    pub fn rsrp_dbm(&self) -> f32 {
+        rsrp_dbm(self.rsrp)
+    }
+
+    pub fn rsrq_db(&self) -> f32 {
+        rsrq_db(self.rsrq)
+    }
+
+    pub fn rssi_dbm(&self) -> f32 {
+        rssi_dbm(self.rssi)
+    }
+}
+
+/// LTE ML1 Intra-Frequency Measurements (0xb0e5)
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(ctx = "version: u8", id = "version")]
+pub enum IntraFreqMeas {
+    #[deku(id_pat = "0..=4")]
+    V4 {
+        rrc_release: u8,
+        reserved: u16,
+        earfcn: u16,
+        serving_pci: u16,
+        serving_rsrp: u16,
+        serving_rsrq: u16,
+        num_neighbor_cells: u8,
+        num_detected_cells: u8,
+        reserved2: u16,
+        /// The neighbor cells, followed by the detected cells
+        #[deku(count = "*num_neighbor_cells as usize + *num_detected_cells as usize")]
+        cells: Vec<MeasuredCell>,
+    },
+    #[deku(id_pat = "5..")]
+    V5 {
+        rrc_release: u8,
+        reserved: u16,
+        earfcn: u32,
+        serving_pci: u16,
+        serving_rsrp: u16,
+        serving_rsrq: u16,
+        num_neighbor_cells: u8,
+        num_detected_cells: u8,
+        reserved2: u16,
+        /// The neighbor cells, followed by the detected cells
+        #[deku(count = "*num_neighbor_cells as usize + *num_detected_cells as usize")]
+        cells: Vec<MeasuredCell>,
+    },
+}
+
+impl IntraFreqMeas {
+    pub fn get_earfcn(&self) -> u32 {
+        match self {
+            IntraFreqMeas::V4 { earfcn, .. } => *earfcn as u32,
+            IntraFreqMeas::V5 { earfcn, .. } => *earfcn,
+        }
+    }
+
+    pub fn get_serving_pci(&self) -> u16 {
+        match self {
+            IntraFreqMeas::V4 { serving_pci, .. } => pci(*serving_pci as u32),
+            IntraFreqMeas::V5 { serving_pci, .. } => pci(*serving_pci as u32),
+        }
+    }
+
+    pub fn serving_rsrp_dbm(&self) -> f32 {
+        match self {
+            IntraFreqMeas::V4 { serving_rsrp, .. } => rsrp_dbm(*serving_rsrp),
+            IntraFreqMeas::V5 { serving_rsrp, .. } => rsrp_dbm(*serving_rsrp),
+        }
+    }
+
+    pub fn serving_rsrq_db(&self) -> f32 {
+        match self {
+            IntraFreqMeas::V4 { serving_rsrq, .. } => rsrq_db(*serving_rsrq),
+            IntraFreqMeas::V5 { serving_rsrq, .. } => rsrq_db(*serving_rsrq),
+        }
+    }
+
+    pub fn get_cells(&self) -> &[MeasuredCell] {
+        match self {
+            IntraFreqMeas::V4 { cells, .. } => cells,
+            IntraFreqMeas::V5 { cells, .. } => cells,
+        }
+    }
+}
+
+/// LTE ML1 Inter-Frequency Measurements (0xb0e6)
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(ctx = "version: u8", id = "version")]
+pub enum InterFreqMeas {
+    #[deku(id_pat = "0..=4")]
+    V4 {
+        rrc_release: u8,
+        reserved: u16,
+        num_layers: u8,
+        reserved2: u8,
+        #[deku(count = "num_layers")]
+        layers: Vec<InterFreqLayerV4>,
+    },
+    #[deku(id_pat = "5..")]
+    V5 {
+        rrc_release: u8,
+        reserved: u16,
+        num_layers: u8,
+        reserved2: u8,
+        #[deku(count = "num_layers")]
+        layers: Vec<InterFreqLayerV5>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+pub struct InterFreqLayerV4 {
+    pub earfcn: u16,
+    pub num_cells: u8,
+    pub reserved: u8,
+    #[deku(count = "num_cells")]
+    pub cells: Vec<MeasuredCell>,
+}
+
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+pub struct InterFreqLayerV5 {
+    pub earfcn: u32,
+    pub num_cells: u8,
+    pub reserved: u8,
+    #[deku(count = "num_cells")]
+    pub cells: Vec<MeasuredCell>,
+}
+
+impl InterFreqMeas {
+    /// Returns each measured cell along with the EARFCN it was measured on
+    pub fn get_cells(&self) -> Vec<(u32, &MeasuredCell)> {
+        match self {
+            InterFreqMeas::V4 { layers, .. } => layers
+                .iter()
+                .flat_map(|layer| layer.cells.iter().map(|cell| (layer.earfcn as u32, cell)))
+                .collect(),
+            InterFreqMeas::V5 { layers, .. } => layers
+                .iter()
+                .flat_map(|layer| layer.cells.iter().map(|cell| (layer.earfcn, cell)))
+                .collect(),
+        }
+    }
+}
+
+/// LTE PHY Serving Cell Measurement (0xb0f0)
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(ctx = "version: u8", id = "version")]
+pub enum PhyServCellMeasurement {
+    #[deku(id_pat = "0..=1")]
+    V1 {
+        reserved: u16,
+        earfcn: u16,
+        cell: MeasuredCell,
+    },
+    #[deku(id_pat = "2..")]
+    V2 {
+        reserved: u16,
+        earfcn: u32,
+        cell: MeasuredCell,
+    },
+}
+
+impl PhyServCellMeasurement {
+    pub fn get_earfcn(&self) -> u32 {
+        match self {
+            PhyServCellMeasurement::V1 { earfcn, .. } => *earfcn as u32,
+            PhyServCellMeasurement::V2 { earfcn, .. } => *earfcn,
+        }
+    }
+
+    pub fn get_cell(&self) -> &MeasuredCell {
+        match self {
+            PhyServCellMeasurement::V1 { cell, .. } => cell,
+            PhyServCellMeasurement::V2 { cell, .. } => cell,
+        }
+    }
+}
+
+/// LTE PHY Neighbor Cell Measurement (0xb0f1)
+#[derive(Debug, Clone, PartialEq, DekuRead, DekuWrite)]
+#[deku(ctx = "version: u8", id = "version")]
+pub enum PhyNeighCellMeasurement {
+    #[deku(id_pat = "0..=1")]
+    V1 {
+        reserved: u16,
+        earfcn: u16,
+        num_cells: u8,
+        reserved2: u8,
+        #[deku(count = "num_cells")]
+        cells: Vec<MeasuredCell>,
+    },
+    #[deku(id_pat = "2..")]
+    V2 {
+        reserved: u16,
+        earfcn: u32,
+        num_cells: u8,
+        reserved2: u8,
+        #[deku(count = "num_cells")]
+        cells: Vec<MeasuredCell>,
+    },
+}
+
+impl PhyNeighCellMeasurement {
+    pub fn get_earfcn(&self) -> u32 {
+        match self {
+            PhyNeighCellMeasurement::V1 { earfcn, .. } => *earfcn as u32,
+            PhyNeighCellMeasurement::V2 { earfcn, .. } => *earfcn,
+        }
+    }
+
+    pub fn get_cells(&self) -> &[MeasuredCell] {
+        match self {
+            PhyNeighCellMeasurement::V1 { cells, .. } => cells,
+            PhyNeighCellMeasurement::V2 { cells, .. } => cells,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diag::{LogBody, Message};
+
+    // wraps a log body in a diag log header
+    fn log_message(log_type: u16, body: &[u8]) -> Vec<u8> {
+        let len = (body.len() + 12) as u16;
+        let mut data = vec![16, 0];
+        data.extend(len.to_le_bytes());
+        data.extend(len.to_le_bytes());
+        data.extend(log_type.to_le_bytes());
+        data.extend([0; 8]);
+        data.extend(body);
+        data
+    }
+
+    fn parse(data: &[u8]) -> LogBody {
+        let ((rest, _), msg) = Message::from_bytes((data, 0)).unwrap();
+        assert!(rest.is_empty());
+        let Message::Log { body, .. } = msg else {
+            panic!("expected log message");
+        };
+        body
+    }
+
+    #[test]
+    fn test_units() {
+        assert_eq!(rsrp_dbm(0), -180.0);
+        assert_eq!(rsrp_dbm(1360), -95.0);
+        assert_eq!(rsrq_db(248), -14.5);
+        assert_eq!(rssi_dbm(720), -65.0);
+    }
+
+    #[test]
+    fn test_serving_cell_meas_and_eval() {
+        let body = [
+            0x05, 0x0f, 0x00, 0x00, // version, RRC release, reserved
+            0x3c, 0x05, 0x01, 0x00, // EARFCN 66876
+            0x04, 0x05, // PCI 260, priority 2
+            0x50, 0x05, 0x00, 0x00, // RSRP -95 dBm
+            0x60, 0x05, 0x00, 0x00, // average RSRP -94 dBm
+            0xf8, 0x00, 0x00, 0x00, // RSRQ -14.5 dB
+            0x00, 0x40, 0x0b, 0x00, // RSSI -65 dBm
+        ];
+        let LogBody::LteML1ServingCellMeasAndEval { version, packet } =
+            parse(&log_message(0xb0e0, &body))
+        else {
+            panic!("expected serving cell meas and eval");
+        };
+        assert_eq!(version, 5);
+        assert_eq!(packet.get_earfcn(), 66876);
+        assert_eq!(packet.get_pci(), 260);
+        assert_eq!(packet.rsrp_dbm(), -95.0);
+        assert_eq!(packet.avg_rsrp_dbm(), -94.0);
+        assert_eq!(packet.rsrq_db(), -14.5);
+        assert_eq!(packet.rssi_dbm(), -65.0);
+    }
+
+    #[test]
+    fn test_neighbor_measurements() {
+        let body = [
+            0x04, 0x0e, 0x00, 0x00, // version, RRC release, reserved
+            0x14, 0x05, // EARFCN 1300
+            0x02, 0x00, 0x00, 0x00, // 2 cells, reserved
+            0x0a, 0x00, 0x00, 0x00, // PCI 10
+            0x50, 0x05, 0x55, 0x00, // RSRP -95 dBm, average -95 dBm
+            0xf8, 0x00, 0x00, 0x00, // RSRQ -14.5 dB
+            0x00, 0x40, 0x0b, 0x00, // RSSI -65 dBm
+            0x0b, 0x00, 0x00, 0x00, // PCI 11
+            0x00, 0x05, 0x00, 0x00, // RSRP -100 dBm
+            0x00, 0x00, 0x00, 0x00, // RSRQ -30 dB
+            0x00, 0x00, 0x00, 0x00, // RSSI -110 dBm
+        ];
+        let LogBody::LteML1NeighborMeasurements { packet, .. } = parse(&log_message(0xb0e1, &body))
+        else {
+            panic!("expected neighbor measurements");
+        };
+        assert_eq!(packet.get_earfcn(), 1300);
+        let cells = packet.get_cells();
+        assert_eq!(cells.len(), 2);
+        assert_eq!(cells[0].get_pci(), 10);
+        assert_eq!(cells[0].rsrp_dbm(), -95.0);
+        assert_eq!(cells[0].avg_rsrp_dbm(), -95.0);
+        assert_eq!(cells[0].rsrq_db(), -14.5);
+        assert_eq!(cells[0].rssi_dbm(), -65.0);
+        assert_eq!(cells[1].get_pci(), 11);
+        assert_eq!(cells[1].rsrp_dbm(), -100.0);
+    }
+
+    #[test]
+    fn test_serving_cell_info() {
+        let body = [
+            0x01, 0x05, 0x10, 0x00, // version, 20 MHz, SFN 16
+            0x14, 0x05, // EARFCN 1300
+            0x04, 0x01, 0x00, 0x00, // PCI 260, reserved
+        ];
+        let LogBody::LteML1ServingCellInfo { packet, .. } = parse(&log_message(0xb0e4, &body))
+        else {
+            panic!("expected serving cell info");
+        };
+        assert_eq!(packet.get_earfcn(), 1300);
+        assert_eq!(packet.get_pci(), 260);
+        assert_eq!(packet.dl_bandwidth_mhz(), Some(20.0));
+    }
+
+    #[test]
+    fn test_intra_and_inter_freq_meas() {
+        let body = [
+            0x05, 0x0f, 0x00, 0x00, // version, RRC release, reserved
+            0x3c, 0x05, 0x01, 0x00, // EARFCN 66876
+            0x04, 0x01, // serving PCI 260
+            0x50, 0x05, 0xf8, 0x00, // serving RSRP -95 dBm, RSRQ -14.5 dB
+            0x01, 0x00, 0x00, 0x00, // 1 neighbor cell, 0 detected cells
+            0x0a, 0x00, 0x00, 0x05, 0xf8, 0x00, 0xd0, 0x02, // PCI 10
+        ];
+        let LogBody::LteML1IntraFreqMeas { packet, .. } = parse(&log_message(0xb0e5, &body)) else {
+            panic!("expected intra-frequency measurements");
+        };
+        assert_eq!(packet.get_earfcn(), 66876);
+        assert_eq!(packet.get_serving_pci(), 260);
+        assert_eq!(packet.serving_rsrp_dbm(), -95.0);
+        assert_eq!(packet.serving_rsrq_db(), -14.5);
+        assert_eq!(packet.get_cells()[0].rsrp_dbm(), -100.0);
+        assert_eq!(packet.get_cells()[0].rssi_dbm(), -65.0);
+
+        let body = [
+            0x04, 0x0e, 0x00, 0x00, // version, RRC release, reserved
+            0x02, 0x00, // 2 layers, reserved
+            0x14, 0x05, 0x01, 0x00, // EARFCN 1300, 1 cell
+            0x0a, 0x00, 0x00, 0x05, 0xf8, 0x00, 0xd0, 0x02, // PCI 10
+            0x52, 0x0c, 0x00, 0x00, // EARFCN 3154, no cells
+        ];
+        let LogBody::LteML1InterFreqMeas { packet, .. } = parse(&log_message(0xb0e6, &body)) else {
+            panic!("expected inter-frequency measurements");
+        };
+        let cells = packet.get_cells();
+        assert_eq!(cells.len(), 1);
+        assert_eq!(cells[0].0, 1300);
+        assert_eq!(cells[0].1.pci, 10);
+    }
+
+    #[test]
+    fn test_phy_measurements() {
+        let body = [
+            0x02, 0x00, 0x00, // version, reserved
+            0x3c, 0x05, 0x01, 0x00, // EARFCN 66876
+            0x04, 0x01, 0x50, 0x05, 0xf8, 0x00, 0xd0, 0x02, // PCI 260
+        ];
+        let LogBody::LtePhyServCellMeasurement { packet, .. } = parse(&log_message(0xb0f0, &body))
+        else {
+            panic!("expected PHY serving cell measurement");
+        };
+        assert_eq!(packet.get_earfcn(), 66876);
+        assert_eq!(packet.get_cell().pci, 260);
+        assert_eq!(packet.get_cell().rsrp_dbm(), -95.0);
+
+        let body = [
+            0x01, 0x00, 0x00, // version, reserved
+            0x14, 0x05, 0x01, 0x00, // EARFCN 1300, 1 cell
+            0x0a, 0x00, 0x00, 0x05, 0xf8, 0x00, 0xd0, 0x02, // PCI 10
+        ];
+        let LogBody::LtePhyNeighCellMeasurement { packet, .. } = parse(&log_message(0xb0f1, &body))
+        else {
+            panic!("expected PHY neighbor cell measurement");
+        };
+        assert_eq!(packet.get_earfcn(), 1300);
+        assert_eq!(packet.get_cells()[0].rsrq_db(), -14.5);
+    }
+
+    #[test]
+    fn test_unknown_version() {
+        // the PHY logs have no version 0 layout with a 32 bit EARFCN, but
+        // any version not covered by a newer layout uses the oldest one
+        let body = [0x00, 0x00, 0x00, 0x14, 0x05, 0x00, 0x00];
+        let LogBody::LtePhyNeighCellMeasurement { packet, .. } = parse(&log_message(0xb0f1, &body))
+        else {
+            panic!("expected PHY neighbor cell measurement");
+        };
+        assert_eq!(packet.get_earfcn(), 1300);
+        assert!(packet.get_cells().is_empty());
+    }
+}
@@ -1,13 +1,15 @@
 //! Parse QMDL files and create a pcap file.
 //! Creates a plausible IP header and [GSMtap](https://osmocom.org/projects/baseband/wiki/GSMTAP) header and then puts the rest of the data under that for wireshark to parse.
-use crate::diag::Timestamp;
-use crate::gsmtap::GsmtapMessage;
+use crate::diag::{ModemInfo, Timestamp};
+use crate::gsmtap::{GsmtapMessage, GsmtapType};
 
 use chrono::prelude::*;
 use deku::prelude::*;
 use pcap_file_tokio::pcapng::PcapNgWriter;
-use pcap_file_tokio::pcapng::blocks::enhanced_packet::EnhancedPacketBlock;
-use pcap_file_tokio::pcapng::blocks::interface_description::InterfaceDescriptionBlock;
+use pcap_file_tokio::pcapng::blocks::enhanced_packet::{EnhancedPacketBlock, EnhancedPacketOption};
+use pcap_file_tokio::pcapng::blocks::interface_description::{
+    InterfaceDescriptionBlock, InterfaceDescriptionOption,
+};
 use pcap_file_tokio::pcapng::blocks::section_header::{SectionHeaderBlock, SectionHeaderOption};
 use pcap_file_tokio::{Endianness, PcapError};
 use std::borrow::Cow;
@@ -34,6 +36,17 @@ where
     ip_id: u16,
 }
 
+// GSMTAP packets are written to the first interface, user-plane IP packets
+// to the second, and messages GSMTAP has no type for (NR RRC) to the third,
+// as Wireshark exported PDUs
+const GSMTAP_INTERFACE_ID: u32 = 0;
+const USER_PLANE_INTERFACE_ID: u32 = 1;
+const EXPORTED_PDU_INTERFACE_ID: u32 = 2;
+
+// exported PDU tags, from Wireshark's epan/exported_pdu.h
+const EXP_PDU_TAG_END_OF_OPT: u16 = 0;
+const EXP_PDU_TAG_DISSECTOR_NAME: u16 = 12;
+
 const IP_HEADER_LEN: u16 = 20;
 #[derive(DekuWrite)]
 #[deku(endian = "big")]
@@ -66,35 +79,65 @@ impl<T> GsmtapPcapWriter<T>
 where
     T: AsyncWrite + Unpin + Send,
 {
-    pub async fn new(writer: T) -> Result<Self, GsmtapPcapError> {
+    /// Starts a pcapng section describing this device and, where it's known,
+    /// the modem whose logs the capture came from
+    pub async fn new(writer: T, modem_info: &ModemInfo) -> Result<Self, GsmtapPcapError> {
         let metadata = crate::util::RuntimeMetadata::new();
         let package = format!(
             "{} {}",
             env!("CARGO_PKG_NAME").to_owned(),
             metadata.rayhunter_version
         );
+        let mut options = vec![
+            SectionHeaderOption::Hardware(Cow::from(metadata.arch)),
+            SectionHeaderOption::OS(Cow::from(metadata.system_os)),
+            SectionHeaderOption::UserApplication(Cow::from(package)),
+        ];
+        // pcapng has no option for the baseband, so it goes in comments
+        if let Some(firmware) = &modem_info.firmware {
+            options.push(SectionHeaderOption::Comment(Cow::from(format!(
+                "modem firmware: {firmware}"
+            ))));
+        }
+        if let Some(model) = &modem_info.model {
+            options.push(SectionHeaderOption::Comment(Cow::from(format!(
+                "modem model: {model}"
+            ))));
+        }
         let section = SectionHeaderBlock {
             endianness: Endianness::Big,
             major_version: 1,
             minor_version: 0,
             section_length: -1,
-            options: vec![
-                SectionHeaderOption::Hardware(Cow::from(metadata.arch)),
-                SectionHeaderOption::OS(Cow::from(metadata.system_os)),
-                SectionHeaderOption::UserApplication(Cow::from(package)),
-            ],
+            options,
         };
         let writer = PcapNgWriter::with_section_header(writer, section).await?;
         Ok(GsmtapPcapWriter { writer, ip_id: 0 })
     }
 
     pub async fn write_iface_header(&mut self) -> Result<(), GsmtapPcapError> {
-        let interface = InterfaceDescriptionBlock {
+        let gsmtap_interface = InterfaceDescriptionBlock {
             linktype: pcap_file_tokio::DataLink::IPV4,
             snaplen: 0xffff,
-            options: vec![],
+            options: vec![InterfaceDescriptionOption::IfName(Cow::from("gsmtap"))],
+        };
+        self.writer.write_pcapng_block(gsmtap_interface).await?;
+        let user_plane_interface = InterfaceDescriptionBlock {
+            linktype: pcap_file_tokio::DataLink::RAW,
+            snaplen: 0xffff,
+            options: vec![InterfaceDescriptionOption::IfName(Cow::from("user-plane"))],
+        };
+        self.writer.write_pcapng_block(user_plane_interface).await?;
+        let exported_pdu_interface = InterfaceDescriptionBlock {
+            linktype: pcap_file_tokio::DataLink::WIRESHARK_UPPER_PDU,
+            snaplen: 0xffff,
+            options: vec![InterfaceDescriptionOption::IfName(Cow::from(
+                "exported-pdu",
+            ))],
         };
-        self.writer.write_pcapng_block(interface).await?;
+        self.writer
+            .write_pcapng_block(exported_pdu_interface)
+            .await?;
         Ok(())
     }
 
@@ -103,16 +146,20 @@ where
         msg: GsmtapMessage,
         timestamp: Timestamp,
     ) -> Result<(), GsmtapPcapError> {
-        let duration = timestamp
-            .to_datetime()
-            .signed_duration_since(DateTime::UNIX_EPOCH)
-            .to_std()?;
-
-        // despite the timestamp above being correct, we have reduce it by
-        // orders of magnitude due to a bug in pcap_file:
-        // https://github.com/courvoif/pcap-file/pull/32
-        let duration = std::time::Duration::from_nanos(duration.as_micros() as u64);
-
+        if let GsmtapType::NrRrc(subtype) = msg.header.gsmtap_type {
+            // there's no address to tell SIMs apart by, so say which one it
+            // was for in a comment
+            let options = match msg.header.radio_id {
+                0 => vec![],
+                radio_id => vec![EnhancedPacketOption::Comment(Cow::from(format!(
+                    "radio {radio_id}"
+                )))],
+            };
+            let data = exported_pdu(subtype.dissector_name(), &msg.payload);
+            return self
+                .write_packet(EXPORTED_PDU_INTERFACE_ID, data, options, timestamp)
+                .await;
+        }
         let msg_bytes = msg.to_bytes()?;
         let ip_header = IpHeader {
             version_and_ihl: 0x45,
@@ -125,7 +172,9 @@ where
             protocol: 0x11, // UDP
             checksum: 0xffff,
             src_addr: 0x7f000001,
-            dst_addr: 0x7f000001, // TODO increment by radio_id
+            // tell SIMs apart by sending each one's messages to a different
+            // loopback address
+            dst_addr: 0x7f000001 + msg.header.radio_id as u32,
         };
         let udp_header = UdpHeader {
             src_port: 13337,
@@ -137,15 +186,82 @@ where
         data.extend(&ip_header.to_bytes()?);
         data.extend(&udp_header.to_bytes()?);
         data.extend(&msg_bytes);
+        self.write_packet(GSMTAP_INTERFACE_ID, data, vec![], timestamp)
+            .await?;
+        self.ip_id = self.ip_id.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Writes a user-plane IP packet, as logged by the modem, to the raw IP
+    /// interface.
+    pub async fn write_ip_packet(
+        &mut self,
+        packet: Vec<u8>,
+        timestamp: Timestamp,
+    ) -> Result<(), GsmtapPcapError> {
+        self.write_packet(USER_PLANE_INTERFACE_ID, packet, vec![], timestamp)
+            .await
+    }
+
+    async fn write_packet(
+        &mut self,
+        interface_id: u32,
+        data: Vec<u8>,
+        options: Vec<EnhancedPacketOption<'_>>,
+        timestamp: Timestamp,
+    ) -> Result<(), GsmtapPcapError> {
+        let duration = timestamp
+            .to_datetime()
+            .signed_duration_since(DateTime::UNIX_EPOCH)
+            .to_std()?;
+
+        // despite the timestamp above being correct, we have reduce it by
+        // orders of magnitude due to a bug in pcap_file:
+        // https://github.com/courvoif/pcap-file/pull/32
+        let duration = std::time::Duration::from_nanos(duration.as_micros() as u64);
+
         let packet = EnhancedPacketBlock {
-            interface_id: 0,
+            interface_id,
             timestamp: duration,
             original_len: data.len() as u32,
             data: Cow::Owned(data),
-            options: vec![],
+            options,
         };
         self.writer.write_pcapng_block(packet).await?;
-        self.ip_id = self.ip_id.wrapping_add(1);
         Ok(())
     }
 }
+
+/// Wraps a message in the exported PDU tags that tell Wireshark which
+/// dissector to hand it to
+fn exported_pdu(dissector_name: &str, payload: &[u8]) -> Vec<u8> {
+    // tag values are NUL padded to a multiple of 4 bytes
+    let name_len = dissector_name.len().next_multiple_of(4);
+    let mut data = Vec::with_capacity(name_len + payload.len() + 8);
+    data.extend(EXP_PDU_TAG_DISSECTOR_NAME.to_be_bytes());
+    data.extend((name_len as u16).to_be_bytes());
+    data.extend(dissector_name.as_bytes());
+    data.resize(name_len + 4, 0);
+    data.extend(EXP_PDU_TAG_END_OF_OPT.to_be_bytes());
+    data.extend(0u16.to_be_bytes());
+    data.extend(payload);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exported_pdu() {
+        assert_eq!(
+            exported_pdu("nr-rrc.pcch", &[0x40, 0x01]),
+            [
+                &[0x00, 0x0c, 0x00, 0x0c][..],
+                b"nr-rrc.pcch\0",
+                &[0x00, 0x00, 0x00, 0x00, 0x40, 0x01],
+            ]
+            .concat()
+        );
+    }
+}
@@ -1,10 +1,15 @@
 //! Qualcomm Mobile Diagnostic Log (QMDL) files have a very simple format: just
 //! a series of of concatenated HDLC encapsulated diag::Message structs.
 //! QmdlReader and QmdlWriter can read and write MessagesContainers to and from
-//! QMDL files.
+//! QMDL files, and QmdlReplay plays a QMDL file back as if it were being read
+//! from a diag device.
 
-use crate::diag::{DataType, HdlcEncapsulatedMessage, MESSAGE_TERMINATOR, MessagesContainer};
+use std::time::Duration;
 
+use crate::diag::{MESSAGE_TERMINATOR, Message, MessagesContainer};
+use crate::hdlc::HdlcFrameAssembler;
+
+use chrono::{DateTime, FixedOffset};
 use futures::TryStream;
 use log::error;
 use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
@@ -48,6 +53,7 @@ where
     reader: BufReader<T>,
     bytes_read: usize,
     max_bytes: Option<usize>,
+    assembler: HdlcFrameAssembler,
 }
 
 impl<T> QmdlReader<T>
@@ -59,6 +65,7 @@ where
             reader: BufReader::new(reader),
             bytes_read: 0,
             max_bytes,
+            assembler: HdlcFrameAssembler::new(),
         }
     }
 
@@ -74,38 +81,132 @@ where
         })
     }
 
+    /// Returns the next HDLC frame in the file. A frame that's cut off by
+    /// the end of the file (or `max_bytes`) is dropped, which is reported in
+    /// the last container's [MessagesContainer::frame_stats].
     pub async fn get_next_messages_container(
         &mut self,
     ) -> Result<Option<MessagesContainer>, std::io::Error> {
-        if let Some(max_bytes) = self.max_bytes {
-            if self.bytes_read >= max_bytes {
-                if self.bytes_read > max_bytes {
-                    error!(
-                        "warning: {} bytes read, but max_bytes was {}",
-                        self.bytes_read, max_bytes
-                    );
+        loop {
+            if let Some(max_bytes) = self.max_bytes {
+                if self.bytes_read >= max_bytes {
+                    if self.bytes_read > max_bytes {
+                        error!(
+                            "warning: {} bytes read, but max_bytes was {}",
+                            self.bytes_read, max_bytes
+                        );
+                    }
+                    return Ok(self.finish());
                 }
-                return Ok(None);
+            }
+
+            let mut buf = Vec::new();
+            let bytes_read = self.reader.read_until(MESSAGE_TERMINATOR, &mut buf).await?;
+            if bytes_read == 0 {
+                return Ok(self.finish());
+            }
+            self.bytes_read += bytes_read;
+
+            // Since QMDL is just a flat list of messages, we can't actually
+            // reproduce the container structure they came from in the original
+            // read. So we'll just pretend that all containers had exactly one
+            // message. As far as I know, the number of messages per container
+            // doesn't actually affect anything, so this should be fine.
+            let mut frames = Vec::new();
+            let frame_stats = self.assembler.push(&buf, &mut frames);
+            if !frames.is_empty() {
+                return Ok(Some(MessagesContainer::from_frames(frames, frame_stats)));
             }
         }
+    }
 
-        let mut buf = Vec::new();
-        let bytes_read = self.reader.read_until(MESSAGE_TERMINATOR, &mut buf).await?;
-        self.bytes_read += bytes_read;
-
-        // Since QMDL is just a flat list of messages, we can't actually
-        // reproduce the container structure they came from in the original
-        // read. So we'll just pretend that all containers had exactly one
-        // message. As far as I know, the number of messages per container
-        // doesn't actually affect anything, so this should be fine.
-        Ok(Some(MessagesContainer {
-            data_type: DataType::UserSpace,
-            num_messages: 1,
-            messages: vec![HdlcEncapsulatedMessage {
-                len: bytes_read as u32,
-                data: buf,
-            }],
-        }))
+    // Reports a dropped frame if the file ended partway through one
+    fn finish(&mut self) -> Option<MessagesContainer> {
+        let frame_stats = self.assembler.finish();
+        (!frame_stats.is_empty()).then(|| MessagesContainer::from_frames(Vec::new(), frame_stats))
+    }
+}
+
+/// How fast a [QmdlReplay] plays back its messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// Wait between messages for as long as the modem originally did
+    Original,
+    /// Like [ReplaySpeed::Original], but sped up by the given factor
+    Accelerated(f64),
+    /// Don't wait between messages at all
+    AsFastAsPossible,
+}
+
+/// Plays back a QMDL file through the same stream interface as
+/// [crate::diag_device::DiagDevice::as_stream], spacing messages out by the
+/// timestamps of the logs they contain.
+pub struct QmdlReplay<T>
+where
+    T: AsyncRead,
+{
+    reader: QmdlReader<T>,
+    speed: ReplaySpeed,
+    last_timestamp: Option<DateTime<FixedOffset>>,
+}
+
+impl<T> QmdlReplay<T>
+where
+    T: AsyncRead + Unpin,
+{
+    pub fn new(reader: QmdlReader<T>, speed: ReplaySpeed) -> Self {
+        QmdlReplay {
+            reader,
+            speed,
+            last_timestamp: None,
+        }
+    }
+
+    pub fn as_stream(
+        &mut self,
+    ) -> impl TryStream<Ok = MessagesContainer, Error = std::io::Error> + '_ {
+        futures::stream::try_unfold(self, |replay| async {
+            let maybe_container = replay.get_next_messages_container().await?;
+            match maybe_container {
+                Some(container) => Ok(Some((container, replay))),
+                None => Ok(None),
+            }
+        })
+    }
+
+    pub async fn get_next_messages_container(
+        &mut self,
+    ) -> Result<Option<MessagesContainer>, std::io::Error> {
+        let Some(container) = self.reader.get_next_messages_container().await? else {
+            return Ok(None);
+        };
+        if let Some(delay) = self.delay_before(&container) {
+            tokio::time::sleep(delay).await;
+        }
+        Ok(Some(container))
+    }
+
+    // How long to wait before yielding this container, based on the time
+    // between its first log message and the last one we saw. Containers
+    // without logs (e.g. responses to requests) are yielded immediately.
+    fn delay_before(&mut self, container: &MessagesContainer) -> Option<Duration> {
+        let factor = match self.speed {
+            ReplaySpeed::Original => 1.0,
+            ReplaySpeed::Accelerated(factor) => factor,
+            ReplaySpeed::AsFastAsPossible => return None,
+        };
+        let timestamp = container
+            .clone()
+            .into_messages()
+            .into_iter()
+            .find_map(|msg| match msg {
+                Ok(Message::Log { timestamp, .. }) => Some(timestamp.to_datetime()),
+                _ => None,
+            })?;
+        let last_timestamp = self.last_timestamp.replace(timestamp)?;
+        // logs aren't always in order, so ignore any that go back in time
+        let elapsed = (timestamp - last_timestamp).to_std().ok()?;
+        Some(elapsed.div_f64(factor))
     }
 }
 
@@ -113,8 +214,8 @@ where
 mod test {
     use std::io::Cursor;
 
-    use crate::diag::CRC_CCITT;
-    use crate::hdlc::hdlc_encapsulate;
+    use crate::diag::{CRC_CCITT, DataType, HdlcEncapsulatedMessage};
+    use crate::hdlc::{HdlcFrameStats, hdlc_encapsulate};
 
     use super::*;
 
@@ -148,11 +249,13 @@ mod test {
                 data_type: DataType::UserSpace,
                 num_messages: messages1.len() as u32,
                 messages: messages1.to_vec(),
+                frame_stats: HdlcFrameStats::default(),
             },
             MessagesContainer {
                 data_type: DataType::UserSpace,
                 num_messages: messages2.len() as u32,
                 messages: messages2.to_vec(),
+                frame_stats: HdlcFrameStats::default(),
             },
         ]
     }
@@ -167,6 +270,7 @@ mod test {
                 data_type: DataType::UserSpace,
                 num_messages: 1,
                 messages: vec![message],
+                frame_stats: HdlcFrameStats::default(),
             };
             assert_eq!(
                 expected_container,
@@ -189,6 +293,7 @@ mod test {
                 data_type: DataType::UserSpace,
                 num_messages: 1,
                 messages: vec![message],
+                frame_stats: HdlcFrameStats::default(),
             };
             assert_eq!(
                 expected_container,
@@ -201,6 +306,26 @@ mod test {
         ));
     }
 
+    #[tokio::test]
+    async fn test_truncated_qmdl_reader() {
+        let mut buf = get_test_message_bytes();
+        let last_len = get_test_messages().last().unwrap().len as usize;
+        buf.truncate(buf.len() - last_len / 2);
+        let mut reader = QmdlReader::new(Cursor::new(buf), None);
+        for _ in 0..9 {
+            let container = reader.get_next_messages_container().await.unwrap().unwrap();
+            assert_eq!(container.num_messages, 1);
+        }
+        // the cut off message is dropped
+        let container = reader.get_next_messages_container().await.unwrap().unwrap();
+        assert_eq!(container.num_messages, 0);
+        assert_eq!(container.frame_stats.dropped, 1);
+        assert!(matches!(
+            reader.get_next_messages_container().await,
+            Ok(None)
+        ));
+    }
+
     #[tokio::test]
     async fn test_qmdl_writer() {
         let mut buf = Vec::new();
@@ -230,6 +355,7 @@ mod test {
                 data_type: DataType::UserSpace,
                 num_messages: 1,
                 messages: vec![message],
+                frame_stats: HdlcFrameStats::default(),
             };
             assert_eq!(
                 expected_container,
@@ -241,4 +367,50 @@ mod test {
             Ok(None)
         ));
     }
+
+    // an HDLC encapsulated LTE ML1 serving cell info log with the given
+    // number of 1.25ms ticks in its timestamp
+    fn get_test_log_message(ticks: u64) -> HdlcEncapsulatedMessage {
+        let mut data = vec![16, 0, 22, 0, 22, 0, 0xe4, 0xb0];
+        data.extend((ticks << 16).to_le_bytes());
+        data.extend([0x01, 0x05, 0x10, 0x00, 0x14, 0x05, 0x04, 0x01, 0x00, 0x00]);
+        let data = hdlc_encapsulate(&data, &CRC_CCITT);
+        HdlcEncapsulatedMessage {
+            len: data.len() as u32,
+            data,
+        }
+    }
+
+    async fn time_replay(speed: ReplaySpeed) -> std::time::Duration {
+        // three logs, 40ms apart
+        let buf: Vec<u8> = [0, 32, 64]
+            .into_iter()
+            .flat_map(|ticks| get_test_log_message(ticks).data)
+            .collect();
+        let limit = Some(buf.len());
+        let mut replay = QmdlReplay::new(QmdlReader::new(Cursor::new(buf), limit), speed);
+        let start = std::time::Instant::now();
+        for ticks in [0, 32, 64] {
+            let container = replay.get_next_messages_container().await.unwrap().unwrap();
+            assert_eq!(container.messages, vec![get_test_log_message(ticks)]);
+        }
+        assert!(matches!(
+            replay.get_next_messages_container().await,
+            Ok(None)
+        ));
+        start.elapsed()
+    }
+
+    #[tokio::test]
+    async fn test_qmdl_replay_timing() {
+        let original = time_replay(ReplaySpeed::Original).await;
+        assert!(original >= std::time::Duration::from_millis(80));
+
+        let accelerated = time_replay(ReplaySpeed::Accelerated(4.0)).await;
+        assert!(accelerated >= std::time::Duration::from_millis(20));
+        assert!(accelerated < original);
+
+        let fast = time_replay(ReplaySpeed::AsFastAsPossible).await;
+        assert!(fast < std::time::Duration::from_millis(20));
+    }
 }
@@ -49,3 +49,8 @@ impl RuntimeMetadata {
         }
     }
 }
+
+/// Reads a big-endian bit string, such as an ASN.1 BIT STRING, as an integer
+pub(crate) fn bits_to_u32(bits: impl Iterator<Item = bool>) -> u32 {
+    bits.fold(0, |acc, bit| (acc << 1) | bit as u32)
+}
@@ -0,0 +1,176 @@
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::FromRawFd;
+use std::sync::mpsc;
+
+use futures::TryStreamExt;
+use rayhunter::{
+    diag::{CRC_CCITT, LogBody, MESSAGE_TERMINATOR, Message},
+    diag_device::DiagDevice,
+    diag_serial::SerialTransport,
+    hdlc::{hdlc_decapsulate, hdlc_encapsulate},
+};
+
+const LOG_CONFIG_OPCODE: u32 = 115;
+const RETRIEVE_ID_RANGES: u32 = 1;
+const SET_MASK: u32 = 3;
+const EXT_MSG_CONFIG_OPCODE: u8 = 0x7d;
+
+// an LTE ML1 serving cell info log for PCI 260 on EARFCN 1300
+const SERVING_CELL_INFO_LOG: [u8; 22] = [
+    16, 0, 22, 0, 22, 0, 0xe4, 0xb0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01, 0x05, 0x10, 0x00, 0x14, 0x05,
+];
+const SERVING_CELL_INFO_BODY_END: [u8; 4] = [0x04, 0x01, 0x00, 0x00];
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn response(subopcode: u32, payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend(LOG_CONFIG_OPCODE.to_le_bytes());
+    data.extend(subopcode.to_le_bytes());
+    data.extend(0u32.to_le_bytes());
+    data.extend(payload);
+    hdlc_encapsulate(&data, &CRC_CCITT)
+}
+
+fn serving_cell_info_frame() -> Vec<u8> {
+    let mut log = SERVING_CELL_INFO_LOG.to_vec();
+    log.extend(SERVING_CELL_INFO_BODY_END);
+    hdlc_encapsulate(&log, &CRC_CCITT)
+}
+
+// Pretends to be a modem on the other end of a tty: answers log config
+// requests, reports each mask it's asked to set, and then, once it's been
+// told which F3 messages to send, starts logging. A log is also sent ahead
+// of the log mask sizes, as if logging were already on.
+// Hands the master end back so it isn't closed (hanging up the tty) before
+// the log has been read.
+fn run_fake_modem(
+    mut master: File,
+    log_mask_sizes: [u32; 16],
+    masks_tx: mpsc::Sender<Vec<u8>>,
+) -> File {
+    let mut f3_configured = false;
+    let mut frame = Vec::new();
+    let mut buf = [0; 1024];
+    while !f3_configured {
+        let bytes_read = master.read(&mut buf).unwrap();
+        for &b in &buf[..bytes_read] {
+            frame.push(b);
+            if b != MESSAGE_TERMINATOR {
+                continue;
+            }
+            let request = hdlc_decapsulate(&frame, &CRC_CCITT).unwrap();
+            frame.clear();
+            match request[0] {
+                // the event report ack isn't waited for, so don't bother
+                0x60 => continue,
+                EXT_MSG_CONFIG_OPCODE => {
+                    // F3 messages should be off by default
+                    assert_eq!(read_u32(&request, 4), 0);
+                    master
+                        .write_all(&hdlc_encapsulate(&request, &CRC_CCITT))
+                        .unwrap();
+                    f3_configured = true;
+                    continue;
+                }
+                _ => {}
+            }
+            assert_eq!(read_u32(&request, 0), LOG_CONFIG_OPCODE);
+            match read_u32(&request, 4) {
+                RETRIEVE_ID_RANGES => {
+                    let payload: Vec<u8> = log_mask_sizes
+                        .iter()
+                        .flat_map(|size| size.to_le_bytes())
+                        .collect();
+                    let mut data = serving_cell_info_frame();
+                    data.extend(response(RETRIEVE_ID_RANGES, &payload));
+                    master.write_all(&data).unwrap();
+                }
+                SET_MASK => {
+                    masks_tx.send(request[8..].to_vec()).unwrap();
+                    master.write_all(&response(SET_MASK, &[])).unwrap();
+                }
+                other => panic!("unexpected log config request {other}"),
+            }
+        }
+    }
+
+    // split a log across two writes, to check that frames are reassembled
+    let frame = serving_cell_info_frame();
+    let (first, second) = frame.split_at(10);
+    master.write_all(first).unwrap();
+    master.flush().unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    master.write_all(second).unwrap();
+    master
+}
+
+// Returns the master end of a new pty, along with the path to its slave end
+// and the slave end itself
+fn open_pty() -> (File, String, File) {
+    let mut master = 0;
+    let mut slave = 0;
+    unsafe {
+        assert_eq!(
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                std::ptr::null(),
+            ),
+            0
+        );
+        let path = CStr::from_ptr(libc::ttyname(slave))
+            .to_str()
+            .unwrap()
+            .to_string();
+        (File::from_raw_fd(master), path, File::from_raw_fd(slave))
+    }
+}
+
+#[tokio::test]
+async fn test_serial_transport_with_fake_modem() {
+    // the slave end must stay open until the transport has opened it too
+    let (master, path, _slave) = open_pty();
+    let mut log_mask_sizes = [0; 16];
+    log_mask_sizes[0x1] = 0x1000;
+    log_mask_sizes[0xb] = 0x1000;
+    let (masks_tx, masks_rx) = mpsc::channel();
+    let modem = std::thread::spawn(move || run_fake_modem(master, log_mask_sizes, masks_tx));
+
+    let transport = SerialTransport::open(&path).await.unwrap();
+    let mut dev = DiagDevice::with_transport(transport);
+    dev.config_logs().await.unwrap();
+
+    let masks: Vec<Vec<u8>> = masks_rx.try_iter().collect();
+    assert_eq!(masks.len(), 2);
+    for mask in &masks {
+        assert_eq!(read_u32(mask, 4), 0x1000);
+    }
+    // LTE RRC OTA (0xb0c0) should be enabled in the log type 0xb mask
+    let lte_mask = masks.iter().find(|mask| read_u32(mask, 0) == 0xb).unwrap();
+    assert_ne!(lte_mask[8 + 0xc0 / 8] & (1 << (0xc0 % 8)), 0);
+
+    // the log sent while the log mask sizes were being asked for comes
+    // first, then the one split across two writes
+    let mut stream = Box::pin(dev.as_stream().into_stream());
+    for _ in 0..2 {
+        let container = stream.try_next().await.unwrap().unwrap();
+        let messages = container.into_messages();
+        assert_eq!(messages.len(), 1);
+        let Ok(Message::Log { body, .. }) = &messages[0] else {
+            panic!("expected log message, got {:?}", messages[0]);
+        };
+        let LogBody::LteML1ServingCellInfo { packet, .. } = body else {
+            panic!("expected serving cell info, got {body:?}");
+        };
+        assert_eq!(packet.get_earfcn(), 1300);
+        assert_eq!(packet.get_pci(), 260);
+    }
+    modem.join().unwrap();
+}
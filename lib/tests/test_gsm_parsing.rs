@@ -0,0 +1,167 @@
+use rayhunter::{
+    analysis::information_element::{GsmInformationElement, InformationElement},
+    diag::{LogBody, Message, Timestamp},
+    gsmtap::{GsmtapType, UmSubtype},
+    gsmtap_parser,
+};
+
+#[test]
+fn test_gsm_rr_bcch() {
+    let si3 = vec![
+        0x06, 0x1b, 0x00, 0x01, 0x00, 0xf1, 0x10, 0x00, 0x01, 0x49, 0x03, 0x05, 0x27, 0x47, 0x40,
+        0xe5, 0x04, 0x00, 0x2c, 0x0b, 0x2b, 0x2b,
+    ];
+    let msg = Message::Log {
+        pending_msgs: 0,
+        outer_length: 37,
+        inner_length: 37,
+        log_type: 0x512f,
+        timestamp: Timestamp { ts: 0 },
+        body: LogBody::GsmRrSignallingMessage {
+            channel_type: 0x81,
+            message_type: 0x1b,
+            length: si3.len() as u8,
+            msg: si3.clone(),
+        },
+        subscription_id: None,
+    };
+    let (_, gsmtap_msg) = gsmtap_parser::parse(msg).unwrap().unwrap();
+    assert_eq!(
+        gsmtap_msg.header.gsmtap_type,
+        GsmtapType::Um(UmSubtype::Bcch)
+    );
+    assert_eq!(gsmtap_msg.header.packet_type, 1);
+    assert_eq!(gsmtap_msg.header.subtype, 1);
+    assert!(!gsmtap_msg.header.uplink);
+    // L2 pseudo length followed by the L3 message
+    assert_eq!(gsmtap_msg.payload[0], 0x59);
+    assert_eq!(&gsmtap_msg.payload[1..], &si3);
+
+    let InformationElement::GSM(gsm_ie) = InformationElement::try_from(&gsmtap_msg).unwrap() else {
+        panic!("expected GSM information element");
+    };
+    let GsmInformationElement::SystemInformationType3(si3) = *gsm_ie else {
+        panic!("expected SI3");
+    };
+    assert_eq!(si3.lai.lac, 1);
+}
+
+#[test]
+fn test_gsm_rr_dcch() {
+    let cmd = vec![0x06, 0x35, 0x01];
+    let msg = Message::Log {
+        pending_msgs: 0,
+        outer_length: 18,
+        inner_length: 18,
+        log_type: 0x512f,
+        timestamp: Timestamp { ts: 0 },
+        body: LogBody::GsmRrSignallingMessage {
+            channel_type: 0x80,
+            message_type: 0x35,
+            length: cmd.len() as u8,
+            msg: cmd.clone(),
+        },
+        subscription_id: None,
+    };
+    let (_, gsmtap_msg) = gsmtap_parser::parse(msg).unwrap().unwrap();
+    assert_eq!(
+        gsmtap_msg.header.gsmtap_type,
+        GsmtapType::Um(UmSubtype::Sdcch)
+    );
+    assert_eq!(&gsmtap_msg.payload[..], &[0x01, 0x03, 0x0d, 0x06, 0x35, 0x01]);
+    assert!(matches!(
+        InformationElement::try_from(&gsmtap_msg).unwrap(),
+        InformationElement::GSM(ie) if matches!(*ie, GsmInformationElement::CipheringModeCommand(_))
+    ));
+}
+
+#[test]
+fn test_gsm_rr_sacch() {
+    // the start of an SI6, which is only sent on the SACCH
+    let si6 = vec![0x06, 0x1e, 0x00, 0x01, 0x00, 0xf1, 0x10, 0x00, 0x01];
+    let msg = Message::Log {
+        pending_msgs: 0,
+        outer_length: 24,
+        inner_length: 24,
+        log_type: 0x512f,
+        timestamp: Timestamp { ts: 0 },
+        body: LogBody::GsmRrSignallingMessage {
+            channel_type: 0x84,
+            message_type: 0x1e,
+            length: si6.len() as u8,
+            msg: si6.clone(),
+        },
+        subscription_id: None,
+    };
+    let (_, gsmtap_msg) = gsmtap_parser::parse(msg).unwrap().unwrap();
+    assert_eq!(
+        gsmtap_msg.header.gsmtap_type,
+        GsmtapType::Um(UmSubtype::Sacch)
+    );
+    // SDCCH with the ACCH flag
+    assert_eq!(gsmtap_msg.header.subtype, 0x86);
+    // L1 header, then the LAPDm header
+    assert_eq!(&gsmtap_msg.payload[..5], &[0x00, 0x00, 0x01, 0x03, 0x25]);
+    assert_eq!(&gsmtap_msg.payload[5..], &si6);
+    assert!(matches!(
+        InformationElement::try_from(&gsmtap_msg).unwrap(),
+        InformationElement::GSM(ie) if matches!(*ie, GsmInformationElement::OtherRr(0x1e))
+    ));
+}
+
+#[test]
+fn test_gsm_rr_too_long() {
+    // the L2 length indicator is only 6 bits wide, so this can't be framed
+    let msg = Message::Log {
+        pending_msgs: 0,
+        outer_length: 79,
+        inner_length: 79,
+        log_type: 0x512f,
+        timestamp: Timestamp { ts: 0 },
+        body: LogBody::GsmRrSignallingMessage {
+            channel_type: 0x81,
+            message_type: 0x1b,
+            length: 64,
+            msg: vec![0x2b; 64],
+        },
+        subscription_id: None,
+    };
+    assert!(matches!(
+        gsmtap_parser::parse(msg),
+        Err(gsmtap_parser::GsmtapParserError::GsmRrMessageTooLong(64))
+    ));
+}
+
+#[test]
+fn test_gprs_mac_pacch() {
+    let pcco = vec![0x40, 0x04, 0x46, 0x82, 0x95, 0x00];
+    let msg = Message::Log {
+        pending_msgs: 0,
+        outer_length: 21,
+        inner_length: 21,
+        log_type: 0x5226,
+        timestamp: Timestamp { ts: 0 },
+        body: LogBody::GprsMacSignallingMessage {
+            channel_type: 0x83,
+            message_type: 0x01,
+            length: pcco.len() as u8,
+            msg: pcco.clone(),
+        },
+        subscription_id: None,
+    };
+    let (_, gsmtap_msg) = gsmtap_parser::parse(msg).unwrap().unwrap();
+    assert_eq!(
+        gsmtap_msg.header.gsmtap_type,
+        GsmtapType::Um(UmSubtype::Pacch)
+    );
+    assert!(!gsmtap_msg.header.uplink);
+    assert_eq!(&gsmtap_msg.payload[..], &pcco);
+
+    let InformationElement::GSM(gsm_ie) = InformationElement::try_from(&gsmtap_msg).unwrap() else {
+        panic!("expected GSM information element");
+    };
+    let GsmInformationElement::PacketCellChangeOrder(pcco) = *gsm_ie else {
+        panic!("expected packet cell change order");
+    };
+    assert_eq!(pcco.target_cell.unwrap().arfcn, 20);
+}
@@ -1,8 +1,12 @@
 use deku::prelude::*;
 use rayhunter::{
-    diag::{LogBody, LteRrcOtaPacket, Message, Timestamp},
+    analysis::information_element::{InformationElement, LteInformationElement},
+    diag::{LogBody, LteRrcOtaPacket, Message, Nas4GMessageDirection, Timestamp},
+    gsmtap::{GsmtapType, LteNasSubtype, LteRrcSubtype},
     gsmtap_parser,
 };
+use telcom_parser::lte_nas::SecurityHeaderType;
+use telcom_parser::nb_iot_rrc::{PagingUeIdentity, PcchMessageType};
 
 // Tests here are based on https://github.com/fgsect/scat/blob/97442580e628de414c9f7c2a185f4e28d0ee7523/tests/test_diagltelogparser.py
 
@@ -38,11 +42,12 @@ fn test_lte_rrc_ota() {
                     len: 2,
                     packet: vec![0x10, 0x15],
                 }
-            }
+            },
+            subscription_id: None,
         }
     );
     let (_, gsmtap_msg) = gsmtap_parser::parse(parsed).unwrap().unwrap();
-    assert_eq!(&gsmtap_msg.payload, &[0x10, 0x15]);
+    assert_eq!(&gsmtap_msg.payload[..], &[0x10, 0x15]);
     assert_eq!(gsmtap_msg.header.packet_type, 13);
     assert_eq!(gsmtap_msg.header.timeslot, 0);
     assert_eq!(gsmtap_msg.header.arfcn, 1811);
@@ -83,10 +88,11 @@ fn test_lte_rrc_ota() {
                     packet: vec![0x10, 0x15],
                 },
             },
+            subscription_id: None,
         }
     );
     let (_, gsmtap_msg) = gsmtap_parser::parse(parsed).unwrap().unwrap();
-    assert_eq!(&gsmtap_msg.payload, &[0x10, 0x15,]);
+    assert_eq!(&gsmtap_msg.payload[..], &[0x10, 0x15,]);
     assert_eq!(gsmtap_msg.header.packet_type, 13);
     assert_eq!(gsmtap_msg.header.timeslot, 0);
     assert_eq!(gsmtap_msg.header.arfcn, 1811);
@@ -128,11 +134,12 @@ fn test_lte_rrc_ota() {
                     ],
                 },
             },
+            subscription_id: None,
         }
     );
     let (_, gsmtap_msg) = gsmtap_parser::parse(parsed).unwrap().unwrap();
     assert_eq!(
-        &gsmtap_msg.payload,
+        &gsmtap_msg.payload[..],
         &[
             0x40, 0x85, 0x8e, 0xc4, 0xe5, 0xbf, 0xe0, 0x50, 0xdc, 0x29, 0x15, 0x16, 0x00,
         ]
@@ -179,11 +186,12 @@ fn test_lte_rrc_ota() {
                     ],
                 },
             },
+            subscription_id: None,
         }
     );
     let (_, gsmtap_msg) = gsmtap_parser::parse(parsed).unwrap().unwrap();
     assert_eq!(
-        &gsmtap_msg.payload,
+        &gsmtap_msg.payload[..],
         &[
             0x08, 0x10, 0xa7, 0x14, 0x53, 0x59, 0xa6, 0x05, 0x43, 0x68, 0xc0, 0x3b, 0xda, 0x30,
             0x04, 0xa6, 0x88, 0x02, 0x8d, 0xa2, 0x00, 0x9a, 0x68, 0x40,
@@ -227,11 +235,12 @@ fn test_lte_rrc_ota() {
                     packet: vec![0x28, 0x18, 0x40, 0x16, 0x8, 0x8, 0x80, 0x0, 0x0],
                 },
             },
+            subscription_id: None,
         }
     );
     let (_, gsmtap_msg) = gsmtap_parser::parse(parsed).unwrap().unwrap();
     assert_eq!(
-        &gsmtap_msg.payload,
+        &gsmtap_msg.payload[..],
         &[0x28, 0x18, 0x40, 0x16, 0x08, 0x08, 0x80, 0x00, 0x00,]
     );
     assert_eq!(gsmtap_msg.header.packet_type, 13);
@@ -272,11 +281,12 @@ fn test_lte_rrc_ota() {
                     packet: vec![0x40, 0xc, 0x8e, 0xc9, 0x42, 0x89, 0xe0],
                 },
             },
+            subscription_id: None,
         }
     );
     let (_, gsmtap_msg) = gsmtap_parser::parse(parsed).unwrap().unwrap();
     assert_eq!(
-        &gsmtap_msg.payload,
+        &gsmtap_msg.payload[..],
         &[0x40, 0x0c, 0x8e, 0xc9, 0x42, 0x89, 0xe0,]
     );
     assert_eq!(gsmtap_msg.header.packet_type, 13);
@@ -323,11 +333,12 @@ fn test_lte_rrc_ota() {
                     ],
                 },
             },
+            subscription_id: None,
         }
     );
     let (_, gsmtap_msg) = gsmtap_parser::parse(parsed).unwrap().unwrap();
     assert_eq!(
-        &gsmtap_msg.payload,
+        &gsmtap_msg.payload[..],
         &[
             0x08, 0x10, 0xa5, 0x34, 0x61, 0x41, 0xa3, 0x1c, 0x31, 0x68, 0x04, 0x40, 0x1a, 0x00,
             0x49, 0x16, 0x7c, 0x23, 0x15, 0x9f, 0x00, 0x10, 0x67, 0xc1, 0x06, 0xd9, 0xe0, 0x00,
@@ -371,10 +382,11 @@ fn test_lte_rrc_ota() {
                     packet: vec![0x2c, 0x0],
                 },
             },
+            subscription_id: None,
         }
     );
     let (_, gsmtap_msg) = gsmtap_parser::parse(parsed).unwrap().unwrap();
-    assert_eq!(&gsmtap_msg.payload, &[0x2c, 0x00]);
+    assert_eq!(&gsmtap_msg.payload[..], &[0x2c, 0x00]);
     assert_eq!(gsmtap_msg.header.packet_type, 13);
     assert_eq!(gsmtap_msg.header.timeslot, 0);
     assert_eq!(gsmtap_msg.header.arfcn, 6200);
@@ -413,11 +425,12 @@ fn test_lte_rrc_ota() {
                     packet: vec![0x40, 0xb, 0x8e, 0xc1, 0xdd, 0x13, 0xb0],
                 },
             },
+            subscription_id: None,
         }
     );
     let (_, gsmtap_msg) = gsmtap_parser::parse(parsed).unwrap().unwrap();
     assert_eq!(
-        &gsmtap_msg.payload,
+        &gsmtap_msg.payload[..],
         &[0x40, 0x0b, 0x8e, 0xc1, 0xdd, 0x13, 0xb0,]
     );
     assert_eq!(gsmtap_msg.header.packet_type, 13);
@@ -458,10 +471,11 @@ fn test_lte_rrc_ota() {
                     packet: vec![0x2e, 0x2],
                 },
             },
+            subscription_id: None,
         }
     );
     let (_, gsmtap_msg) = gsmtap_parser::parse(parsed).unwrap().unwrap();
-    assert_eq!(&gsmtap_msg.payload, &[0x2e, 0x02]);
+    assert_eq!(&gsmtap_msg.payload[..], &[0x2e, 0x02]);
     assert_eq!(gsmtap_msg.header.packet_type, 13);
     assert_eq!(gsmtap_msg.header.timeslot, 0);
     assert_eq!(gsmtap_msg.header.arfcn, 6300);
@@ -504,11 +518,12 @@ fn test_lte_rrc_ota() {
                     ],
                 },
             },
+            subscription_id: None,
         }
     );
     let (_, gsmtap_msg) = gsmtap_parser::parse(parsed).unwrap().unwrap();
     assert_eq!(
-        &gsmtap_msg.payload,
+        &gsmtap_msg.payload[..],
         &[
             0x40, 0x49, 0x88, 0x05, 0xc0, 0x97, 0x02, 0xd3, 0xb0, 0x98, 0x1c, 0x20, 0xa0, 0x81,
             0x8c, 0x43, 0x26, 0xd0,
@@ -523,3 +538,96 @@ fn test_lte_rrc_ota() {
     assert_eq!(gsmtap_msg.header.subtype, 5);
     assert_eq!(gsmtap_msg.header.subslot, 5);
 }
+
+#[test]
+fn test_lte_nas_ciphered() {
+    let nas = vec![0x27, 0x01, 0x02, 0x03, 0x04, 0x2a, 0x99, 0x88];
+    let msg = Message::Log {
+        pending_msgs: 0,
+        outer_length: 28,
+        inner_length: 28,
+        log_type: 0xb0ec,
+        timestamp: Timestamp { ts: 0 },
+        body: LogBody::Nas4GMessage {
+            direction: Nas4GMessageDirection::Downlink,
+            ext_header_version: 1,
+            rrc_rel: 15,
+            rrc_version_minor: 0,
+            rrc_version_major: 15,
+            msg: nas.clone(),
+        },
+        subscription_id: None,
+    };
+    let (_, gsmtap_msg) = gsmtap_parser::parse(msg).unwrap().unwrap();
+    assert_eq!(
+        gsmtap_msg.header.gsmtap_type,
+        GsmtapType::LteNas(LteNasSubtype::Secure)
+    );
+    assert_eq!(gsmtap_msg.payload, nas);
+
+    let InformationElement::LTE(lte_ie) = InformationElement::try_from(&gsmtap_msg).unwrap() else {
+        panic!("expected LTE information element");
+    };
+    let LteInformationElement::CipheredNAS(header) = *lte_ie else {
+        panic!("expected ciphered NAS message");
+    };
+    assert_eq!(
+        header.security_header_type,
+        SecurityHeaderType::IntegrityProtectedAndCiphered
+    );
+    assert_eq!(header.message_authentication_code, 0x01020304);
+    assert_eq!(header.sequence_number, 0x2a);
+}
+
+#[test]
+fn test_nb_iot_rrc_ota() {
+    let paging = vec![
+        0x40, 0x81, 0x2d, 0xea, 0xdb, 0xee, 0xf3, 0x20, 0x02, 0x02, 0x02, 0x46, 0x8a, 0xcf, 0x12,
+    ];
+    let msg = Message::Log {
+        pending_msgs: 0,
+        outer_length: 0,
+        inner_length: 0,
+        log_type: 0xb0c0,
+        timestamp: Timestamp { ts: 0 },
+        body: LogBody::LteRrcOtaMessage {
+            ext_header_version: 26,
+            packet: LteRrcOtaPacket::V25 {
+                rrc_rel_maj: 15,
+                rrc_rel_min: 64,
+                nr_rrc_rel_maj: 15,
+                nr_rrc_rel_min: 64,
+                bearer_id: 0,
+                phy_cell_id: 270,
+                earfcn: 2525,
+                sfn_subfn: 0,
+                pdu_num: 47,
+                sib_mask: 0,
+                len: paging.len() as u16,
+                packet: paging,
+            },
+        },
+        subscription_id: None,
+    };
+    let (_, gsmtap_msg) = gsmtap_parser::parse(msg).unwrap().unwrap();
+    assert_eq!(
+        gsmtap_msg.header.gsmtap_type,
+        GsmtapType::LteRrc(LteRrcSubtype::PcchNb)
+    );
+    let InformationElement::LTE(lte_ie) = InformationElement::try_from(&gsmtap_msg).unwrap() else {
+        panic!("expected LTE information element");
+    };
+    let LteInformationElement::PcchNb(pcch) = *lte_ie else {
+        panic!("expected NB-IoT PCCH");
+    };
+    let PcchMessageType::Paging(paging) = pcch.message else {
+        panic!("expected paging");
+    };
+    assert_eq!(
+        paging.paging_records[0],
+        PagingUeIdentity::STmsi {
+            mmec: 0x12,
+            m_tmsi: 0xdeadbeef,
+        }
+    );
+}
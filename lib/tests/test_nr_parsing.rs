@@ -0,0 +1,57 @@
+use rayhunter::{
+    analysis::information_element::{InformationElement, NrInformationElement},
+    diag::{LogBody, Message, NrRrcOtaPacket, Timestamp},
+    gsmtap::{GsmtapType, NrRrcSubtype},
+    gsmtap_parser,
+};
+use telcom_parser::nr_rrc::{CipheringAlgorithm, DlDcchMessageType};
+
+#[test]
+fn test_nr_rrc_security_mode_command() {
+    let rrc = vec![0x22, 0x08, 0x10];
+    let msg = Message::Log {
+        pending_msgs: 0,
+        outer_length: 38,
+        inner_length: 38,
+        log_type: 0xb821,
+        timestamp: Timestamp { ts: 0 },
+        body: LogBody::NrRrcOtaMessage {
+            header_version: 9,
+            packet: NrRrcOtaPacket::V0 {
+                rrc_rel_maj: 15,
+                rrc_rel_min: 64,
+                bearer_id: 1,
+                phy_cell_id: 1,
+                nr_arfcn: 632628,
+                sfn_subfn: 0,
+                pdu_num: 4,
+                sib_mask: 0,
+                len: rrc.len() as u16,
+                packet: rrc.clone(),
+            },
+        },
+        subscription_id: None,
+    };
+    let (_, gsmtap_msg) = gsmtap_parser::parse(msg).unwrap().unwrap();
+    assert_eq!(
+        gsmtap_msg.header.gsmtap_type,
+        GsmtapType::NrRrc(NrRrcSubtype::DlDcch)
+    );
+    assert!(!gsmtap_msg.header.uplink);
+    assert_eq!(gsmtap_msg.payload, rrc);
+
+    let InformationElement::FiveG(nr_ie) = InformationElement::try_from(&gsmtap_msg).unwrap()
+    else {
+        panic!("expected 5G information element");
+    };
+    let NrInformationElement::DlDcch(dl_dcch) = *nr_ie else {
+        panic!("expected DL-DCCH message");
+    };
+    let DlDcchMessageType::SecurityModeCommand(smc) = dl_dcch.message else {
+        panic!("expected security mode command");
+    };
+    assert_eq!(
+        smc.security_algorithm_config.unwrap().ciphering_algorithm,
+        CipheringAlgorithm::Nea0
+    );
+}
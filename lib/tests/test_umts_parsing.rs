@@ -0,0 +1,99 @@
+use rayhunter::{
+    analysis::information_element::{InformationElement, UmtsInformationElement},
+    diag::{LogBody, Message, Timestamp},
+    gsmtap::{GsmtapType, UmtsRrcSubtype},
+    gsmtap_parser,
+};
+use telcom_parser::gsm_nas::{GsmNasMessage, MobileIdentityType};
+use telcom_parser::umts_rrc::{InitialUeIdentity, UlCcchMessageType};
+
+#[test]
+fn test_wcdma_rrc_connection_request() {
+    let rrc = vec![0x20, 0x90, 0x01, 0x01, 0x01, 0x23, 0x45, 0x67, 0x89];
+    let msg = Message::Log {
+        pending_msgs: 0,
+        outer_length: 25,
+        inner_length: 25,
+        log_type: 0x412f,
+        timestamp: Timestamp { ts: 0 },
+        body: LogBody::WcdmaSignallingMessage {
+            channel_type: 0,
+            radio_bearer: 0,
+            length: rrc.len() as u16,
+            msg: rrc.clone(),
+        },
+        subscription_id: None,
+    };
+    let (_, gsmtap_msg) = gsmtap_parser::parse(msg).unwrap().unwrap();
+    assert_eq!(
+        gsmtap_msg.header.gsmtap_type,
+        GsmtapType::UmtsRrc(UmtsRrcSubtype::UlCcch)
+    );
+    assert!(gsmtap_msg.header.uplink);
+    assert_eq!(gsmtap_msg.payload, rrc);
+
+    let InformationElement::UMTS(umts_ie) = InformationElement::try_from(&gsmtap_msg).unwrap()
+    else {
+        panic!("expected UMTS information element");
+    };
+    let UmtsInformationElement::UlCcch(ul_ccch) = *umts_ie else {
+        panic!("expected UL-CCCH message");
+    };
+    let UlCcchMessageType::RrcConnectionRequest(req) = ul_ccch.message else {
+        panic!("expected RRC connection request");
+    };
+    assert_eq!(
+        req.initial_ue_identity,
+        InitialUeIdentity::Imsi("001010123456789".to_string())
+    );
+}
+
+#[test]
+fn test_wcdma_invalid_channel_type() {
+    let msg = Message::Log {
+        pending_msgs: 0,
+        outer_length: 16,
+        inner_length: 16,
+        log_type: 0x412f,
+        timestamp: Timestamp { ts: 0 },
+        body: LogBody::WcdmaSignallingMessage {
+            channel_type: 0x42,
+            radio_bearer: 0,
+            length: 0,
+            msg: vec![],
+        },
+        subscription_id: None,
+    };
+    assert!(gsmtap_parser::parse(msg).is_err());
+}
+
+#[test]
+fn test_umts_nas_identity_request() {
+    let nas = vec![0x05, 0x18, 0x01];
+    let msg = Message::Log {
+        pending_msgs: 0,
+        outer_length: 20,
+        inner_length: 20,
+        log_type: 0x713a,
+        timestamp: Timestamp { ts: 0 },
+        body: LogBody::UmtsNasOtaMessage {
+            is_uplink: 0,
+            length: nas.len() as u32,
+            msg: nas.clone(),
+        },
+        subscription_id: None,
+    };
+    let (_, gsmtap_msg) = gsmtap_parser::parse(msg).unwrap().unwrap();
+    assert_eq!(gsmtap_msg.header.gsmtap_type, GsmtapType::Abis);
+    assert!(!gsmtap_msg.header.uplink);
+    assert_eq!(gsmtap_msg.payload, nas);
+
+    let InformationElement::UMTS(umts_ie) = InformationElement::try_from(&gsmtap_msg).unwrap()
+    else {
+        panic!("expected UMTS information element");
+    };
+    let UmtsInformationElement::Nas(GsmNasMessage::MmIdentityRequest(req)) = *umts_ie else {
+        panic!("expected MM identity request");
+    };
+    assert_eq!(req.identity_type, MobileIdentityType::Imsi);
+}
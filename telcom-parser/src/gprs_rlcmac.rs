@@ -0,0 +1,235 @@
+//! GPRS RLC/MAC control messages sent on the PACCH, as described in 3GPP TS
+//! 44.060.
+//!
+//! These are CSN.1 encoded, which (like unaligned PER) packs fields MSB first
+//! with no padding, so we reuse the PER bit reader. Only the messages which
+//! move the MS between cells or channels are decoded; for the rest we just
+//! report the message type.
+
+use serde::Serialize;
+
+use crate::ParsingError;
+use crate::per::PerReader;
+
+pub const MSG_TYPE_PACKET_CELL_CHANGE_ORDER: u8 = 0x01;
+pub const MSG_TYPE_PACKET_DOWNLINK_ASSIGNMENT: u8 = 0x02;
+pub const MSG_TYPE_PACKET_UPLINK_ASSIGNMENT: u8 = 0x0a;
+
+// MAC header payload types (TS 44.060 10.4.7)
+const PAYLOAD_TYPE_CONTROL_BLOCK: u64 = 0b01;
+const PAYLOAD_TYPE_CONTROL_BLOCK_WITH_OPTIONAL_OCTETS: u64 = 0b10;
+
+/// A decoded RLC/MAC control block
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum RlcMacControlMessage {
+    PacketCellChangeOrder(PacketCellChangeOrder),
+    PacketDownlinkAssignment(PacketDownlinkAssignment),
+    PacketUplinkAssignment(PacketUplinkAssignment),
+    /// A downlink message we only decode the type of
+    OtherDownlink(u8),
+    /// An uplink message we only decode the type of
+    OtherUplink(u8),
+}
+
+/// Decodes an RLC/MAC control block, starting with its MAC header
+pub fn decode(data: &[u8], uplink: bool) -> Result<RlcMacControlMessage, ParsingError> {
+    let mut reader = PerReader::new(data);
+    if uplink {
+        // payload type, spare bits and the retry bit
+        let _mac_header = reader.bits(8)?;
+        return Ok(RlcMacControlMessage::OtherUplink(reader.bits(6)? as u8));
+    }
+
+    let payload_type = reader.bits(2)?;
+    // RRBP, S/P and USF
+    let _ = reader.bits(6)?;
+    match payload_type {
+        PAYLOAD_TYPE_CONTROL_BLOCK => {}
+        PAYLOAD_TYPE_CONTROL_BLOCK_WITH_OPTIONAL_OCTETS => {
+            // RBSN, RTI and FS, followed by the AC bit which says whether
+            // there's an octet containing PR, TFI and D
+            let _ = reader.bits(7)?;
+            if reader.bool()? {
+                let _ = reader.bits(8)?;
+            }
+        }
+        _ => {
+            return Err(ParsingError::InvalidEncoding(
+                "not an RLC/MAC control block",
+            ));
+        }
+    }
+
+    let message_type = reader.bits(6)? as u8;
+    Ok(match message_type {
+        MSG_TYPE_PACKET_CELL_CHANGE_ORDER => {
+            RlcMacControlMessage::PacketCellChangeOrder(PacketCellChangeOrder::decode(&mut reader)?)
+        }
+        MSG_TYPE_PACKET_DOWNLINK_ASSIGNMENT => RlcMacControlMessage::PacketDownlinkAssignment(
+            PacketDownlinkAssignment::decode(&mut reader)?,
+        ),
+        MSG_TYPE_PACKET_UPLINK_ASSIGNMENT => RlcMacControlMessage::PacketUplinkAssignment(
+            PacketUplinkAssignment::decode(&mut reader)?,
+        ),
+        other => RlcMacControlMessage::OtherDownlink(other),
+    })
+}
+
+/// Which MS (or TBF) a downlink control message is addressed to
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum PacketAddress {
+    UplinkTfi(u8),
+    DownlinkTfi(u8),
+    Tlli(u32),
+    Tqi(u16),
+    PacketRequestReference {
+        random_access_info: u16,
+        frame_number: u16,
+    },
+}
+
+impl PacketAddress {
+    fn decode(reader: &mut PerReader, allow_tqi: bool) -> Result<Self, ParsingError> {
+        if !reader.bool()? {
+            // Global TFI IE
+            let downlink = reader.bool()?;
+            let tfi = reader.bits(5)? as u8;
+            return Ok(if downlink {
+                Self::DownlinkTfi(tfi)
+            } else {
+                Self::UplinkTfi(tfi)
+            });
+        }
+        if !reader.bool()? {
+            return Ok(Self::Tlli(reader.bits(32)? as u32));
+        }
+        if !allow_tqi {
+            return Err(ParsingError::InvalidEncoding(
+                "unexpected packet address type",
+            ));
+        }
+        if !reader.bool()? {
+            return Ok(Self::Tqi(reader.bits(16)? as u16));
+        }
+        Ok(Self::PacketRequestReference {
+            random_access_info: reader.bits(11)? as u16,
+            frame_number: reader.bits(16)? as u16,
+        })
+    }
+}
+
+// Reads the { 0 | 1 < PERSISTENCE_LEVEL : bit (4) > * 4 } field
+fn skip_persistence_levels(reader: &mut PerReader) -> Result<(), ParsingError> {
+    if reader.bool()? {
+        let _ = reader.bits(16)?;
+    }
+    Ok(())
+}
+
+/// Packet Cell Change Order (TS 44.060 11.2.4)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct PacketCellChangeOrder {
+    pub page_mode: u8,
+    pub address: PacketAddress,
+    /// The GSM cell the MS is ordered to, or None if the message uses an
+    /// escape (e.g. for a 3G or LTE target cell)
+    pub target_cell: Option<GsmTargetCell>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct GsmTargetCell {
+    pub immediate_release: bool,
+    pub arfcn: u16,
+    pub bsic: u8,
+}
+
+impl PacketCellChangeOrder {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        let page_mode = reader.bits(2)? as u8;
+        let address = PacketAddress::decode(reader, false)?;
+        let target_cell = if !reader.bool()? {
+            Some(GsmTargetCell {
+                immediate_release: reader.bool()?,
+                arfcn: reader.bits(10)? as u16,
+                bsic: reader.bits(6)? as u8,
+            })
+        } else {
+            None
+        };
+        Ok(Self {
+            page_mode,
+            address,
+            target_cell,
+        })
+    }
+}
+
+/// Packet Downlink Assignment (TS 44.060 11.2.7)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct PacketDownlinkAssignment {
+    pub page_mode: u8,
+    pub address: PacketAddress,
+    /// None if the message uses an escape (e.g. for EGPRS)
+    pub assignment: Option<DownlinkAssignment>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct DownlinkAssignment {
+    pub mac_mode: u8,
+    /// false for acknowledged mode, true for unacknowledged mode
+    pub rlc_unacknowledged: bool,
+    pub control_ack: bool,
+    /// Bitmap of assigned timeslots, with timeslot 0 as the MSB
+    pub timeslot_allocation: u8,
+}
+
+impl PacketDownlinkAssignment {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        let page_mode = reader.bits(2)? as u8;
+        skip_persistence_levels(reader)?;
+        let address = PacketAddress::decode(reader, false)?;
+        let assignment = if !reader.bool()? {
+            Some(DownlinkAssignment {
+                mac_mode: reader.bits(2)? as u8,
+                rlc_unacknowledged: reader.bool()?,
+                control_ack: reader.bool()?,
+                timeslot_allocation: reader.bits(8)? as u8,
+            })
+        } else {
+            None
+        };
+        Ok(Self {
+            page_mode,
+            address,
+            assignment,
+        })
+    }
+}
+
+/// Packet Uplink Assignment (TS 44.060 11.2.29)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct PacketUplinkAssignment {
+    pub page_mode: u8,
+    pub address: PacketAddress,
+    /// The coding scheme (CS-1 to CS-4) to use, or None if the message uses
+    /// an escape (e.g. for EGPRS)
+    pub channel_coding_command: Option<u8>,
+}
+
+impl PacketUplinkAssignment {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        let page_mode = reader.bits(2)? as u8;
+        skip_persistence_levels(reader)?;
+        let address = PacketAddress::decode(reader, true)?;
+        let channel_coding_command = if !reader.bool()? {
+            Some(reader.bits(2)? as u8 + 1)
+        } else {
+            None
+        };
+        Ok(Self {
+            page_mode,
+            address,
+            channel_coding_command,
+        })
+    }
+}
@@ -0,0 +1,278 @@
+//! 2G/3G Non-Access Stratum messages, as described in 3GPP TS 24.008 (MM,
+//! GMM, CC) and TS 24.011 (SMS).
+//!
+//! Like [crate::gsm_rr], these aren't ASN.1 encoded, so they're decoded by
+//! hand. Every message has its header decoded, but only the handful of
+//! messages that are interesting for IMSI catcher detection are decoded in
+//! full; everything else is returned as [GsmNasMessage::Other].
+
+use serde::Serialize;
+
+use crate::ParsingError;
+use crate::gsm_rr::Reader;
+
+pub const MSG_TYPE_MM_LOCATION_UPDATING_REJECT: u8 = 0x04;
+pub const MSG_TYPE_MM_AUTHENTICATION_REJECT: u8 = 0x11;
+pub const MSG_TYPE_MM_AUTHENTICATION_REQUEST: u8 = 0x12;
+pub const MSG_TYPE_MM_IDENTITY_REQUEST: u8 = 0x18;
+pub const MSG_TYPE_MM_CM_SERVICE_ACCEPT: u8 = 0x21;
+pub const MSG_TYPE_MM_CM_SERVICE_REJECT: u8 = 0x22;
+pub const MSG_TYPE_MM_CM_SERVICE_REQUEST: u8 = 0x24;
+pub const MSG_TYPE_GMM_IDENTITY_REQUEST: u8 = 0x15;
+
+/// IEI of the optional AUTN in MM Authentication Request
+const IEI_AUTN: u8 = 0x20;
+
+/// Protocol discriminator (TS 24.007 11.2.3.1.1)
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Debug)]
+pub enum ProtocolDiscriminator {
+    CallControl = 0x03,
+    MobilityManagement = 0x05,
+    GprsMobilityManagement = 0x08,
+    Sms = 0x09,
+    GprsSessionManagement = 0x0a,
+}
+
+impl ProtocolDiscriminator {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0x03 => Some(Self::CallControl),
+            0x05 => Some(Self::MobilityManagement),
+            0x08 => Some(Self::GprsMobilityManagement),
+            0x09 => Some(Self::Sms),
+            0x0a => Some(Self::GprsSessionManagement),
+            _ => None,
+        }
+    }
+}
+
+/// The two octet header common to all 24.008 NAS messages
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct NasHeader {
+    pub protocol_discriminator: ProtocolDiscriminator,
+    /// The skip indicator for MM and GMM, or the transaction identifier for
+    /// CC, SMS and SM
+    pub transaction_identifier: u8,
+    pub message_type: u8,
+}
+
+impl NasHeader {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        let octet = reader.u8()?;
+        let message_type = reader.u8()?;
+        let pd = octet & 0x0f;
+        let Some(protocol_discriminator) = ProtocolDiscriminator::from_u8(pd) else {
+            return Err(ParsingError::UnsupportedMessageType(pd, message_type));
+        };
+        let message_type = match protocol_discriminator {
+            // bits 7 and 8 of MM and CC message types carry the send
+            // sequence number
+            ProtocolDiscriminator::MobilityManagement | ProtocolDiscriminator::CallControl => {
+                message_type & 0x3f
+            }
+            _ => message_type,
+        };
+        Ok(Self {
+            protocol_discriminator,
+            transaction_identifier: octet >> 4,
+            message_type,
+        })
+    }
+}
+
+/// A decoded 2G/3G NAS message
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum GsmNasMessage {
+    MmIdentityRequest(IdentityRequest),
+    GmmIdentityRequest(IdentityRequest),
+    LocationUpdatingReject(LocationUpdatingReject),
+    AuthenticationRequest(AuthenticationRequest),
+    AuthenticationReject,
+    CmServiceRequest(CmServiceRequest),
+    CmServiceAccept,
+    CmServiceReject(CmServiceReject),
+    /// A message we only decode the header of
+    Other(NasHeader),
+}
+
+/// Decodes a 2G/3G NAS message, starting at its protocol discriminator octet
+pub fn decode(data: &[u8]) -> Result<GsmNasMessage, ParsingError> {
+    let mut reader = Reader::new(data);
+    let header = NasHeader::decode(&mut reader)?;
+    match header.protocol_discriminator {
+        ProtocolDiscriminator::MobilityManagement => match header.message_type {
+            MSG_TYPE_MM_IDENTITY_REQUEST => Ok(GsmNasMessage::MmIdentityRequest(
+                IdentityRequest::decode(&mut reader)?,
+            )),
+            MSG_TYPE_MM_LOCATION_UPDATING_REJECT => Ok(GsmNasMessage::LocationUpdatingReject(
+                LocationUpdatingReject {
+                    reject_cause: reader.u8()?,
+                },
+            )),
+            MSG_TYPE_MM_AUTHENTICATION_REQUEST => Ok(GsmNasMessage::AuthenticationRequest(
+                AuthenticationRequest::decode(&mut reader)?,
+            )),
+            MSG_TYPE_MM_AUTHENTICATION_REJECT => Ok(GsmNasMessage::AuthenticationReject),
+            MSG_TYPE_MM_CM_SERVICE_REQUEST => Ok(GsmNasMessage::CmServiceRequest(
+                CmServiceRequest::decode(&mut reader)?,
+            )),
+            MSG_TYPE_MM_CM_SERVICE_ACCEPT => Ok(GsmNasMessage::CmServiceAccept),
+            MSG_TYPE_MM_CM_SERVICE_REJECT => Ok(GsmNasMessage::CmServiceReject(CmServiceReject {
+                reject_cause: reader.u8()?,
+            })),
+            _ => Ok(GsmNasMessage::Other(header)),
+        },
+        ProtocolDiscriminator::GprsMobilityManagement => match header.message_type {
+            MSG_TYPE_GMM_IDENTITY_REQUEST => Ok(GsmNasMessage::GmmIdentityRequest(
+                IdentityRequest::decode(&mut reader)?,
+            )),
+            _ => Ok(GsmNasMessage::Other(header)),
+        },
+        _ => Ok(GsmNasMessage::Other(header)),
+    }
+}
+
+/// Type of identity as used in Identity Request messages (TS 24.008
+/// 10.5.3.4 and 10.5.5.9)
+#[derive(Copy, Clone, PartialEq, Serialize, Debug)]
+pub enum MobileIdentityType {
+    Imsi,
+    Imei,
+    Imeisv,
+    Tmsi,
+    Other(u8),
+}
+
+impl From<u8> for MobileIdentityType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => MobileIdentityType::Imsi,
+            2 => MobileIdentityType::Imei,
+            3 => MobileIdentityType::Imeisv,
+            4 => MobileIdentityType::Tmsi,
+            other => MobileIdentityType::Other(other),
+        }
+    }
+}
+
+/// MM or GMM Identity Request
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct IdentityRequest {
+    pub identity_type: MobileIdentityType,
+}
+
+impl IdentityRequest {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        // for GMM, the upper half octet is the force to standby flag
+        Ok(Self {
+            identity_type: MobileIdentityType::from(reader.u8()? & 0x07),
+        })
+    }
+}
+
+/// Mobile identity (TS 24.008 10.5.1.4)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum MobileIdentity {
+    Imsi(String),
+    Imei(String),
+    Imeisv(String),
+    Tmsi(u32),
+    NoIdentity,
+}
+
+impl MobileIdentity {
+    pub fn decode(value: &[u8]) -> Result<Self, ParsingError> {
+        let Some(&first) = value.first() else {
+            return Err(ParsingError::TooShort(1, 0));
+        };
+        match first & 0x07 {
+            1 => Ok(Self::Imsi(Self::decode_digits(value))),
+            2 => Ok(Self::Imei(Self::decode_digits(value))),
+            3 => Ok(Self::Imeisv(Self::decode_digits(value))),
+            4 => {
+                let mut reader = Reader::new(&value[1..]);
+                Ok(Self::Tmsi(u32::from_be_bytes(reader.array()?)))
+            }
+            _ => Ok(Self::NoIdentity),
+        }
+    }
+
+    // the first digit shares an octet with the identity type, and the rest
+    // are BCD encoded with a filler nibble if there's an even number of them
+    pub(crate) fn decode_digits(value: &[u8]) -> String {
+        let mut nibbles = vec![value[0] >> 4];
+        for octet in &value[1..] {
+            nibbles.push(octet & 0x0f);
+            nibbles.push(octet >> 4);
+        }
+        nibbles
+            .into_iter()
+            .take_while(|&nibble| nibble != 0x0f)
+            .filter_map(|nibble| char::from_digit(nibble as u32, 10))
+            .collect()
+    }
+}
+
+/// MM Location Updating Reject
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct LocationUpdatingReject {
+    /// Reject cause (TS 24.008 10.5.3.6)
+    pub reject_cause: u8,
+}
+
+/// MM Authentication Request
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct AuthenticationRequest {
+    pub ciphering_key_sequence_number: u8,
+    pub rand: [u8; 16],
+    /// Only present for UMTS authentication. A GSM authentication challenge
+    /// doesn't authenticate the network to the MS.
+    pub autn: Option<Vec<u8>>,
+}
+
+impl AuthenticationRequest {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        let ciphering_key_sequence_number = reader.u8()? & 0x07;
+        let rand = reader.array()?;
+        let mut autn = None;
+        let rest = reader.rest();
+        if rest.first() == Some(&IEI_AUTN) {
+            let mut reader = Reader::new(&rest[1..]);
+            autn = Some(reader.lv()?.to_vec());
+        }
+        Ok(Self {
+            ciphering_key_sequence_number,
+            rand,
+            autn,
+        })
+    }
+}
+
+/// MM CM Service Request
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct CmServiceRequest {
+    /// CM service type (TS 24.008 10.5.3.3), e.g. 1 for a mobile originating
+    /// call or 4 for SMS
+    pub service_type: u8,
+    pub ciphering_key_sequence_number: u8,
+    pub mobile_identity: MobileIdentity,
+}
+
+impl CmServiceRequest {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        let octet = reader.u8()?;
+        let _classmark2 = reader.lv()?;
+        Ok(Self {
+            service_type: octet & 0x0f,
+            ciphering_key_sequence_number: (octet >> 4) & 0x07,
+            mobile_identity: MobileIdentity::decode(reader.lv()?)?,
+        })
+    }
+}
+
+/// MM CM Service Reject
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct CmServiceReject {
+    /// Reject cause (TS 24.008 10.5.3.6)
+    pub reject_cause: u8,
+}
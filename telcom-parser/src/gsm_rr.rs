@@ -0,0 +1,541 @@
+//! GSM Radio Resource (RR) layer 3 messages, as described in 3GPP TS 44.018.
+//!
+//! Unlike LTE RRC, GSM RR messages aren't ASN.1 encoded, so instead of
+//! generating this module from a spec we decode the handful of messages that
+//! are useful for IMSI catcher detection by hand. Fields that we don't
+//! currently interpret (e.g. rest octets) are kept around as raw bytes.
+
+use serde::Serialize;
+
+use crate::ParsingError;
+use crate::gsm_nas::{self, GsmNasMessage};
+
+/// Protocol discriminator for Radio Resource management messages
+pub const PD_RR: u8 = 0x06;
+
+pub const MSG_TYPE_SYSTEM_INFORMATION_13: u8 = 0x00;
+pub const MSG_TYPE_CHANNEL_RELEASE: u8 = 0x0d;
+pub const MSG_TYPE_SYSTEM_INFORMATION_1: u8 = 0x19;
+pub const MSG_TYPE_SYSTEM_INFORMATION_2: u8 = 0x1a;
+pub const MSG_TYPE_SYSTEM_INFORMATION_3: u8 = 0x1b;
+pub const MSG_TYPE_SYSTEM_INFORMATION_4: u8 = 0x1c;
+pub const MSG_TYPE_CIPHERING_MODE_COMMAND: u8 = 0x35;
+pub const MSG_TYPE_IMMEDIATE_ASSIGNMENT: u8 = 0x3f;
+
+/// A decoded GSM layer 3 message
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum GsmL3Message {
+    SystemInformationType1(SystemInformationType1),
+    SystemInformationType2(SystemInformationType2),
+    SystemInformationType3(SystemInformationType3),
+    SystemInformationType4(SystemInformationType4),
+    SystemInformationType13(SystemInformationType13),
+    ImmediateAssignment(ImmediateAssignment),
+    CipheringModeCommand(CipheringModeCommand),
+    ChannelRelease(ChannelRelease),
+    /// NAS messages are carried on the same dedicated channels as RR
+    /// messages, so we decode them here as well
+    Nas(GsmNasMessage),
+    /// An RR message we only decode the message type of
+    Other(u8),
+}
+
+/// Decodes a GSM layer 3 message, starting at its protocol discriminator
+/// octet (i.e. with any L2 pseudo length or LAPDm header already stripped).
+pub fn decode(data: &[u8]) -> Result<GsmL3Message, ParsingError> {
+    let mut reader = Reader::new(data);
+    let pd = reader.u8()? & 0x0f;
+    let message_type = reader.u8()?;
+    match pd {
+        PD_RR => match message_type {
+            MSG_TYPE_SYSTEM_INFORMATION_1 => Ok(GsmL3Message::SystemInformationType1(
+                SystemInformationType1::decode(&mut reader)?,
+            )),
+            MSG_TYPE_SYSTEM_INFORMATION_2 => Ok(GsmL3Message::SystemInformationType2(
+                SystemInformationType2::decode(&mut reader)?,
+            )),
+            MSG_TYPE_SYSTEM_INFORMATION_3 => Ok(GsmL3Message::SystemInformationType3(
+                SystemInformationType3::decode(&mut reader)?,
+            )),
+            MSG_TYPE_SYSTEM_INFORMATION_4 => Ok(GsmL3Message::SystemInformationType4(
+                SystemInformationType4::decode(&mut reader)?,
+            )),
+            MSG_TYPE_SYSTEM_INFORMATION_13 => Ok(GsmL3Message::SystemInformationType13(
+                SystemInformationType13 {
+                    rest_octets: reader.rest().to_vec(),
+                },
+            )),
+            MSG_TYPE_IMMEDIATE_ASSIGNMENT => Ok(GsmL3Message::ImmediateAssignment(
+                ImmediateAssignment::decode(&mut reader)?,
+            )),
+            MSG_TYPE_CIPHERING_MODE_COMMAND => Ok(GsmL3Message::CipheringModeCommand(
+                CipheringModeCommand::decode(&mut reader)?,
+            )),
+            MSG_TYPE_CHANNEL_RELEASE => Ok(GsmL3Message::ChannelRelease(ChannelRelease {
+                rr_cause: reader.u8()?,
+            })),
+            _ => Ok(GsmL3Message::Other(message_type)),
+        },
+        _ => Ok(GsmL3Message::Nas(gsm_nas::decode(data)?)),
+    }
+}
+
+/// Minimal cursor over a byte slice which reports truncated messages as
+/// [ParsingError::TooShort].
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, ParsingError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn u16_be(&mut self) -> Result<u16, ParsingError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], ParsingError> {
+        let end = self.pos + len;
+        if end > self.data.len() {
+            return Err(ParsingError::TooShort(end, self.data.len()));
+        }
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N], ParsingError> {
+        let mut result = [0; N];
+        result.copy_from_slice(self.bytes(N)?);
+        Ok(result)
+    }
+
+    // length-value element, where the first octet is the length of the value
+    pub(crate) fn lv(&mut self) -> Result<&'a [u8], ParsingError> {
+        let len = self.u8()? as usize;
+        self.bytes(len)
+    }
+
+    // the next octet, without consuming it. Useful for checking the IEI of
+    // an optional element
+    pub(crate) fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    pub(crate) fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.pos..];
+        self.pos = self.data.len();
+        rest
+    }
+}
+
+/// A PLMN identity, decoded from its 3-octet BCD encoding (TS 24.008 10.5.1.3)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct Plmn {
+    pub mcc: u16,
+    pub mnc: u16,
+    pub three_digit_mnc: bool,
+}
+
+impl Plmn {
+    pub fn from_bcd(bytes: [u8; 3]) -> Self {
+        let mcc1 = (bytes[0] & 0x0f) as u16;
+        let mcc2 = (bytes[0] >> 4) as u16;
+        let mcc3 = (bytes[1] & 0x0f) as u16;
+        let mnc3 = (bytes[1] >> 4) as u16;
+        let mnc1 = (bytes[2] & 0x0f) as u16;
+        let mnc2 = (bytes[2] >> 4) as u16;
+        let three_digit_mnc = mnc3 != 0x0f;
+        let mnc = if three_digit_mnc {
+            mnc1 * 100 + mnc2 * 10 + mnc3
+        } else {
+            mnc1 * 10 + mnc2
+        };
+        Plmn {
+            mcc: mcc1 * 100 + mcc2 * 10 + mcc3,
+            mnc,
+            three_digit_mnc,
+        }
+    }
+}
+
+impl std::fmt::Display for Plmn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.three_digit_mnc {
+            write!(f, "{:03}-{:03}", self.mcc, self.mnc)
+        } else {
+            write!(f, "{:03}-{:02}", self.mcc, self.mnc)
+        }
+    }
+}
+
+/// Location Area Identification (TS 24.008 10.5.1.3)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct LocationAreaIdentification {
+    pub plmn: Plmn,
+    pub lac: u16,
+}
+
+impl LocationAreaIdentification {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        Ok(Self {
+            plmn: Plmn::from_bcd(reader.array()?),
+            lac: reader.u16_be()?,
+        })
+    }
+}
+
+/// RACH Control Parameters (TS 44.018 10.5.2.29)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct RachControlParameters {
+    pub max_retrans: u8,
+    pub tx_integer: u8,
+    pub cell_barred: bool,
+    pub call_reestablishment_allowed: bool,
+    /// Bitmask of the barred access control classes, with AC15 as the most
+    /// significant bit. Bit 10 is the emergency call (EC) flag.
+    pub access_control_classes: u16,
+}
+
+impl RachControlParameters {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        let octet = reader.u8()?;
+        Ok(Self {
+            max_retrans: octet >> 6,
+            tx_integer: (octet >> 2) & 0x0f,
+            cell_barred: octet & 0x02 != 0,
+            // RE is inverted: 1 means call re-establishment is *not* allowed
+            call_reestablishment_allowed: octet & 0x01 == 0,
+            access_control_classes: reader.u16_be()?,
+        })
+    }
+}
+
+/// Cell Selection Parameters (TS 44.018 10.5.2.4)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct CellSelectionParameters {
+    pub cell_reselect_hysteresis: u8,
+    pub ms_txpwr_max_cch: u8,
+    pub additional_reselect_param_ind: bool,
+    pub neci: bool,
+    pub rxlev_access_min: u8,
+}
+
+impl CellSelectionParameters {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        let octet1 = reader.u8()?;
+        let octet2 = reader.u8()?;
+        Ok(Self {
+            cell_reselect_hysteresis: octet1 >> 5,
+            ms_txpwr_max_cch: octet1 & 0x1f,
+            additional_reselect_param_ind: octet2 & 0x80 != 0,
+            neci: octet2 & 0x40 != 0,
+            rxlev_access_min: octet2 & 0x3f,
+        })
+    }
+}
+
+/// Control Channel Description (TS 44.018 10.5.2.11)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct ControlChannelDescription {
+    pub mscr: bool,
+    /// Whether IMSI attach/detach is required in this cell
+    pub att: bool,
+    pub bs_ag_blks_res: u8,
+    pub ccch_conf: u8,
+    pub bs_pa_mfrms: u8,
+    /// Periodic location updating timer, in decihours
+    pub t3212: u8,
+}
+
+impl ControlChannelDescription {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        let octet1 = reader.u8()?;
+        let octet2 = reader.u8()?;
+        let octet3 = reader.u8()?;
+        Ok(Self {
+            mscr: octet1 & 0x80 != 0,
+            att: octet1 & 0x40 != 0,
+            bs_ag_blks_res: (octet1 >> 3) & 0x07,
+            ccch_conf: octet1 & 0x07,
+            bs_pa_mfrms: octet2 & 0x07,
+            t3212: octet3,
+        })
+    }
+}
+
+/// A 16 octet frequency list, as used by the Cell Channel Description
+/// (10.5.2.1b) and Neighbour Cell Description (10.5.2.22) IEs.
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct FrequencyList(pub [u8; 16]);
+
+impl FrequencyList {
+    /// Returns the ARFCNs contained in this list if it uses the "bit map 0"
+    /// format, or None if it uses one of the range formats.
+    pub fn bitmap_0_arfcns(&self) -> Option<Vec<u16>> {
+        // format ID is in bits 8 and 7 of the first octet
+        if self.0[0] & 0xc0 != 0 {
+            return None;
+        }
+        let mut arfcns = Vec::new();
+        // ARFCN 124 is bit 4 of octet 1, ARFCN 1 is bit 1 of octet 16
+        for arfcn in 1..=124u16 {
+            let bit = arfcn - 1;
+            let octet = 15 - (bit / 8) as usize;
+            if self.0[octet] & (1 << (bit % 8)) != 0 {
+                arfcns.push(arfcn);
+            }
+        }
+        Some(arfcns)
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct SystemInformationType1 {
+    pub cell_channel_description: FrequencyList,
+    pub rach_control: RachControlParameters,
+    pub rest_octets: Vec<u8>,
+}
+
+impl SystemInformationType1 {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        Ok(Self {
+            cell_channel_description: FrequencyList(reader.array()?),
+            rach_control: RachControlParameters::decode(reader)?,
+            rest_octets: reader.rest().to_vec(),
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct SystemInformationType2 {
+    pub neighbour_cell_description: FrequencyList,
+    pub ncc_permitted: u8,
+    pub rach_control: RachControlParameters,
+}
+
+impl SystemInformationType2 {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        Ok(Self {
+            neighbour_cell_description: FrequencyList(reader.array()?),
+            ncc_permitted: reader.u8()?,
+            rach_control: RachControlParameters::decode(reader)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct SystemInformationType3 {
+    pub cell_identity: u16,
+    pub lai: LocationAreaIdentification,
+    pub control_channel_description: ControlChannelDescription,
+    pub cell_options: u8,
+    pub cell_selection: CellSelectionParameters,
+    pub rach_control: RachControlParameters,
+    pub rest_octets: Vec<u8>,
+}
+
+impl SystemInformationType3 {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        Ok(Self {
+            cell_identity: reader.u16_be()?,
+            lai: LocationAreaIdentification::decode(reader)?,
+            control_channel_description: ControlChannelDescription::decode(reader)?,
+            cell_options: reader.u8()?,
+            cell_selection: CellSelectionParameters::decode(reader)?,
+            rach_control: RachControlParameters::decode(reader)?,
+            rest_octets: reader.rest().to_vec(),
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct SystemInformationType4 {
+    pub lai: LocationAreaIdentification,
+    pub cell_selection: CellSelectionParameters,
+    pub rach_control: RachControlParameters,
+    /// Optional CBCH channel description/mobile allocation, followed by the
+    /// SI4 rest octets
+    pub rest_octets: Vec<u8>,
+}
+
+impl SystemInformationType4 {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        Ok(Self {
+            lai: LocationAreaIdentification::decode(reader)?,
+            cell_selection: CellSelectionParameters::decode(reader)?,
+            rach_control: RachControlParameters::decode(reader)?,
+            rest_octets: reader.rest().to_vec(),
+        })
+    }
+}
+
+/// SI13 consists solely of CSN.1 encoded rest octets describing the cell's
+/// GPRS configuration, which we don't decode yet. Its presence alone tells
+/// us the cell supports GPRS.
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct SystemInformationType13 {
+    pub rest_octets: Vec<u8>,
+}
+
+/// Channel Description (TS 44.018 10.5.2.5)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct ChannelDescription {
+    pub channel_type_and_tdma_offset: u8,
+    pub timeslot: u8,
+    pub training_sequence_code: u8,
+    pub hopping: Option<HoppingParameters>,
+    /// Only set when the channel isn't hopping
+    pub arfcn: Option<u16>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct HoppingParameters {
+    pub maio: u8,
+    pub hsn: u8,
+}
+
+impl ChannelDescription {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        let octet1 = reader.u8()?;
+        let octet2 = reader.u8()?;
+        let octet3 = reader.u8()?;
+        let (hopping, arfcn) = if octet2 & 0x10 != 0 {
+            let maio = ((octet2 & 0x0f) << 2) | (octet3 >> 6);
+            (
+                Some(HoppingParameters {
+                    maio,
+                    hsn: octet3 & 0x3f,
+                }),
+                None,
+            )
+        } else {
+            (None, Some((((octet2 & 0x03) as u16) << 8) | octet3 as u16))
+        };
+        Ok(Self {
+            channel_type_and_tdma_offset: octet1 >> 3,
+            timeslot: octet1 & 0x07,
+            training_sequence_code: octet2 >> 5,
+            hopping,
+            arfcn,
+        })
+    }
+}
+
+/// Request Reference (TS 44.018 10.5.2.30), which echoes the random access
+/// burst the assignment is responding to.
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct RequestReference {
+    pub ra: u8,
+    pub t1_prime: u8,
+    pub t3: u8,
+    pub t2: u8,
+}
+
+impl RequestReference {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        let ra = reader.u8()?;
+        let octet2 = reader.u8()?;
+        let octet3 = reader.u8()?;
+        Ok(Self {
+            ra,
+            t1_prime: octet2 >> 3,
+            t3: ((octet2 & 0x07) << 3) | (octet3 >> 5),
+            t2: octet3 & 0x1f,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct ImmediateAssignment {
+    pub page_mode: u8,
+    /// Set if this assigns a packet uplink/downlink TBF rather than a
+    /// dedicated channel. In that case the channel description should be
+    /// interpreted as a Packet Channel Description.
+    pub tbf: bool,
+    pub channel_description: ChannelDescription,
+    pub request_reference: RequestReference,
+    pub timing_advance: u8,
+    pub mobile_allocation: Vec<u8>,
+    /// Optional starting time followed by the IA rest octets
+    pub rest_octets: Vec<u8>,
+}
+
+impl ImmediateAssignment {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        // dedicated mode or TBF is the high nibble, page mode the low one
+        let octet = reader.u8()?;
+        Ok(Self {
+            page_mode: octet & 0x03,
+            tbf: octet & 0x10 != 0,
+            channel_description: ChannelDescription::decode(reader)?,
+            request_reference: RequestReference::decode(reader)?,
+            timing_advance: reader.u8()? & 0x3f,
+            mobile_allocation: reader.lv()?.to_vec(),
+            rest_octets: reader.rest().to_vec(),
+        })
+    }
+}
+
+/// GSM ciphering algorithms as listed in the Cipher Mode Setting IE (TS
+/// 44.018 10.5.2.9)
+#[derive(Copy, Clone, PartialEq, Serialize, Debug)]
+pub enum GsmCipherAlgorithm {
+    A5_1,
+    A5_2,
+    A5_3,
+    A5_4,
+    A5_5,
+    A5_6,
+    A5_7,
+    Reserved,
+}
+
+impl From<u8> for GsmCipherAlgorithm {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => GsmCipherAlgorithm::A5_1,
+            1 => GsmCipherAlgorithm::A5_2,
+            2 => GsmCipherAlgorithm::A5_3,
+            3 => GsmCipherAlgorithm::A5_4,
+            4 => GsmCipherAlgorithm::A5_5,
+            5 => GsmCipherAlgorithm::A5_6,
+            6 => GsmCipherAlgorithm::A5_7,
+            _ => GsmCipherAlgorithm::Reserved,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct CipheringModeCommand {
+    /// The ciphering algorithm to start, or None if the network asked the MS
+    /// not to cipher at all (i.e. A5/0)
+    pub algorithm: Option<GsmCipherAlgorithm>,
+    /// Whether the MS has to include its IMEISV in the Ciphering Mode Complete
+    pub imeisv_requested: bool,
+}
+
+impl CipheringModeCommand {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        // cipher response is the high nibble, cipher mode setting the low one
+        let octet = reader.u8()?;
+        let start_ciphering = octet & 0x01 != 0;
+        Ok(Self {
+            algorithm: start_ciphering.then(|| GsmCipherAlgorithm::from((octet >> 1) & 0x07)),
+            imeisv_requested: octet & 0x10 != 0,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct ChannelRelease {
+    /// RR Cause value (TS 44.018 10.5.2.31)
+    pub rr_cause: u8,
+}
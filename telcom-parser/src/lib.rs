@@ -1,12 +1,28 @@
 use asn1_codecs::{PerCodecData, PerCodecError, uper::UperCodec};
 use thiserror::Error;
+pub mod gprs_rlcmac;
+pub mod gsm_nas;
+pub mod gsm_rr;
+pub mod lte_nas;
 #[allow(warnings, unused, unreachable_patterns, non_camel_case_types)]
 pub mod lte_rrc;
+pub mod nb_iot_rrc;
+pub mod nr_rrc;
+mod per;
+pub mod umts_rrc;
 
 #[derive(Error, Debug)]
 pub enum ParsingError {
     #[error("Failed to decode UPER data: {0}")]
     UperDecodeError(PerCodecError),
+    #[error("Message too short (needed {0} bytes, got {1})")]
+    TooShort(usize, usize),
+    #[error("Unsupported message type {1:#04x} for protocol discriminator {0:#x}")]
+    UnsupportedMessageType(u8, u8),
+    #[error("Value {0} out of range (max {1})")]
+    ValueOutOfRange(u64, u64),
+    #[error("Invalid encoding: {0}")]
+    InvalidEncoding(&'static str),
 }
 
 pub fn decode<T>(data: &[u8]) -> Result<T, ParsingError>
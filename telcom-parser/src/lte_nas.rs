@@ -0,0 +1,456 @@
+//! LTE NAS (EPS mobility management) security headers, as described in 3GPP
+//! TS 24.301 section 9.1.
+//!
+//! This is rayhunter's only NAS decoder. Besides the security protected
+//! envelope, it decodes the fields of the EMM messages that the analyzers look
+//! at, and only the message type of the rest.
+
+use serde::Serialize;
+
+use crate::ParsingError;
+use crate::gsm_nas::MobileIdentity;
+use crate::gsm_rr::{Plmn, Reader};
+
+/// Protocol discriminator for EPS mobility management messages
+pub const PD_EMM: u8 = 0x07;
+
+/// Security header type of a Service Request, which has a header format of
+/// its own (TS 24.301 9.3.1)
+pub const SECURITY_HEADER_TYPE_SERVICE_REQUEST: u8 = 0x0c;
+
+pub const MSG_TYPE_ATTACH_REQUEST: u8 = 0x41;
+pub const MSG_TYPE_ATTACH_ACCEPT: u8 = 0x42;
+pub const MSG_TYPE_ATTACH_REJECT: u8 = 0x44;
+pub const MSG_TYPE_TRACKING_AREA_UPDATE_REQUEST: u8 = 0x48;
+pub const MSG_TYPE_TRACKING_AREA_UPDATE_ACCEPT: u8 = 0x49;
+pub const MSG_TYPE_TRACKING_AREA_UPDATE_REJECT: u8 = 0x4b;
+pub const MSG_TYPE_EXTENDED_SERVICE_REQUEST: u8 = 0x4c;
+pub const MSG_TYPE_SERVICE_REJECT: u8 = 0x4e;
+pub const MSG_TYPE_GUTI_REALLOCATION_COMMAND: u8 = 0x50;
+pub const MSG_TYPE_AUTHENTICATION_REQUEST: u8 = 0x52;
+pub const MSG_TYPE_AUTHENTICATION_RESPONSE: u8 = 0x53;
+pub const MSG_TYPE_AUTHENTICATION_REJECT: u8 = 0x54;
+pub const MSG_TYPE_IDENTITY_REQUEST: u8 = 0x55;
+pub const MSG_TYPE_IDENTITY_RESPONSE: u8 = 0x56;
+pub const MSG_TYPE_AUTHENTICATION_FAILURE: u8 = 0x5c;
+pub const MSG_TYPE_SECURITY_MODE_COMMAND: u8 = 0x5d;
+pub const MSG_TYPE_SECURITY_MODE_COMPLETE: u8 = 0x5e;
+
+/// IEI of the IMEISV in a Security Mode Complete (TS 24.301 8.2.21)
+const IEI_IMEISV: u8 = 0x23;
+/// IEIs of the optional GUTI in Attach Accept and Tracking Area Update
+/// Accept, and of the T3412 value that can come before it in the latter (TS
+/// 24.301 8.2.1 and 8.2.26)
+const IEI_GUTI: u8 = 0x50;
+const IEI_T3412_VALUE: u8 = 0x5a;
+
+/// EMM cause sent with an Authentication Failure when the network's
+/// authentication sequence number isn't in the range the USIM accepts (TS
+/// 24.301 9.9.3.9)
+pub const EMM_CAUSE_SYNCH_FAILURE: u8 = 21;
+
+/// Security header type (TS 24.301 9.3.1)
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Debug)]
+pub enum SecurityHeaderType {
+    IntegrityProtected = 1,
+    IntegrityProtectedAndCiphered = 2,
+    IntegrityProtectedWithNewContext = 3,
+    IntegrityProtectedAndCipheredWithNewContext = 4,
+}
+
+impl SecurityHeaderType {
+    /// Returns the security header type of a NAS message given its first
+    /// octet, or None if the message is a plain NAS message. Service
+    /// requests (security header type 12) use their own header format, and
+    /// are treated as plain messages.
+    pub fn from_first_octet(octet: u8) -> Option<Self> {
+        if octet & 0x0f != PD_EMM {
+            return None;
+        }
+        match octet >> 4 {
+            1 => Some(Self::IntegrityProtected),
+            2 => Some(Self::IntegrityProtectedAndCiphered),
+            3 => Some(Self::IntegrityProtectedWithNewContext),
+            4 => Some(Self::IntegrityProtectedAndCipheredWithNewContext),
+            _ => None,
+        }
+    }
+
+    pub fn is_ciphered(&self) -> bool {
+        matches!(
+            self,
+            Self::IntegrityProtectedAndCiphered | Self::IntegrityProtectedAndCipheredWithNewContext
+        )
+    }
+}
+
+/// The header of a security protected NAS message (TS 24.301 9.1)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct SecurityHeader {
+    pub security_header_type: SecurityHeaderType,
+    pub message_authentication_code: u32,
+    pub sequence_number: u8,
+}
+
+impl SecurityHeader {
+    /// Splits a security protected NAS message into its security header and
+    /// the inner NAS message. Returns None if the message isn't security
+    /// protected.
+    pub fn decode(data: &[u8]) -> Result<Option<(Self, &[u8])>, ParsingError> {
+        let mut reader = Reader::new(data);
+        let Some(security_header_type) = SecurityHeaderType::from_first_octet(reader.u8()?) else {
+            return Ok(None);
+        };
+        let message_authentication_code = u32::from_be_bytes(reader.array()?);
+        let sequence_number = reader.u8()?;
+        let header = Self {
+            security_header_type,
+            message_authentication_code,
+            sequence_number,
+        };
+        Ok(Some((header, reader.rest())))
+    }
+}
+
+/// Type of ciphering algorithm (TS 24.301 9.9.3.23)
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Debug)]
+pub enum CipheringAlgorithm {
+    Eea0,
+    Eea1,
+    Eea2,
+    Eea3,
+    Other(u8),
+}
+
+impl From<u8> for CipheringAlgorithm {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Eea0,
+            1 => Self::Eea1,
+            2 => Self::Eea2,
+            3 => Self::Eea3,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// Type of integrity protection algorithm (TS 24.301 9.9.3.23)
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Debug)]
+pub enum IntegrityAlgorithm {
+    Eia0,
+    Eia1,
+    Eia2,
+    Eia3,
+    Other(u8),
+}
+
+impl From<u8> for IntegrityAlgorithm {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Eia0,
+            1 => Self::Eia1,
+            2 => Self::Eia2,
+            3 => Self::Eia3,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// NAS security algorithms (TS 24.301 9.9.3.23)
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Debug)]
+pub struct NasSecurityAlgorithms {
+    pub ciphering: CipheringAlgorithm,
+    pub integrity: IntegrityAlgorithm,
+}
+
+impl From<u8> for NasSecurityAlgorithms {
+    fn from(value: u8) -> Self {
+        Self {
+            ciphering: CipheringAlgorithm::from((value >> 4) & 0x07),
+            integrity: IntegrityAlgorithm::from(value & 0x07),
+        }
+    }
+}
+
+/// A decoded EMM message
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum EmmMessage {
+    AttachRequest(AttachRequest),
+    AttachAccept(AttachAccept),
+    AttachReject(EmmCause),
+    TrackingAreaUpdateRequest,
+    TrackingAreaUpdateAccept(TrackingAreaUpdateAccept),
+    TrackingAreaUpdateReject(EmmCause),
+    ServiceRequest,
+    ExtendedServiceRequest,
+    ServiceReject(EmmCause),
+    GutiReallocationCommand(GutiReallocationCommand),
+    AuthenticationRequest,
+    AuthenticationResponse,
+    AuthenticationReject,
+    AuthenticationFailure(EmmCause),
+    IdentityRequest(IdentityRequest),
+    IdentityResponse(IdentityResponse),
+    SecurityModeCommand(SecurityModeCommand),
+    SecurityModeComplete(SecurityModeComplete),
+    /// A message we only decode the message type of
+    Other(u8),
+}
+
+/// Decodes a plain EMM message, starting at its protocol discriminator
+/// octet. Returns None for ESM messages, and for security protected messages,
+/// which have to be unwrapped with [SecurityHeader::decode] first.
+pub fn decode(data: &[u8]) -> Result<Option<EmmMessage>, ParsingError> {
+    let mut reader = Reader::new(data);
+    let octet = reader.u8()?;
+    if octet & 0x0f != PD_EMM {
+        return Ok(None);
+    }
+    match octet >> 4 {
+        0 => {}
+        SECURITY_HEADER_TYPE_SERVICE_REQUEST => return Ok(Some(EmmMessage::ServiceRequest)),
+        _ => return Ok(None),
+    }
+    let message_type = reader.u8()?;
+    let msg = match message_type {
+        MSG_TYPE_ATTACH_REQUEST => EmmMessage::AttachRequest(AttachRequest::decode(&mut reader)?),
+        MSG_TYPE_ATTACH_ACCEPT => EmmMessage::AttachAccept(AttachAccept::decode(&mut reader)?),
+        MSG_TYPE_ATTACH_REJECT => EmmMessage::AttachReject(EmmCause::decode(&mut reader)?),
+        MSG_TYPE_TRACKING_AREA_UPDATE_REQUEST => EmmMessage::TrackingAreaUpdateRequest,
+        MSG_TYPE_TRACKING_AREA_UPDATE_ACCEPT => {
+            EmmMessage::TrackingAreaUpdateAccept(TrackingAreaUpdateAccept::decode(&mut reader)?)
+        }
+        MSG_TYPE_TRACKING_AREA_UPDATE_REJECT => {
+            EmmMessage::TrackingAreaUpdateReject(EmmCause::decode(&mut reader)?)
+        }
+        MSG_TYPE_EXTENDED_SERVICE_REQUEST => EmmMessage::ExtendedServiceRequest,
+        MSG_TYPE_SERVICE_REJECT => EmmMessage::ServiceReject(EmmCause::decode(&mut reader)?),
+        MSG_TYPE_GUTI_REALLOCATION_COMMAND => {
+            let EpsMobileIdentity::Guti(guti) = EpsMobileIdentity::decode(reader.lv()?)? else {
+                return Err(ParsingError::InvalidEncoding(
+                    "GUTI Reallocation Command's identity isn't a GUTI",
+                ));
+            };
+            EmmMessage::GutiReallocationCommand(GutiReallocationCommand { guti })
+        }
+        MSG_TYPE_AUTHENTICATION_REQUEST => EmmMessage::AuthenticationRequest,
+        MSG_TYPE_AUTHENTICATION_RESPONSE => EmmMessage::AuthenticationResponse,
+        MSG_TYPE_AUTHENTICATION_REJECT => EmmMessage::AuthenticationReject,
+        MSG_TYPE_AUTHENTICATION_FAILURE => {
+            EmmMessage::AuthenticationFailure(EmmCause::decode(&mut reader)?)
+        }
+        MSG_TYPE_IDENTITY_REQUEST => EmmMessage::IdentityRequest(IdentityRequest {
+            identity_type: IdentityType::from(reader.u8()? & 0x07),
+        }),
+        MSG_TYPE_IDENTITY_RESPONSE => EmmMessage::IdentityResponse(IdentityResponse {
+            mobile_identity: MobileIdentity::decode(reader.lv()?)?,
+        }),
+        MSG_TYPE_SECURITY_MODE_COMMAND => {
+            EmmMessage::SecurityModeCommand(SecurityModeCommand::decode(&mut reader)?)
+        }
+        MSG_TYPE_SECURITY_MODE_COMPLETE => EmmMessage::SecurityModeComplete(SecurityModeComplete {
+            includes_imeisv: reader.rest().first() == Some(&IEI_IMEISV),
+        }),
+        _ => EmmMessage::Other(message_type),
+    };
+    Ok(Some(msg))
+}
+
+/// Security Mode Command (TS 24.301 8.2.20)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct SecurityModeCommand {
+    pub selected_algorithms: NasSecurityAlgorithms,
+}
+
+impl SecurityModeCommand {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        Ok(Self {
+            selected_algorithms: NasSecurityAlgorithms::from(reader.u8()?),
+        })
+    }
+}
+
+/// Security Mode Complete (TS 24.301 8.2.21)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct SecurityModeComplete {
+    /// Whether the phone sent its IMEISV, which it does when the Security
+    /// Mode Command asked for it. The IMEISV is the first optional IE, so we
+    /// don't need to walk the others to find it.
+    pub includes_imeisv: bool,
+}
+
+/// Type of identity (TS 24.301 9.9.3.21)
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Debug)]
+pub enum IdentityType {
+    Imsi,
+    Imei,
+    Imeisv,
+    Tmsi,
+    Other(u8),
+}
+
+impl From<u8> for IdentityType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::Imsi,
+            2 => Self::Imei,
+            3 => Self::Imeisv,
+            4 => Self::Tmsi,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// Identity Request (TS 24.301 8.2.18)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct IdentityRequest {
+    pub identity_type: IdentityType,
+}
+
+/// Attach Reject, Tracking Area Update Reject, Service Reject or
+/// Authentication Failure (TS 24.301 8.2.3, 8.2.28, 8.2.24 and 8.2.5). All of
+/// them start with the EMM cause; the optional IEs after it aren't decoded.
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct EmmCause {
+    pub emm_cause: u8,
+}
+
+impl EmmCause {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        Ok(Self {
+            emm_cause: reader.u8()?,
+        })
+    }
+}
+
+/// Globally unique temporary identity (TS 23.003 2.8)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct Guti {
+    pub plmn: Plmn,
+    pub mme_group_id: u16,
+    pub mme_code: u8,
+    pub m_tmsi: u32,
+}
+
+impl Guti {
+    /// Decodes a GUTI from the value of an EPS mobile identity IE
+    fn decode(value: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = Reader::new(value);
+        // odd/even indicator and type of identity, which the caller has
+        // already checked
+        reader.u8()?;
+        Ok(Self {
+            plmn: Plmn::from_bcd(reader.array()?),
+            mme_group_id: reader.u16_be()?,
+            mme_code: reader.u8()?,
+            m_tmsi: u32::from_be_bytes(reader.array()?),
+        })
+    }
+}
+
+/// EPS mobile identity (TS 24.301 9.9.3.12)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum EpsMobileIdentity {
+    Imsi(String),
+    Imei(String),
+    Guti(Guti),
+    Other(u8),
+}
+
+impl EpsMobileIdentity {
+    pub fn decode(value: &[u8]) -> Result<Self, ParsingError> {
+        let Some(&first) = value.first() else {
+            return Err(ParsingError::TooShort(1, 0));
+        };
+        match first & 0x07 {
+            1 => Ok(Self::Imsi(MobileIdentity::decode_digits(value))),
+            3 => Ok(Self::Imei(MobileIdentity::decode_digits(value))),
+            6 => Ok(Self::Guti(Guti::decode(value)?)),
+            other => Ok(Self::Other(other)),
+        }
+    }
+}
+
+/// Finds the GUTI among an accept message's optional IEs. It's the first one
+/// in an Attach Accept, and can only follow a T3412 value in a Tracking Area
+/// Update Accept, so we don't need to walk the rest.
+fn optional_guti(reader: &mut Reader) -> Result<Option<Guti>, ParsingError> {
+    if reader.peek() == Some(IEI_T3412_VALUE) {
+        reader.array::<2>()?;
+    }
+    if reader.peek() != Some(IEI_GUTI) {
+        return Ok(None);
+    }
+    reader.u8()?;
+    match EpsMobileIdentity::decode(reader.lv()?)? {
+        EpsMobileIdentity::Guti(guti) => Ok(Some(guti)),
+        _ => Ok(None),
+    }
+}
+
+/// Attach Request (TS 24.301 8.2.4)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct AttachRequest {
+    pub identity: EpsMobileIdentity,
+}
+
+impl AttachRequest {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        // NAS key set identifier and EPS attach type
+        reader.u8()?;
+        Ok(Self {
+            identity: EpsMobileIdentity::decode(reader.lv()?)?,
+        })
+    }
+}
+
+/// Attach Accept (TS 24.301 8.2.1)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct AttachAccept {
+    /// The GUTI the network allocated, if it allocated a new one
+    pub guti: Option<Guti>,
+}
+
+impl AttachAccept {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        // EPS attach result and T3412 value
+        reader.array::<2>()?;
+        // TAI list
+        reader.lv()?;
+        // ESM message container, which has a two octet length
+        let len = reader.u16_be()?;
+        reader.bytes(len as usize)?;
+        Ok(Self {
+            guti: optional_guti(reader)?,
+        })
+    }
+}
+
+/// Tracking Area Update Accept (TS 24.301 8.2.26)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct TrackingAreaUpdateAccept {
+    /// The GUTI the network allocated, if it allocated a new one
+    pub guti: Option<Guti>,
+}
+
+impl TrackingAreaUpdateAccept {
+    fn decode(reader: &mut Reader) -> Result<Self, ParsingError> {
+        // EPS update result
+        reader.u8()?;
+        Ok(Self {
+            guti: optional_guti(reader)?,
+        })
+    }
+}
+
+/// GUTI Reallocation Command (TS 24.301 8.2.16)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct GutiReallocationCommand {
+    pub guti: Guti,
+}
+
+/// Identity Response (TS 24.301 8.2.19)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct IdentityResponse {
+    pub mobile_identity: MobileIdentity,
+}
@@ -0,0 +1,421 @@
+//! NB-IoT RRC messages, as described in 3GPP TS 36.331 section 6.7.
+//!
+//! The NB-IoT ("-NB") definitions live in their own ASN.1 module, separate
+//! from the EUTRA definitions that [crate::lte_rrc] is generated from. Like
+//! [crate::nr_rrc], we decode the outer layers of each logical channel's
+//! message by hand: the message type, plus the MIB, paging records and
+//! security mode commands, which reuse the LTE definitions.
+
+use serde::Serialize;
+
+use crate::ParsingError;
+use crate::per::{PerReader, c1_message_types};
+
+c1_message_types! {
+    /// BCCH-DL-SCH-MessageType-NB
+    BcchDlSchMessageType {
+        SystemInformation,
+        SystemInformationBlockType1,
+    }
+}
+
+c1_message_types! {
+    /// DL-CCCH-MessageType-NB
+    DlCcchMessageType {
+        RrcConnectionReestablishment,
+        RrcConnectionReestablishmentReject,
+        RrcConnectionReject,
+        RrcConnectionSetup,
+    }
+}
+
+c1_message_types! {
+    /// UL-CCCH-MessageType-NB
+    UlCcchMessageType {
+        RrcConnectionReestablishmentRequest,
+        RrcConnectionRequest,
+        RrcConnectionResumeRequest,
+        RrcEarlyDataRequest,
+    }
+}
+
+c1_message_types! {
+    /// UL-DCCH-MessageType-NB
+    UlDcchMessageType {
+        RrcConnectionReconfigurationComplete,
+        RrcConnectionReestablishmentComplete,
+        RrcConnectionSetupComplete,
+        SecurityModeComplete,
+        SecurityModeFailure,
+        UeCapabilityInformation,
+        UlInformationTransfer,
+        RrcConnectionResumeComplete,
+        Spare8,
+        Spare7,
+        Spare6,
+        Spare5,
+        Spare4,
+        Spare3,
+        Spare2,
+        Spare1,
+    }
+}
+
+c1_message_types! {
+    /// SC-MCCH-MessageType-NB
+    ScMcchMessageType {
+        ScptmConfiguration,
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct BcchBchMessage {
+    pub message: MasterInformationBlock,
+}
+
+impl BcchBchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        Ok(Self {
+            message: MasterInformationBlock::decode(&mut reader, false)?,
+        })
+    }
+}
+
+/// BCCH-BCH-Message-TDD-NB, which carries the TDD variant of the MIB
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct BcchBchTddMessage {
+    pub message: MasterInformationBlock,
+}
+
+impl BcchBchTddMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        Ok(Self {
+            message: MasterInformationBlock::decode(&mut reader, true)?,
+        })
+    }
+}
+
+/// MasterInformationBlock-NB and MasterInformationBlock-TDD-NB, which share
+/// all the fields we care about
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct MasterInformationBlock {
+    /// The 4 most significant bits of the SFN
+    pub system_frame_number_msb: u8,
+    /// The 2 least significant bits of the hyper-SFN
+    pub hyper_sfn_lsb: u8,
+    pub scheduling_info_sib1: u8,
+    pub system_info_value_tag: u8,
+    pub ab_enabled: bool,
+    pub operation_mode: OperationMode,
+}
+
+impl MasterInformationBlock {
+    fn decode(reader: &mut PerReader, tdd: bool) -> Result<Self, ParsingError> {
+        let system_frame_number_msb = reader.bits(4)? as u8;
+        let hyper_sfn_lsb = reader.bits(2)? as u8;
+        let scheduling_info_sib1 = reader.constrained(0, 15)? as u8;
+        let system_info_value_tag = reader.constrained(0, 31)? as u8;
+        let ab_enabled = reader.bool()?;
+        // the TDD MIB has no guardband mode
+        let operation_mode = if tdd {
+            match reader.choice(3)? {
+                0 => OperationMode::InbandSamePci,
+                1 => OperationMode::InbandDifferentPci,
+                _ => OperationMode::Standalone,
+            }
+        } else {
+            match reader.choice(4)? {
+                0 => OperationMode::InbandSamePci,
+                1 => OperationMode::InbandDifferentPci,
+                2 => OperationMode::Guardband,
+                _ => OperationMode::Standalone,
+            }
+        };
+        Ok(Self {
+            system_frame_number_msb,
+            hyper_sfn_lsb,
+            scheduling_info_sib1,
+            system_info_value_tag,
+            ab_enabled,
+            operation_mode,
+        })
+    }
+}
+
+/// How the NB-IoT carrier is deployed relative to an LTE carrier
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Debug)]
+pub enum OperationMode {
+    InbandSamePci,
+    InbandDifferentPci,
+    Guardband,
+    Standalone,
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct BcchDlSchMessage {
+    pub message: BcchDlSchMessageType,
+}
+
+impl BcchDlSchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        Ok(Self {
+            message: BcchDlSchMessageType::decode(&mut reader)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct DlCcchMessage {
+    pub message: DlCcchMessageType,
+}
+
+impl DlCcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        Ok(Self {
+            message: DlCcchMessageType::decode(&mut reader)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct DlDcchMessage {
+    pub message: DlDcchMessageType,
+}
+
+impl DlDcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        if reader.bool()? {
+            return Ok(Self {
+                message: DlDcchMessageType::MessageClassExtension,
+            });
+        }
+        use DlDcchMessageType as T;
+        let message = match reader.choice(8)? {
+            0 => T::DlInformationTransfer,
+            1 => T::RrcConnectionReconfiguration,
+            2 => T::RrcConnectionRelease,
+            3 => T::SecurityModeCommand(SecurityModeCommand::decode(&mut reader)?),
+            4 => T::UeCapabilityEnquiry,
+            5 => T::RrcConnectionResume,
+            _ => T::Spare,
+        };
+        Ok(Self { message })
+    }
+}
+
+/// DL-DCCH-MessageType-NB
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum DlDcchMessageType {
+    DlInformationTransfer,
+    RrcConnectionReconfiguration,
+    RrcConnectionRelease,
+    SecurityModeCommand(SecurityModeCommand),
+    UeCapabilityEnquiry,
+    RrcConnectionResume,
+    Spare,
+    MessageClassExtension,
+}
+
+/// Security Mode Command (TS 36.331 6.2.2), which NB-IoT shares with LTE
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct SecurityModeCommand {
+    pub rrc_transaction_identifier: u8,
+    /// None if the message uses a critical extension we don't know about
+    pub security_algorithm_config: Option<SecurityAlgorithmConfig>,
+}
+
+impl SecurityModeCommand {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        let rrc_transaction_identifier = reader.constrained(0, 3)? as u8;
+        // criticalExtensions is either c1 or criticalExtensionsFuture, and
+        // only the first of c1's alternatives isn't a spare
+        let security_algorithm_config = if !reader.bool()? && reader.choice(4)? == 0 {
+            let [_non_critical_extension_present] = reader.presence_bits()?;
+            // SecurityConfigSMC's extension marker
+            let _ = reader.bool()?;
+            Some(SecurityAlgorithmConfig::decode(reader)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            rrc_transaction_identifier,
+            security_algorithm_config,
+        })
+    }
+}
+
+/// Security algorithm config (TS 36.331 6.3.3)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct SecurityAlgorithmConfig {
+    pub ciphering_algorithm: CipheringAlgorithm,
+    pub integrity_prot_algorithm: IntegrityProtAlgorithm,
+}
+
+impl SecurityAlgorithmConfig {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        let ciphering_algorithm = match reader.extensible_choice(8)? {
+            Some(0) => CipheringAlgorithm::Eea0,
+            Some(1) => CipheringAlgorithm::Eea1,
+            Some(2) => CipheringAlgorithm::Eea2,
+            Some(3) => CipheringAlgorithm::Eea3,
+            _ => CipheringAlgorithm::Unknown,
+        };
+        let integrity_prot_algorithm = match reader.extensible_choice(8)? {
+            Some(0) => IntegrityProtAlgorithm::Eia0,
+            Some(1) => IntegrityProtAlgorithm::Eia1,
+            Some(2) => IntegrityProtAlgorithm::Eia2,
+            Some(3) => IntegrityProtAlgorithm::Eia3,
+            _ => IntegrityProtAlgorithm::Unknown,
+        };
+        Ok(Self {
+            ciphering_algorithm,
+            integrity_prot_algorithm,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Debug)]
+pub enum CipheringAlgorithm {
+    Eea0,
+    Eea1,
+    Eea2,
+    Eea3,
+    /// A spare or extension value
+    Unknown,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Debug)]
+pub enum IntegrityProtAlgorithm {
+    Eia0,
+    Eia1,
+    Eia2,
+    Eia3,
+    /// A spare or extension value
+    Unknown,
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct UlCcchMessage {
+    pub message: UlCcchMessageType,
+}
+
+impl UlCcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        Ok(Self {
+            message: UlCcchMessageType::decode(&mut reader)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct UlDcchMessage {
+    pub message: UlDcchMessageType,
+}
+
+impl UlDcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        Ok(Self {
+            message: UlDcchMessageType::decode(&mut reader)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct PcchMessage {
+    pub message: PcchMessageType,
+}
+
+impl PcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        // c1 has a single alternative, so there's no index to read
+        let message = if reader.bool()? {
+            PcchMessageType::MessageClassExtension
+        } else {
+            PcchMessageType::Paging(Paging::decode(&mut reader)?)
+        };
+        Ok(Self { message })
+    }
+}
+
+/// PCCH-MessageType-NB
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum PcchMessageType {
+    Paging(Paging),
+    MessageClassExtension,
+}
+
+/// Paging-NB (TS 36.331 6.7.2)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct Paging {
+    pub paging_records: Vec<PagingUeIdentity>,
+    pub system_info_modification: bool,
+}
+
+impl Paging {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        // pagingRecordList, systemInfoModification,
+        // systemInfoModification-eDRX and nonCriticalExtension
+        let [paging_record_list_present, system_info_modification, _, _] =
+            reader.presence_bits()?;
+        let mut paging_records = Vec::new();
+        if paging_record_list_present {
+            let count = reader.constrained(1, 16)?;
+            for _ in 0..count {
+                let extended = reader.bool()?;
+                let identity = match reader.extensible_choice(2)? {
+                    Some(0) => PagingUeIdentity::STmsi {
+                        mmec: reader.bits(8)? as u8,
+                        m_tmsi: reader.bits(32)? as u32,
+                    },
+                    Some(_) => PagingUeIdentity::Imsi(reader.digits(6, 21)?),
+                    None => PagingUeIdentity::Unknown,
+                };
+                let is_unknown = identity == PagingUeIdentity::Unknown;
+                paging_records.push(identity);
+                // we don't skip over extension additions, so we can't tell
+                // where the next record starts
+                if extended || is_unknown {
+                    break;
+                }
+            }
+        }
+        Ok(Self {
+            paging_records,
+            system_info_modification,
+        })
+    }
+}
+
+/// PagingUE-Identity
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum PagingUeIdentity {
+    STmsi {
+        mmec: u8,
+        m_tmsi: u32,
+    },
+    Imsi(String),
+    /// An extension alternative
+    Unknown,
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct ScMcchMessage {
+    pub message: ScMcchMessageType,
+}
+
+impl ScMcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        Ok(Self {
+            message: ScMcchMessageType::decode(&mut reader)?,
+        })
+    }
+}
@@ -0,0 +1,476 @@
+//! 5G NR RRC messages, as described in 3GPP TS 38.331.
+//!
+//! Like [crate::umts_rrc], this decodes the outer layers of each logical
+//! channel's message by hand rather than generating the full ASN.1 module:
+//! the message type, plus the contents of the few messages that matter for
+//! IMSI catcher detection (MIB, paging, RRC setup requests and security mode
+//! commands).
+
+use serde::Serialize;
+
+use crate::ParsingError;
+use crate::per::{PerReader, c1_message_types};
+
+c1_message_types! {
+    /// BCCH-DL-SCH-MessageType
+    BcchDlSchMessageType {
+        SystemInformation,
+        SystemInformationBlockType1,
+    }
+}
+
+c1_message_types! {
+    /// DL-CCCH-MessageType
+    DlCcchMessageType {
+        RrcReject,
+        RrcSetup,
+        Spare2,
+        Spare1,
+    }
+}
+
+c1_message_types! {
+    /// UL-CCCH1-MessageType
+    UlCcch1MessageType {
+        RrcResumeRequest1,
+        Spare3,
+        Spare2,
+        Spare1,
+    }
+}
+
+c1_message_types! {
+    /// UL-DCCH-MessageType
+    UlDcchMessageType {
+        MeasurementReport,
+        RrcReconfigurationComplete,
+        RrcSetupComplete,
+        RrcReestablishmentComplete,
+        RrcResumeComplete,
+        SecurityModeComplete,
+        SecurityModeFailure,
+        UlInformationTransfer,
+        LocationMeasurementIndication,
+        UeCapabilityInformation,
+        CounterCheckResponse,
+        UeAssistanceInformation,
+        FailureInformation,
+        UlInformationTransferMrdc,
+        ScgFailureInformation,
+        ScgFailureInformationEutra,
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct BcchBchMessage {
+    pub message: BcchBchMessageType,
+}
+
+impl BcchBchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        let message = match reader.choice(2)? {
+            0 => BcchBchMessageType::Mib(Mib::decode(&mut reader)?),
+            _ => BcchBchMessageType::MessageClassExtension,
+        };
+        Ok(Self { message })
+    }
+}
+
+/// BCCH-BCH-MessageType
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum BcchBchMessageType {
+    Mib(Mib),
+    MessageClassExtension,
+}
+
+/// Master Information Block (TS 38.331 6.2.2)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct Mib {
+    /// The 6 most significant bits of the SFN
+    pub system_frame_number: u8,
+    pub ssb_subcarrier_offset: u8,
+    pub cell_barred: bool,
+    pub intra_freq_reselection_allowed: bool,
+}
+
+impl Mib {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        let system_frame_number = reader.bits(6)? as u8;
+        let _sub_carrier_spacing_common = reader.bits(1)?;
+        let ssb_subcarrier_offset = reader.constrained(0, 15)? as u8;
+        let _dmrs_type_a_position = reader.bits(1)?;
+        let _pdcch_config_sib1 = reader.bits(8)?;
+        // both ENUMERATEDs list the restrictive value first
+        let cell_barred = !reader.bool()?;
+        let intra_freq_reselection_allowed = !reader.bool()?;
+        Ok(Self {
+            system_frame_number,
+            ssb_subcarrier_offset,
+            cell_barred,
+            intra_freq_reselection_allowed,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct BcchDlSchMessage {
+    pub message: BcchDlSchMessageType,
+}
+
+impl BcchDlSchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        Ok(Self {
+            message: BcchDlSchMessageType::decode(&mut reader)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct DlCcchMessage {
+    pub message: DlCcchMessageType,
+}
+
+impl DlCcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        Ok(Self {
+            message: DlCcchMessageType::decode(&mut reader)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct DlDcchMessage {
+    pub message: DlDcchMessageType,
+}
+
+impl DlDcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        if reader.bool()? {
+            return Ok(Self {
+                message: DlDcchMessageType::MessageClassExtension,
+            });
+        }
+        use DlDcchMessageType as T;
+        let message = match reader.choice(16)? {
+            0 => T::RrcReconfiguration(RrcReconfiguration::decode(&mut reader)?),
+            1 => T::RrcResume,
+            2 => T::RrcRelease,
+            3 => T::RrcReestablishment,
+            4 => T::SecurityModeCommand(SecurityModeCommand::decode(&mut reader)?),
+            5 => T::DlInformationTransfer,
+            6 => T::UeCapabilityEnquiry,
+            7 => T::CounterCheck,
+            8 => T::MobilityFromNrCommand,
+            9 => T::DlDedicatedMessageSegment,
+            10 => T::UeInformationRequest,
+            11 => T::DlInformationTransferMrdc,
+            12 => T::LoggedMeasurementConfiguration,
+            _ => T::Spare,
+        };
+        Ok(Self { message })
+    }
+}
+
+/// DL-DCCH-MessageType
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum DlDcchMessageType {
+    RrcReconfiguration(RrcReconfiguration),
+    RrcResume,
+    RrcRelease,
+    RrcReestablishment,
+    SecurityModeCommand(SecurityModeCommand),
+    DlInformationTransfer,
+    UeCapabilityEnquiry,
+    CounterCheck,
+    MobilityFromNrCommand,
+    DlDedicatedMessageSegment,
+    UeInformationRequest,
+    DlInformationTransferMrdc,
+    LoggedMeasurementConfiguration,
+    Spare,
+    MessageClassExtension,
+}
+
+/// RRC Reconfiguration (TS 38.331 6.2.2). Besides being sent on the DL-DCCH,
+/// in EN-DC this is also carried inside LTE RRC messages, in which case it's
+/// logged on its own.
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct RrcReconfiguration {
+    pub rrc_transaction_identifier: u8,
+}
+
+impl RrcReconfiguration {
+    pub fn decode_standalone(data: &[u8]) -> Result<Self, ParsingError> {
+        Self::decode(&mut PerReader::new(data))
+    }
+
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        Ok(Self {
+            rrc_transaction_identifier: reader.constrained(0, 3)? as u8,
+        })
+    }
+}
+
+/// RRC Reconfiguration Complete (TS 38.331 6.2.2), which like
+/// [RrcReconfiguration] may be logged on its own in EN-DC.
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct RrcReconfigurationComplete {
+    pub rrc_transaction_identifier: u8,
+}
+
+impl RrcReconfigurationComplete {
+    pub fn decode_standalone(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        Ok(Self {
+            rrc_transaction_identifier: reader.constrained(0, 3)? as u8,
+        })
+    }
+}
+
+/// Security Mode Command (TS 38.331 6.2.2)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct SecurityModeCommand {
+    pub rrc_transaction_identifier: u8,
+    /// None if the message uses a critical extension we don't know about
+    pub security_algorithm_config: Option<SecurityAlgorithmConfig>,
+}
+
+impl SecurityModeCommand {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        let rrc_transaction_identifier = reader.constrained(0, 3)? as u8;
+        let security_algorithm_config = match reader.choice(2)? {
+            0 => {
+                // lateNonCriticalExtension and nonCriticalExtension
+                let [_, _] = reader.presence_bits()?;
+                // SecurityConfigSMC's extension marker
+                let _ = reader.bool()?;
+                Some(SecurityAlgorithmConfig::decode(reader)?)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            rrc_transaction_identifier,
+            security_algorithm_config,
+        })
+    }
+}
+
+/// Security algorithm config (TS 38.331 6.3.2)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct SecurityAlgorithmConfig {
+    pub ciphering_algorithm: CipheringAlgorithm,
+    pub integrity_prot_algorithm: Option<IntegrityProtAlgorithm>,
+}
+
+impl SecurityAlgorithmConfig {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        let _extension = reader.bool()?;
+        let [integrity_prot_algorithm_present] = reader.presence_bits()?;
+        let ciphering_algorithm = match reader.extensible_choice(8)? {
+            Some(0) => CipheringAlgorithm::Nea0,
+            Some(1) => CipheringAlgorithm::Nea1,
+            Some(2) => CipheringAlgorithm::Nea2,
+            Some(3) => CipheringAlgorithm::Nea3,
+            _ => CipheringAlgorithm::Unknown,
+        };
+        let integrity_prot_algorithm = if integrity_prot_algorithm_present {
+            Some(match reader.extensible_choice(8)? {
+                Some(0) => IntegrityProtAlgorithm::Nia0,
+                Some(1) => IntegrityProtAlgorithm::Nia1,
+                Some(2) => IntegrityProtAlgorithm::Nia2,
+                Some(3) => IntegrityProtAlgorithm::Nia3,
+                _ => IntegrityProtAlgorithm::Unknown,
+            })
+        } else {
+            None
+        };
+        Ok(Self {
+            ciphering_algorithm,
+            integrity_prot_algorithm,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Debug)]
+pub enum CipheringAlgorithm {
+    Nea0,
+    Nea1,
+    Nea2,
+    Nea3,
+    /// A spare or extension value
+    Unknown,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Debug)]
+pub enum IntegrityProtAlgorithm {
+    Nia0,
+    Nia1,
+    Nia2,
+    Nia3,
+    /// A spare or extension value
+    Unknown,
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct UlCcchMessage {
+    pub message: UlCcchMessageType,
+}
+
+impl UlCcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        if reader.bool()? {
+            return Ok(Self {
+                message: UlCcchMessageType::MessageClassExtension,
+            });
+        }
+        let message = match reader.choice(4)? {
+            0 => UlCcchMessageType::RrcSetupRequest(RrcSetupRequest::decode(&mut reader)?),
+            1 => UlCcchMessageType::RrcResumeRequest,
+            2 => UlCcchMessageType::RrcReestablishmentRequest,
+            _ => UlCcchMessageType::RrcSystemInfoRequest,
+        };
+        Ok(Self { message })
+    }
+}
+
+/// UL-CCCH-MessageType
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum UlCcchMessageType {
+    RrcSetupRequest(RrcSetupRequest),
+    RrcResumeRequest,
+    RrcReestablishmentRequest,
+    RrcSystemInfoRequest,
+    MessageClassExtension,
+}
+
+/// RRC Setup Request (TS 38.331 6.2.2)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct RrcSetupRequest {
+    pub ue_identity: InitialUeIdentity,
+    pub establishment_cause: u8,
+}
+
+impl RrcSetupRequest {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        let ue_identity = match reader.choice(2)? {
+            0 => InitialUeIdentity::Ng5gSTmsiPart1(reader.bits(39)?),
+            _ => InitialUeIdentity::RandomValue(reader.bits(39)?),
+        };
+        Ok(Self {
+            ue_identity,
+            establishment_cause: reader.choice(16)? as u8,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum InitialUeIdentity {
+    /// The rightmost 39 bits of the 5G-S-TMSI
+    Ng5gSTmsiPart1(u64),
+    RandomValue(u64),
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct UlCcch1Message {
+    pub message: UlCcch1MessageType,
+}
+
+impl UlCcch1Message {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        Ok(Self {
+            message: UlCcch1MessageType::decode(&mut reader)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct UlDcchMessage {
+    pub message: UlDcchMessageType,
+}
+
+impl UlDcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        Ok(Self {
+            message: UlDcchMessageType::decode(&mut reader)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct PcchMessage {
+    pub message: PcchMessageType,
+}
+
+impl PcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        if reader.bool()? {
+            return Ok(Self {
+                message: PcchMessageType::MessageClassExtension,
+            });
+        }
+        let message = match reader.choice(2)? {
+            0 => PcchMessageType::Paging(Paging::decode(&mut reader)?),
+            _ => PcchMessageType::Spare,
+        };
+        Ok(Self { message })
+    }
+}
+
+/// PCCH-MessageType
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum PcchMessageType {
+    Paging(Paging),
+    Spare,
+    MessageClassExtension,
+}
+
+/// Paging (TS 38.331 6.2.2)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct Paging {
+    pub paging_records: Vec<PagingUeIdentity>,
+}
+
+impl Paging {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        // pagingRecordList, lateNonCriticalExtension and nonCriticalExtension
+        let [paging_record_list_present, _, _] = reader.presence_bits()?;
+        let mut paging_records = Vec::new();
+        if paging_record_list_present {
+            let count = reader.constrained(1, 32)?;
+            for _ in 0..count {
+                let extended = reader.bool()?;
+                let [_access_type_present] = reader.presence_bits()?;
+                let identity = match reader.extensible_choice(2)? {
+                    Some(0) => PagingUeIdentity::Ng5gSTmsi(reader.bits(48)?),
+                    Some(_) => PagingUeIdentity::FullIRnti(reader.bits(40)?),
+                    None => PagingUeIdentity::Unknown,
+                };
+                let is_unknown = identity == PagingUeIdentity::Unknown;
+                paging_records.push(identity);
+                // we don't skip over extension additions, so we can't tell
+                // where the next record starts
+                if extended || is_unknown {
+                    break;
+                }
+            }
+        }
+        Ok(Self { paging_records })
+    }
+}
+
+/// PagingUE-Identity
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum PagingUeIdentity {
+    Ng5gSTmsi(u64),
+    FullIRnti(u64),
+    /// An extension alternative
+    Unknown,
+}
@@ -0,0 +1,145 @@
+//! A minimal unaligned PER (X.691) bit reader, used by the hand-written UMTS,
+//! NR and NB-IoT RRC decoders, which only look at the outermost layers of a
+//! message (e.g. which message type it is) and the few fields analyzers
+//! need. Only LTE RRC is generated from its ASN.1 module with hampi; the rest
+//! of these messages isn't decoded at all.
+
+use crate::ParsingError;
+
+pub(crate) struct PerReader<'a> {
+    data: &'a [u8],
+    bit_pos: usize,
+}
+
+impl<'a> PerReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, bit_pos: 0 }
+    }
+
+    pub(crate) fn bits(&mut self, num_bits: usize) -> Result<u64, ParsingError> {
+        assert!(num_bits <= 64);
+        let end = self.bit_pos + num_bits;
+        if end > self.data.len() * 8 {
+            return Err(ParsingError::TooShort(end.div_ceil(8), self.data.len()));
+        }
+        let mut value = 0;
+        for pos in self.bit_pos..end {
+            let bit = (self.data[pos / 8] >> (7 - pos % 8)) & 1;
+            value = (value << 1) | bit as u64;
+        }
+        self.bit_pos = end;
+        Ok(value)
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, ParsingError> {
+        Ok(self.bits(1)? == 1)
+    }
+
+    /// Reads a constrained whole number in the range `lb..=ub`
+    pub(crate) fn constrained(&mut self, lb: u64, ub: u64) -> Result<u64, ParsingError> {
+        let range = ub - lb + 1;
+        let num_bits = (u64::BITS - (range - 1).leading_zeros()) as usize;
+        let value = lb + self.bits(num_bits)?;
+        if value > ub {
+            return Err(ParsingError::ValueOutOfRange(value, ub));
+        }
+        Ok(value)
+    }
+
+    /// Reads the index of a non-extensible CHOICE or ENUMERATED type with
+    /// `num_alternatives` root alternatives
+    pub(crate) fn choice(&mut self, num_alternatives: u64) -> Result<usize, ParsingError> {
+        Ok(self.constrained(0, num_alternatives - 1)? as usize)
+    }
+
+    /// Reads the index of an extensible CHOICE or ENUMERATED type, returning
+    /// None if it was one of the (unknown to us) extension alternatives
+    pub(crate) fn extensible_choice(
+        &mut self,
+        num_alternatives: u64,
+    ) -> Result<Option<usize>, ParsingError> {
+        if self.bool()? {
+            return Ok(None);
+        }
+        Ok(Some(self.choice(num_alternatives)?))
+    }
+
+    /// Reads a SEQUENCE's preamble of OPTIONAL/DEFAULT presence bits
+    pub(crate) fn presence_bits<const N: usize>(&mut self) -> Result<[bool; N], ParsingError> {
+        let mut present = [false; N];
+        for bit in present.iter_mut() {
+            *bit = self.bool()?;
+        }
+        Ok(present)
+    }
+
+    /// Reads a SEQUENCE OF decimal digits whose length is constrained to
+    /// `lb..=ub`, as used for IMSIs and IMEIs
+    pub(crate) fn digits(&mut self, lb: u64, ub: u64) -> Result<String, ParsingError> {
+        let len = self.constrained(lb, ub)?;
+        self.fixed_digits(len)
+    }
+
+    pub(crate) fn fixed_digits(&mut self, len: u64) -> Result<String, ParsingError> {
+        let mut digits = String::with_capacity(len as usize);
+        for _ in 0..len {
+            let digit = self.bits(4)?;
+            digits.push(char::from_digit(digit as u32, 16).unwrap_or('?'));
+        }
+        Ok(digits)
+    }
+
+    /// Reads an OCTET STRING whose length is constrained to `lb..=ub`
+    pub(crate) fn octets(&mut self, lb: u64, ub: u64) -> Result<Vec<u8>, ParsingError> {
+        let len = self.constrained(lb, ub)?;
+        (0..len).map(|_| Ok(self.bits(8)? as u8)).collect()
+    }
+}
+
+// Declares a message type whose root is `CHOICE { c1 CHOICE { ... },
+// messageClassExtension }`, where we don't decode the c1 alternatives any
+// further. Alternatives are listed in the order they appear in the ASN.1.
+macro_rules! c1_message_types {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, PartialEq, Eq, serde::Serialize, Debug)]
+        pub enum $name {
+            $($variant,)+
+            MessageClassExtension,
+        }
+
+        impl $name {
+            const C1_VARIANTS: &[$name] = &[$($name::$variant),+];
+
+            fn decode(
+                reader: &mut $crate::per::PerReader,
+            ) -> Result<Self, $crate::ParsingError> {
+                if reader.bool()? {
+                    return Ok(Self::MessageClassExtension);
+                }
+                Ok(Self::C1_VARIANTS[reader.choice(Self::C1_VARIANTS.len() as u64)?])
+            }
+        }
+    };
+}
+
+pub(crate) use c1_message_types;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_per_reader() {
+        let mut reader = PerReader::new(&[0b1011_0011, 0b0100_0000]);
+        assert!(reader.bool().unwrap());
+        // 3 bits for 0..=7
+        assert_eq!(reader.choice(8).unwrap(), 0b011);
+        // 0 bits for a single alternative
+        assert_eq!(reader.choice(1).unwrap(), 0);
+        // 2 bits for 5..=8
+        assert_eq!(reader.constrained(5, 8).unwrap(), 5);
+        assert_eq!(reader.bits(4).unwrap(), 0b1101);
+        assert!(reader.bits(8).is_err());
+    }
+}
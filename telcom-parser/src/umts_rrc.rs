@@ -0,0 +1,459 @@
+//! UMTS RRC messages, as described in 3GPP TS 25.331.
+//!
+//! The full 25.331 ASN.1 module is enormous, and we're only interested in a
+//! handful of its messages for IMSI catcher detection. Rather than generating
+//! the whole thing, we decode the outer layers of each logical channel's
+//! message by hand: the integrity check info, the message type, and for a few
+//! messages (paging, RRC connection requests) the UE identities they carry.
+
+use serde::Serialize;
+
+use crate::ParsingError;
+use crate::per::PerReader;
+
+/// Integrity check info (TS 25.331 10.3.3.16), present on integrity
+/// protected DCCH and CCCH messages.
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct IntegrityCheckInfo {
+    pub message_authentication_code: u32,
+    pub rrc_message_sequence_number: u8,
+}
+
+impl IntegrityCheckInfo {
+    fn decode_optional(
+        reader: &mut PerReader,
+        present: bool,
+    ) -> Result<Option<Self>, ParsingError> {
+        if !present {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            message_authentication_code: reader.bits(32)? as u32,
+            rrc_message_sequence_number: reader.constrained(0, 15)? as u8,
+        }))
+    }
+}
+
+// Declares a message type CHOICE whose alternatives we don't decode any
+// further, in the order they appear in the ASN.1 definition.
+macro_rules! message_types {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, PartialEq, Eq, Serialize, Debug)]
+        pub enum $name {
+            $($variant),+
+        }
+
+        impl $name {
+            const VARIANTS: &[$name] = &[$($name::$variant),+];
+
+            fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+                Ok(Self::VARIANTS[reader.choice(Self::VARIANTS.len() as u64)?])
+            }
+        }
+    };
+}
+
+message_types! {
+    /// DL-DCCH-MessageType
+    DlDcchMessageType {
+        ActiveSetUpdate,
+        AssistanceDataDelivery,
+        CellChangeOrderFromUtran,
+        CellUpdateConfirm,
+        CounterCheck,
+        DownlinkDirectTransfer,
+        HandoverFromUtranCommandGsm,
+        HandoverFromUtranCommandCdma2000,
+        MeasurementControl,
+        PagingType2,
+        PhysicalChannelReconfiguration,
+        PhysicalSharedChannelAllocation,
+        RadioBearerReconfiguration,
+        RadioBearerRelease,
+        RadioBearerSetup,
+        RrcConnectionRelease,
+        SecurityModeCommand,
+        SignallingConnectionRelease,
+        TransportChannelReconfiguration,
+        TransportFormatCombinationControl,
+        UeCapabilityEnquiry,
+        UeCapabilityInformationConfirm,
+        UplinkPhysicalChannelControl,
+        UraUpdateConfirm,
+        UtranMobilityInformation,
+        HandoverFromUtranCommandGeranIu,
+        MbmsModifiedServicesInformation,
+        EtwsPrimaryNotificationWithSecurity,
+        HandoverFromUtranCommandEutra,
+        UeInformationRequest,
+        LoggingMeasurementConfiguration,
+        Spare1,
+    }
+}
+
+message_types! {
+    /// UL-DCCH-MessageType
+    UlDcchMessageType {
+        ActiveSetUpdateComplete,
+        ActiveSetUpdateFailure,
+        CellChangeOrderFromUtranFailure,
+        CounterCheckResponse,
+        HandoverToUtranComplete,
+        InitialDirectTransfer,
+        HandoverFromUtranFailure,
+        MeasurementControlFailure,
+        MeasurementReport,
+        PhysicalChannelReconfigurationComplete,
+        PhysicalChannelReconfigurationFailure,
+        RadioBearerReconfigurationComplete,
+        RadioBearerReconfigurationFailure,
+        RadioBearerReleaseComplete,
+        RadioBearerReleaseFailure,
+        RadioBearerSetupComplete,
+        RadioBearerSetupFailure,
+        RrcConnectionReleaseComplete,
+        RrcConnectionSetupComplete,
+        RrcStatus,
+        SecurityModeComplete,
+        SecurityModeFailure,
+        SignallingConnectionReleaseIndication,
+        TransportChannelReconfigurationComplete,
+        TransportChannelReconfigurationFailure,
+        TransportFormatCombinationControlFailure,
+        UeCapabilityInformation,
+        UplinkDirectTransfer,
+        UtranMobilityInformationConfirm,
+        UtranMobilityInformationFailure,
+        MbmsModificationRequest,
+        MessageExtension,
+    }
+}
+
+message_types! {
+    /// DL-CCCH-MessageType
+    DlCcchMessageType {
+        CellUpdateConfirm,
+        RrcConnectionReject,
+        RrcConnectionRelease,
+        RrcConnectionSetup,
+        UraUpdateConfirm,
+        EtwsPrimaryNotificationWithSecurity,
+        Spare2,
+        Spare1,
+    }
+}
+
+message_types! {
+    /// The payload type of a SystemInformation-BCH message, which describes
+    /// how the system information blocks it carries were segmented
+    SystemInformationBchPayload {
+        NoSegment,
+        FirstSegment,
+        SubsequentSegment,
+        LastSegmentShort,
+        LastAndFirst,
+        LastAndComplete,
+        LastAndCompleteAndFirst,
+        CompleteSibList,
+        CompleteAndFirst,
+        CompleteSib,
+        LastSegment,
+        Spare5,
+        Spare4,
+        Spare3,
+        Spare2,
+        Spare1,
+    }
+}
+
+message_types! {
+    /// BCCH-FACH-MessageType
+    BcchFachMessageType {
+        SystemInformation,
+        SystemInformationChangeIndication,
+        Spare2,
+        Spare1,
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct DlDcchMessage {
+    pub integrity_check_info: Option<IntegrityCheckInfo>,
+    pub message: DlDcchMessageType,
+}
+
+impl DlDcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        let [integrity_check_info_present] = reader.presence_bits()?;
+        Ok(Self {
+            integrity_check_info: IntegrityCheckInfo::decode_optional(
+                &mut reader,
+                integrity_check_info_present,
+            )?,
+            message: DlDcchMessageType::decode(&mut reader)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct UlDcchMessage {
+    pub integrity_check_info: Option<IntegrityCheckInfo>,
+    pub message: UlDcchMessageType,
+}
+
+impl UlDcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        let [integrity_check_info_present] = reader.presence_bits()?;
+        Ok(Self {
+            integrity_check_info: IntegrityCheckInfo::decode_optional(
+                &mut reader,
+                integrity_check_info_present,
+            )?,
+            message: UlDcchMessageType::decode(&mut reader)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct DlCcchMessage {
+    pub integrity_check_info: Option<IntegrityCheckInfo>,
+    pub message: DlCcchMessageType,
+}
+
+impl DlCcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        let [integrity_check_info_present] = reader.presence_bits()?;
+        Ok(Self {
+            integrity_check_info: IntegrityCheckInfo::decode_optional(
+                &mut reader,
+                integrity_check_info_present,
+            )?,
+            message: DlCcchMessageType::decode(&mut reader)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct UlCcchMessage {
+    pub integrity_check_info: Option<IntegrityCheckInfo>,
+    pub message: UlCcchMessageType,
+}
+
+impl UlCcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        let [integrity_check_info_present] = reader.presence_bits()?;
+        let integrity_check_info =
+            IntegrityCheckInfo::decode_optional(&mut reader, integrity_check_info_present)?;
+        let message = match reader.choice(4)? {
+            0 => UlCcchMessageType::CellUpdate,
+            1 => {
+                UlCcchMessageType::RrcConnectionRequest(RrcConnectionRequest::decode(&mut reader)?)
+            }
+            2 => UlCcchMessageType::UraUpdate,
+            _ => UlCcchMessageType::Spare,
+        };
+        Ok(Self {
+            integrity_check_info,
+            message,
+        })
+    }
+}
+
+/// UL-CCCH-MessageType
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum UlCcchMessageType {
+    CellUpdate,
+    RrcConnectionRequest(RrcConnectionRequest),
+    UraUpdate,
+    Spare,
+}
+
+/// RRC Connection Request (TS 25.331 10.2.39). Only the initial UE identity
+/// is decoded, since that's what tells us whether the UE identified itself
+/// with a permanent identity.
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct RrcConnectionRequest {
+    pub initial_ue_identity: InitialUeIdentity,
+}
+
+impl RrcConnectionRequest {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        // measuredResultsOnRACH and v3d0NonCriticalExtensions
+        let [_, _] = reader.presence_bits()?;
+        Ok(Self {
+            initial_ue_identity: InitialUeIdentity::decode(reader)?,
+        })
+    }
+}
+
+/// Initial UE identity (TS 25.331 10.3.3.15)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum InitialUeIdentity {
+    Imsi(String),
+    TmsiAndLai(u32),
+    PTmsiAndRai(u32),
+    Imei(String),
+    EsnDs41,
+    ImsiDs41,
+    ImsiAndEsnDs41,
+    TmsiDs41,
+}
+
+impl InitialUeIdentity {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        Ok(match reader.choice(8)? {
+            0 => Self::Imsi(reader.digits(6, 21)?),
+            1 => Self::TmsiAndLai(reader.bits(32)? as u32),
+            2 => Self::PTmsiAndRai(reader.bits(32)? as u32),
+            3 => Self::Imei(reader.fixed_digits(15)?),
+            4 => Self::EsnDs41,
+            5 => Self::ImsiDs41,
+            6 => Self::ImsiAndEsnDs41,
+            _ => Self::TmsiDs41,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct PcchMessage {
+    pub message: PcchMessageType,
+}
+
+impl PcchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        let message = match reader.choice(2)? {
+            0 => PcchMessageType::PagingType1(PagingType1::decode(&mut reader)?),
+            _ => PcchMessageType::Spare,
+        };
+        Ok(Self { message })
+    }
+}
+
+/// PCCH-MessageType
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum PcchMessageType {
+    PagingType1(PagingType1),
+    Spare,
+}
+
+/// Paging Type 1 (TS 25.331 10.2.20)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct PagingType1 {
+    pub paging_records: Vec<PagingRecord>,
+}
+
+impl PagingType1 {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        // pagingRecordList, bcch-ModificationInfo and laterNonCriticalExtensions
+        let [paging_record_list_present, _, _] = reader.presence_bits()?;
+        let mut paging_records = Vec::new();
+        if paging_record_list_present {
+            let count = reader.constrained(1, 8)?;
+            for _ in 0..count {
+                let record = PagingRecord::decode(reader)?;
+                let is_utran_identity = matches!(record, PagingRecord::UtranIdentity);
+                paging_records.push(record);
+                // we don't decode UTRAN identities, so we can't tell where
+                // the next record starts
+                if is_utran_identity {
+                    break;
+                }
+            }
+        }
+        Ok(Self { paging_records })
+    }
+}
+
+/// Paging record (TS 25.331 10.3.3.23)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum PagingRecord {
+    CnIdentity {
+        paging_cause: u8,
+        cn_domain: CnDomainIdentity,
+        identity: CnPagedUeIdentity,
+    },
+    /// A UE in connected mode being paged by its U-RNTI
+    UtranIdentity,
+}
+
+impl PagingRecord {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        if reader.choice(2)? == 1 {
+            return Ok(Self::UtranIdentity);
+        }
+        Ok(Self::CnIdentity {
+            paging_cause: reader.choice(8)? as u8,
+            cn_domain: match reader.choice(2)? {
+                0 => CnDomainIdentity::Cs,
+                _ => CnDomainIdentity::Ps,
+            },
+            identity: CnPagedUeIdentity::decode(reader)?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Debug)]
+pub enum CnDomainIdentity {
+    Cs,
+    Ps,
+}
+
+/// CN paged UE identity (TS 25.331 10.3.1.5)
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum CnPagedUeIdentity {
+    Imsi(String),
+    Tmsi(u32),
+    PTmsi(u32),
+    ImsiDs41(Vec<u8>),
+    TmsiDs41(Vec<u8>),
+    Spare,
+}
+
+impl CnPagedUeIdentity {
+    fn decode(reader: &mut PerReader) -> Result<Self, ParsingError> {
+        Ok(match reader.choice(8)? {
+            0 => Self::Imsi(reader.digits(6, 21)?),
+            1 => Self::Tmsi(reader.bits(32)? as u32),
+            2 => Self::PTmsi(reader.bits(32)? as u32),
+            3 => Self::ImsiDs41(reader.octets(5, 7)?),
+            4 => Self::TmsiDs41(reader.octets(2, 17)?),
+            _ => Self::Spare,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct BcchBchMessage {
+    pub sfn_prime: u16,
+    pub payload: SystemInformationBchPayload,
+}
+
+impl BcchBchMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        Ok(Self {
+            sfn_prime: reader.bits(11)? as u16,
+            payload: SystemInformationBchPayload::decode(&mut reader)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct BcchFachMessage {
+    pub message: BcchFachMessageType,
+}
+
+impl BcchFachMessage {
+    pub fn decode(data: &[u8]) -> Result<Self, ParsingError> {
+        let mut reader = PerReader::new(data);
+        Ok(Self {
+            message: BcchFachMessageType::decode(&mut reader)?,
+        })
+    }
+}
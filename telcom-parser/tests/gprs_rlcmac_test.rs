@@ -0,0 +1,75 @@
+use telcom_parser::ParsingError;
+use telcom_parser::gprs_rlcmac::{self, GsmTargetCell, PacketAddress, RlcMacControlMessage};
+
+fn hex_to_bin(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_packet_cell_change_order() {
+    let RlcMacControlMessage::PacketCellChangeOrder(pcco) =
+        gprs_rlcmac::decode(&hex_to_bin("400446829500"), false).unwrap()
+    else {
+        panic!("expected packet cell change order");
+    };
+    assert_eq!(pcco.address, PacketAddress::DownlinkTfi(3));
+    assert_eq!(
+        pcco.target_cell,
+        Some(GsmTargetCell {
+            immediate_release: true,
+            arfcn: 20,
+            bsic: 0x2a,
+        })
+    );
+
+    // control block with optional octets, ordering the MS to a 3G/LTE cell
+    let RlcMacControlMessage::PacketCellChangeOrder(pcco) =
+        gprs_rlcmac::decode(&hex_to_bin("80000447"), false).unwrap()
+    else {
+        panic!("expected packet cell change order");
+    };
+    assert_eq!(pcco.address, PacketAddress::DownlinkTfi(3));
+    assert_eq!(pcco.target_cell, None);
+}
+
+#[test]
+fn test_packet_downlink_assignment() {
+    let RlcMacControlMessage::PacketDownlinkAssignment(pda) =
+        gprs_rlcmac::decode(&hex_to_bin("4008580002468218"), false).unwrap()
+    else {
+        panic!("expected packet downlink assignment");
+    };
+    assert_eq!(pda.address, PacketAddress::Tlli(0xc0001234));
+    let assignment = pda.assignment.unwrap();
+    assert!(assignment.rlc_unacknowledged);
+    assert!(!assignment.control_ack);
+    assert_eq!(assignment.timeslot_allocation, 0x18);
+}
+
+#[test]
+fn test_packet_uplink_assignment() {
+    let RlcMacControlMessage::PacketUplinkAssignment(pua) =
+        gprs_rlcmac::decode(&hex_to_bin("4028612342"), false).unwrap()
+    else {
+        panic!("expected packet uplink assignment");
+    };
+    assert_eq!(pua.address, PacketAddress::Tqi(0x1234));
+    assert_eq!(pua.channel_coding_command, Some(2));
+}
+
+#[test]
+fn test_other_messages() {
+    // packet control acknowledgement
+    assert_eq!(
+        gprs_rlcmac::decode(&hex_to_bin("4004"), true).unwrap(),
+        RlcMacControlMessage::OtherUplink(0x01)
+    );
+    // RLC data blocks aren't expected on the PACCH
+    assert!(matches!(
+        gprs_rlcmac::decode(&hex_to_bin("0000"), false),
+        Err(ParsingError::InvalidEncoding(_))
+    ));
+}
@@ -0,0 +1,119 @@
+use telcom_parser::ParsingError;
+use telcom_parser::gsm_nas::{
+    self, GsmNasMessage, MobileIdentity, MobileIdentityType, ProtocolDiscriminator,
+};
+
+fn hex_to_bin(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_identity_requests() {
+    let GsmNasMessage::MmIdentityRequest(req) = gsm_nas::decode(&hex_to_bin("055801")).unwrap()
+    else {
+        panic!("expected MM identity request");
+    };
+    assert_eq!(req.identity_type, MobileIdentityType::Imsi);
+
+    // force to standby is set in the upper half octet
+    let GsmNasMessage::GmmIdentityRequest(req) = gsm_nas::decode(&hex_to_bin("081512")).unwrap()
+    else {
+        panic!("expected GMM identity request");
+    };
+    assert_eq!(req.identity_type, MobileIdentityType::Imei);
+}
+
+#[test]
+fn test_location_updating_reject() {
+    let GsmNasMessage::LocationUpdatingReject(reject) =
+        gsm_nas::decode(&hex_to_bin("05040b")).unwrap()
+    else {
+        panic!("expected location updating reject");
+    };
+    assert_eq!(reject.reject_cause, 11);
+}
+
+#[test]
+fn test_authentication_request() {
+    let rand = "00112233445566778899aabbccddeeff";
+    let autn = "ffeeddccbbaa99887766554433221100";
+    let GsmNasMessage::AuthenticationRequest(gsm_auth) =
+        gsm_nas::decode(&hex_to_bin(&format!("051202{rand}"))).unwrap()
+    else {
+        panic!("expected authentication request");
+    };
+    assert_eq!(gsm_auth.ciphering_key_sequence_number, 2);
+    assert_eq!(gsm_auth.rand.to_vec(), hex_to_bin(rand));
+    assert!(gsm_auth.autn.is_none());
+
+    let GsmNasMessage::AuthenticationRequest(umts_auth) =
+        gsm_nas::decode(&hex_to_bin(&format!("051202{rand}2010{autn}"))).unwrap()
+    else {
+        panic!("expected authentication request");
+    };
+    assert_eq!(umts_auth.autn, Some(hex_to_bin(autn)));
+
+    assert_eq!(
+        gsm_nas::decode(&hex_to_bin("0511")).unwrap(),
+        GsmNasMessage::AuthenticationReject
+    );
+}
+
+#[test]
+fn test_cm_service_messages() {
+    let GsmNasMessage::CmServiceRequest(req) =
+        gsm_nas::decode(&hex_to_bin("052411035758a605f412345678")).unwrap()
+    else {
+        panic!("expected CM service request");
+    };
+    assert_eq!(req.service_type, 1);
+    assert_eq!(req.ciphering_key_sequence_number, 1);
+    assert_eq!(req.mobile_identity, MobileIdentity::Tmsi(0x12345678));
+
+    let GsmNasMessage::CmServiceRequest(req) =
+        gsm_nas::decode(&hex_to_bin("052404035758a6080910101032547698")).unwrap()
+    else {
+        panic!("expected CM service request");
+    };
+    assert_eq!(req.service_type, 4);
+    assert_eq!(
+        req.mobile_identity,
+        MobileIdentity::Imsi("001010123456789".to_string())
+    );
+
+    assert_eq!(
+        gsm_nas::decode(&hex_to_bin("0521")).unwrap(),
+        GsmNasMessage::CmServiceAccept
+    );
+    let GsmNasMessage::CmServiceReject(reject) = gsm_nas::decode(&hex_to_bin("052206")).unwrap()
+    else {
+        panic!("expected CM service reject");
+    };
+    assert_eq!(reject.reject_cause, 6);
+}
+
+#[test]
+fn test_other_messages() {
+    // CC setup, with a transaction identifier
+    let GsmNasMessage::Other(header) = gsm_nas::decode(&hex_to_bin("83450401a0")).unwrap() else {
+        panic!("expected undecoded message");
+    };
+    assert_eq!(
+        header.protocol_discriminator,
+        ProtocolDiscriminator::CallControl
+    );
+    assert_eq!(header.transaction_identifier, 8);
+    assert_eq!(header.message_type, 0x05);
+
+    assert!(matches!(
+        gsm_nas::decode(&hex_to_bin("0f01")),
+        Err(ParsingError::UnsupportedMessageType(0x0f, 0x01))
+    ));
+    assert!(matches!(
+        gsm_nas::decode(&hex_to_bin("0512")),
+        Err(ParsingError::TooShort(..))
+    ));
+}
@@ -0,0 +1,114 @@
+use telcom_parser::gsm_nas::{GsmNasMessage, MobileIdentityType};
+use telcom_parser::gsm_rr::{self, FrequencyList, GsmCipherAlgorithm, GsmL3Message};
+
+fn hex_to_bin(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_system_information_3() {
+    let data = hex_to_bin("061b000100f1100001490305274740e504002c0b2b2b");
+    let GsmL3Message::SystemInformationType3(si3) = gsm_rr::decode(&data).unwrap() else {
+        panic!("expected SI3");
+    };
+    assert_eq!(si3.cell_identity, 1);
+    assert_eq!(si3.lai.plmn.mcc, 1);
+    assert_eq!(si3.lai.plmn.mnc, 1);
+    assert!(!si3.lai.plmn.three_digit_mnc);
+    assert_eq!(si3.lai.plmn.to_string(), "001-01");
+    assert_eq!(si3.lai.lac, 1);
+    assert!(si3.control_channel_description.att);
+    assert_eq!(si3.control_channel_description.bs_ag_blks_res, 1);
+    assert_eq!(si3.control_channel_description.ccch_conf, 1);
+    assert_eq!(si3.control_channel_description.bs_pa_mfrms, 3);
+    assert_eq!(si3.control_channel_description.t3212, 5);
+    assert_eq!(si3.cell_selection.cell_reselect_hysteresis, 2);
+    assert_eq!(si3.cell_selection.ms_txpwr_max_cch, 7);
+    assert!(si3.cell_selection.neci);
+    assert_eq!(si3.rach_control.max_retrans, 3);
+    assert_eq!(si3.rach_control.tx_integer, 9);
+    assert!(!si3.rach_control.cell_barred);
+    assert!(!si3.rach_control.call_reestablishment_allowed);
+    assert_eq!(si3.rach_control.access_control_classes, 0x0400);
+    assert_eq!(si3.rest_octets, vec![0x2c, 0x0b, 0x2b, 0x2b]);
+}
+
+#[test]
+fn test_other_message() {
+    // paging request type 1 for a TMSI, which we don't decode
+    let data = hex_to_bin("0621000105f4deadbeef2b2b2b");
+    assert_eq!(gsm_rr::decode(&data).unwrap(), GsmL3Message::Other(0x21));
+}
+
+#[test]
+fn test_truncated_message() {
+    let data = hex_to_bin("061b000100f1");
+    assert!(gsm_rr::decode(&data).is_err());
+}
+
+#[test]
+fn test_frequency_list_bitmap_0() {
+    let mut list = [0; 16];
+    list[0] = 0x08;
+    list[15] = 0x01;
+    assert_eq!(FrequencyList(list).bitmap_0_arfcns(), Some(vec![1, 124]));
+    list[0] = 0x80;
+    assert_eq!(FrequencyList(list).bitmap_0_arfcns(), None);
+}
+
+#[test]
+fn test_immediate_assignment() {
+    let data = hex_to_bin("063f0048e03f123456010000");
+    let GsmL3Message::ImmediateAssignment(ia) = gsm_rr::decode(&data).unwrap() else {
+        panic!("expected Immediate Assignment");
+    };
+    assert!(!ia.tbf);
+    assert_eq!(ia.channel_description.channel_type_and_tdma_offset, 9);
+    assert_eq!(ia.channel_description.timeslot, 0);
+    assert_eq!(ia.channel_description.training_sequence_code, 7);
+    assert_eq!(ia.channel_description.hopping, None);
+    assert_eq!(ia.channel_description.arfcn, Some(63));
+    assert_eq!(ia.request_reference.ra, 0x12);
+    assert_eq!(ia.request_reference.t1_prime, 6);
+    assert_eq!(ia.request_reference.t3, 34);
+    assert_eq!(ia.request_reference.t2, 22);
+    assert_eq!(ia.timing_advance, 1);
+    assert!(ia.mobile_allocation.is_empty());
+    assert_eq!(ia.rest_octets, vec![0x00]);
+}
+
+#[test]
+fn test_ciphering_mode_command() {
+    let GsmL3Message::CipheringModeCommand(cmd) = gsm_rr::decode(&[0x06, 0x35, 0x11]).unwrap()
+    else {
+        panic!("expected Ciphering Mode Command");
+    };
+    assert_eq!(cmd.algorithm, Some(GsmCipherAlgorithm::A5_1));
+    assert!(cmd.imeisv_requested);
+
+    let GsmL3Message::CipheringModeCommand(cmd) = gsm_rr::decode(&[0x06, 0x35, 0x00]).unwrap()
+    else {
+        panic!("expected Ciphering Mode Command");
+    };
+    assert_eq!(cmd.algorithm, None);
+    assert!(!cmd.imeisv_requested);
+}
+
+#[test]
+fn test_identity_request() {
+    // the second message has its send sequence number set
+    for (data, identity_type) in [
+        ([0x05, 0x18, 0x01], MobileIdentityType::Imsi),
+        ([0x05, 0x58, 0x02], MobileIdentityType::Imei),
+    ] {
+        let GsmL3Message::Nas(GsmNasMessage::MmIdentityRequest(req)) =
+            gsm_rr::decode(&data).unwrap()
+        else {
+            panic!("expected Identity Request");
+        };
+        assert_eq!(req.identity_type, identity_type);
+    }
+}
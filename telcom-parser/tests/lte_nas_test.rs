@@ -0,0 +1,280 @@
+use telcom_parser::gsm_nas::MobileIdentity;
+use telcom_parser::gsm_rr::Plmn;
+use telcom_parser::lte_nas::{
+    self, AttachAccept, AttachRequest, CipheringAlgorithm, EMM_CAUSE_SYNCH_FAILURE, EmmCause,
+    EmmMessage, EpsMobileIdentity, Guti, GutiReallocationCommand, IdentityRequest,
+    IdentityResponse, IdentityType, IntegrityAlgorithm, SecurityHeader, SecurityHeaderType,
+    SecurityModeComplete, TrackingAreaUpdateAccept,
+};
+
+#[test]
+fn test_integrity_protected_message() {
+    // integrity protected identity request
+    let data = [0x17, 0xde, 0xad, 0xbe, 0xef, 0x05, 0x07, 0x55, 0x01];
+    let (header, inner) = SecurityHeader::decode(&data).unwrap().unwrap();
+    assert_eq!(
+        header.security_header_type,
+        SecurityHeaderType::IntegrityProtected
+    );
+    assert!(!header.security_header_type.is_ciphered());
+    assert_eq!(header.message_authentication_code, 0xdeadbeef);
+    assert_eq!(header.sequence_number, 5);
+    assert_eq!(inner, &[0x07, 0x55, 0x01]);
+}
+
+#[test]
+fn test_plain_messages() {
+    // plain identity request
+    assert!(
+        SecurityHeader::decode(&[0x07, 0x55, 0x01])
+            .unwrap()
+            .is_none()
+    );
+    // service request
+    assert!(
+        SecurityHeader::decode(&[0xc7, 0x01, 0x02, 0x03])
+            .unwrap()
+            .is_none()
+    );
+    // ESM message
+    assert!(
+        SecurityHeader::decode(&[0x52, 0x01, 0xc1])
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn test_truncated_header() {
+    assert!(SecurityHeader::decode(&[0x27, 0x01, 0x02]).is_err());
+}
+
+#[test]
+fn test_security_mode_command() {
+    // EEA2/EIA2, KSI 0, UE security capabilities
+    let data = [0x07, 0x5d, 0x22, 0x00, 0x02, 0xe0, 0xe0];
+    let Some(EmmMessage::SecurityModeCommand(smc)) = lte_nas::decode(&data).unwrap() else {
+        panic!("expected a security mode command");
+    };
+    assert_eq!(smc.selected_algorithms.ciphering, CipheringAlgorithm::Eea2);
+    assert_eq!(smc.selected_algorithms.integrity, IntegrityAlgorithm::Eia2);
+
+    // null ciphering and integrity
+    let data = [0x07, 0x5d, 0x00, 0x00, 0x02, 0xe0, 0xe0];
+    let Some(EmmMessage::SecurityModeCommand(smc)) = lte_nas::decode(&data).unwrap() else {
+        panic!("expected a security mode command");
+    };
+    assert_eq!(smc.selected_algorithms.ciphering, CipheringAlgorithm::Eea0);
+    assert_eq!(smc.selected_algorithms.integrity, IntegrityAlgorithm::Eia0);
+}
+
+#[test]
+fn test_non_emm_messages() {
+    // ESM message
+    assert_eq!(lte_nas::decode(&[0x52, 0x01, 0xc1]).unwrap(), None);
+    // still security protected
+    assert_eq!(
+        lte_nas::decode(&[0x37, 0xde, 0xad, 0xbe, 0xef, 0x00, 0x07, 0x5d]).unwrap(),
+        None
+    );
+    // EMM status
+    assert_eq!(
+        lte_nas::decode(&[0x07, 0x60, 0x6f]).unwrap(),
+        Some(EmmMessage::Other(0x60))
+    );
+}
+
+#[test]
+fn test_authentication_messages() {
+    // authentication response, with its RES
+    assert_eq!(
+        lte_nas::decode(&[0x07, 0x53, 0x08, 1, 2, 3, 4, 5, 6, 7, 8]).unwrap(),
+        Some(EmmMessage::AuthenticationResponse)
+    );
+    assert_eq!(
+        lte_nas::decode(&[0x07, 0x54]).unwrap(),
+        Some(EmmMessage::AuthenticationReject)
+    );
+    // synch failure, with its AUTS
+    let mut data = vec![0x07, 0x5c, 0x15, 0x30, 0x0e];
+    data.extend([0; 14]);
+    assert_eq!(
+        lte_nas::decode(&data).unwrap(),
+        Some(EmmMessage::AuthenticationFailure(EmmCause {
+            emm_cause: EMM_CAUSE_SYNCH_FAILURE
+        }))
+    );
+    assert!(lte_nas::decode(&[0x07, 0x5c]).is_err());
+}
+
+#[test]
+fn test_service_request() {
+    assert_eq!(
+        lte_nas::decode(&[0xc7, 0x01, 0x02, 0x03]).unwrap(),
+        Some(EmmMessage::ServiceRequest)
+    );
+}
+
+#[test]
+fn test_reject_messages() {
+    // attach reject, cause #7 with an ESM message container
+    assert_eq!(
+        lte_nas::decode(&[0x07, 0x44, 0x07, 0x78, 0x00, 0x03, 0x52, 0x01, 0xc1]).unwrap(),
+        Some(EmmMessage::AttachReject(EmmCause { emm_cause: 7 }))
+    );
+    assert_eq!(
+        lte_nas::decode(&[0x07, 0x4b, 0x0c]).unwrap(),
+        Some(EmmMessage::TrackingAreaUpdateReject(EmmCause {
+            emm_cause: 12
+        }))
+    );
+    // service reject, cause #22 with a T3442 value
+    assert_eq!(
+        lte_nas::decode(&[0x07, 0x4e, 0x16, 0x5b, 0x21]).unwrap(),
+        Some(EmmMessage::ServiceReject(EmmCause { emm_cause: 22 }))
+    );
+    assert!(lte_nas::decode(&[0x07, 0x44]).is_err());
+}
+
+#[test]
+fn test_identity_request() {
+    let expected = [
+        (0x01, IdentityType::Imsi),
+        (0x02, IdentityType::Imei),
+        (0x03, IdentityType::Imeisv),
+        (0x04, IdentityType::Tmsi),
+        // the spare half octet is ignored
+        (0xf2, IdentityType::Imei),
+    ];
+    for (octet, identity_type) in expected {
+        assert_eq!(
+            lte_nas::decode(&[0x07, 0x55, octet]).unwrap(),
+            Some(EmmMessage::IdentityRequest(IdentityRequest {
+                identity_type
+            }))
+        );
+    }
+}
+
+#[test]
+fn test_security_mode_complete() {
+    assert_eq!(
+        lte_nas::decode(&[0x07, 0x5e]).unwrap(),
+        Some(EmmMessage::SecurityModeComplete(SecurityModeComplete {
+            includes_imeisv: false
+        }))
+    );
+    // with an IMEISV
+    let data = [
+        0x07, 0x5e, 0x23, 0x09, 0x33, 0x55, 0x44, 0x66, 0x77, 0x88, 0x99, 0x00, 0xf1,
+    ];
+    assert_eq!(
+        lte_nas::decode(&data).unwrap(),
+        Some(EmmMessage::SecurityModeComplete(SecurityModeComplete {
+            includes_imeisv: true
+        }))
+    );
+}
+
+const GUTI: [u8; 11] = [
+    0xf6, 0x00, 0xf1, 0x10, 0x80, 0x01, 0x05, 0x12, 0x34, 0x56, 0x78,
+];
+// IMSI 001010123456789
+const IMSI: [u8; 8] = [0x09, 0x10, 0x10, 0x10, 0x32, 0x54, 0x76, 0x98];
+
+fn expected_guti() -> Guti {
+    Guti {
+        plmn: Plmn {
+            mcc: 1,
+            mnc: 1,
+            three_digit_mnc: false,
+        },
+        mme_group_id: 0x8001,
+        mme_code: 0x05,
+        m_tmsi: 0x12345678,
+    }
+}
+
+#[test]
+fn test_attach_request() {
+    let mut data = vec![0x07, 0x41, 0x71, 0x08];
+    data.extend(IMSI);
+    // UE network capability
+    data.extend([0x02, 0xe0, 0xe0]);
+    assert_eq!(
+        lte_nas::decode(&data).unwrap(),
+        Some(EmmMessage::AttachRequest(AttachRequest {
+            identity: EpsMobileIdentity::Imsi("001010123456789".to_string()),
+        }))
+    );
+
+    let mut data = vec![0x07, 0x41, 0x71, 0x0b];
+    data.extend(GUTI);
+    assert_eq!(
+        lte_nas::decode(&data).unwrap(),
+        Some(EmmMessage::AttachRequest(AttachRequest {
+            identity: EpsMobileIdentity::Guti(expected_guti()),
+        }))
+    );
+}
+
+#[test]
+fn test_guti_allocation() {
+    // attach accept with a TAI list, an ESM message container and a GUTI
+    let mut data = vec![
+        0x07, 0x42, 0x02, 0x5e, 0x06, 0x00, 0x00, 0xf1, 0x10, 0x00, 0x01, 0x00, 0x03, 0x52, 0x01,
+        0xc2, 0x50, 0x0b,
+    ];
+    data.extend(GUTI);
+    assert_eq!(
+        lte_nas::decode(&data).unwrap(),
+        Some(EmmMessage::AttachAccept(AttachAccept {
+            guti: Some(expected_guti()),
+        }))
+    );
+
+    // tracking area update accept with a T3412 value before the GUTI
+    let mut data = vec![0x07, 0x49, 0x00, 0x5a, 0x21, 0x50, 0x0b];
+    data.extend(GUTI);
+    assert_eq!(
+        lte_nas::decode(&data).unwrap(),
+        Some(EmmMessage::TrackingAreaUpdateAccept(
+            TrackingAreaUpdateAccept {
+                guti: Some(expected_guti()),
+            }
+        ))
+    );
+    // and one that keeps the old GUTI
+    assert_eq!(
+        lte_nas::decode(&[0x07, 0x49, 0x00, 0x57, 0x02, 0x20, 0x00]).unwrap(),
+        Some(EmmMessage::TrackingAreaUpdateAccept(
+            TrackingAreaUpdateAccept { guti: None }
+        ))
+    );
+
+    let mut data = vec![0x07, 0x50, 0x0b];
+    data.extend(GUTI);
+    assert_eq!(
+        lte_nas::decode(&data).unwrap(),
+        Some(EmmMessage::GutiReallocationCommand(
+            GutiReallocationCommand {
+                guti: expected_guti(),
+            }
+        ))
+    );
+    // the same, but with the identity type changed to IMSI
+    data[3] = 0xf1;
+    assert!(lte_nas::decode(&data).is_err());
+}
+
+#[test]
+fn test_identity_response() {
+    let mut data = vec![0x07, 0x56, 0x08];
+    data.extend(IMSI);
+    assert_eq!(
+        lte_nas::decode(&data).unwrap(),
+        Some(EmmMessage::IdentityResponse(IdentityResponse {
+            mobile_identity: MobileIdentity::Imsi("001010123456789".to_string()),
+        }))
+    );
+}
@@ -0,0 +1,80 @@
+use telcom_parser::nb_iot_rrc::{
+    BcchBchMessage, BcchDlSchMessage, BcchDlSchMessageType, CipheringAlgorithm, DlDcchMessage,
+    DlDcchMessageType, IntegrityProtAlgorithm, OperationMode, PagingUeIdentity, PcchMessage,
+    PcchMessageType, UlDcchMessage, UlDcchMessageType,
+};
+
+fn hex_to_bin(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_mib() {
+    let mib = BcchBchMessage::decode(&hex_to_bin("a547c000"))
+        .unwrap()
+        .message;
+    assert_eq!(mib.system_frame_number_msb, 0b1010);
+    assert_eq!(mib.hyper_sfn_lsb, 1);
+    assert_eq!(mib.scheduling_info_sib1, 5);
+    assert_eq!(mib.system_info_value_tag, 3);
+    assert!(mib.ab_enabled);
+    assert_eq!(mib.operation_mode, OperationMode::Standalone);
+}
+
+#[test]
+fn test_paging() {
+    let PcchMessageType::Paging(paging) =
+        PcchMessage::decode(&hex_to_bin("40812deadbeef320020202468acf12"))
+            .unwrap()
+            .message
+    else {
+        panic!("expected paging");
+    };
+    assert!(!paging.system_info_modification);
+    assert_eq!(
+        paging.paging_records,
+        vec![
+            PagingUeIdentity::STmsi {
+                mmec: 0x12,
+                m_tmsi: 0xdeadbeef,
+            },
+            PagingUeIdentity::Imsi("001010123456789".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_security_mode_command() {
+    let DlDcchMessageType::SecurityModeCommand(smc) = DlDcchMessage::decode(&hex_to_bin("340040"))
+        .unwrap()
+        .message
+    else {
+        panic!("expected security mode command");
+    };
+    assert_eq!(smc.rrc_transaction_identifier, 1);
+    let config = smc.security_algorithm_config.unwrap();
+    assert_eq!(config.ciphering_algorithm, CipheringAlgorithm::Eea0);
+    assert_eq!(
+        config.integrity_prot_algorithm,
+        IntegrityProtAlgorithm::Eia2
+    );
+}
+
+#[test]
+fn test_message_types() {
+    assert_eq!(
+        UlDcchMessage::decode(&hex_to_bin("18")).unwrap().message,
+        UlDcchMessageType::SecurityModeComplete
+    );
+    assert_eq!(
+        BcchDlSchMessage::decode(&hex_to_bin("40")).unwrap().message,
+        BcchDlSchMessageType::SystemInformationBlockType1
+    );
+    assert_eq!(
+        BcchDlSchMessage::decode(&hex_to_bin("80")).unwrap().message,
+        BcchDlSchMessageType::MessageClassExtension
+    );
+}
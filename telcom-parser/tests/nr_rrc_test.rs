@@ -0,0 +1,73 @@
+use telcom_parser::ParsingError;
+use telcom_parser::nr_rrc::{
+    BcchBchMessage, BcchBchMessageType, CipheringAlgorithm, DlDcchMessage, DlDcchMessageType,
+    InitialUeIdentity, IntegrityProtAlgorithm, PagingUeIdentity, PcchMessage, PcchMessageType,
+    UlCcchMessage, UlCcchMessageType,
+};
+
+fn hex_to_bin(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_mib() {
+    let msg = BcchBchMessage::decode(&hex_to_bin("546004")).unwrap();
+    let BcchBchMessageType::Mib(mib) = msg.message else {
+        panic!("expected MIB");
+    };
+    assert_eq!(mib.system_frame_number, 42);
+    assert_eq!(mib.ssb_subcarrier_offset, 6);
+    assert!(!mib.cell_barred);
+    assert!(mib.intra_freq_reselection_allowed);
+}
+
+#[test]
+fn test_security_mode_command() {
+    let msg = DlDcchMessage::decode(&hex_to_bin("220810")).unwrap();
+    let DlDcchMessageType::SecurityModeCommand(smc) = msg.message else {
+        panic!("expected security mode command");
+    };
+    assert_eq!(smc.rrc_transaction_identifier, 1);
+    let config = smc.security_algorithm_config.unwrap();
+    assert_eq!(config.ciphering_algorithm, CipheringAlgorithm::Nea0);
+    assert_eq!(
+        config.integrity_prot_algorithm,
+        Some(IntegrityProtAlgorithm::Nia2)
+    );
+}
+
+#[test]
+fn test_paging() {
+    let msg = PcchMessage::decode(&hex_to_bin("2000048d159e26ac")).unwrap();
+    let PcchMessageType::Paging(paging) = msg.message else {
+        panic!("expected paging");
+    };
+    assert_eq!(
+        paging.paging_records,
+        vec![PagingUeIdentity::Ng5gSTmsi(0x0123456789ab)]
+    );
+}
+
+#[test]
+fn test_rrc_setup_request() {
+    let msg = UlCcchMessage::decode(&hex_to_bin("0002468acf06")).unwrap();
+    let UlCcchMessageType::RrcSetupRequest(req) = msg.message else {
+        panic!("expected RRC setup request");
+    };
+    assert_eq!(
+        req.ue_identity,
+        InitialUeIdentity::Ng5gSTmsiPart1(0x12345678)
+    );
+    assert_eq!(req.establishment_cause, 3);
+}
+
+#[test]
+fn test_truncated_message() {
+    assert!(matches!(
+        PcchMessage::decode(&hex_to_bin("2000")),
+        Err(ParsingError::TooShort(..))
+    ));
+}
@@ -0,0 +1,69 @@
+use telcom_parser::ParsingError;
+use telcom_parser::umts_rrc::{
+    BcchBchMessage, CnDomainIdentity, CnPagedUeIdentity, DlDcchMessage, DlDcchMessageType,
+    InitialUeIdentity, PagingRecord, PcchMessage, PcchMessageType, SystemInformationBchPayload,
+    UlCcchMessage, UlCcchMessageType,
+};
+
+fn hex_to_bin(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_paging_type_1_imsi() {
+    let data = hex_to_bin("404120020202468acf12");
+    let msg = PcchMessage::decode(&data).unwrap();
+    let PcchMessageType::PagingType1(paging) = msg.message else {
+        panic!("expected paging type 1");
+    };
+    assert_eq!(
+        paging.paging_records,
+        vec![PagingRecord::CnIdentity {
+            paging_cause: 2,
+            cn_domain: CnDomainIdentity::Cs,
+            identity: CnPagedUeIdentity::Imsi("001010123456789".to_string()),
+        }]
+    );
+}
+
+#[test]
+fn test_rrc_connection_request_imsi() {
+    let data = hex_to_bin("209001010123456789");
+    let msg = UlCcchMessage::decode(&data).unwrap();
+    assert!(msg.integrity_check_info.is_none());
+    let UlCcchMessageType::RrcConnectionRequest(req) = msg.message else {
+        panic!("expected RRC connection request");
+    };
+    assert_eq!(
+        req.initial_ue_identity,
+        InitialUeIdentity::Imsi("001010123456789".to_string())
+    );
+}
+
+#[test]
+fn test_security_mode_command() {
+    let data = hex_to_bin("ef56df77ac00");
+    let msg = DlDcchMessage::decode(&data).unwrap();
+    let integrity_check_info = msg.integrity_check_info.unwrap();
+    assert_eq!(integrity_check_info.message_authentication_code, 0xdeadbeef);
+    assert_eq!(integrity_check_info.rrc_message_sequence_number, 5);
+    assert_eq!(msg.message, DlDcchMessageType::SecurityModeCommand);
+}
+
+#[test]
+fn test_system_information_bch() {
+    let msg = BcchBchMessage::decode(&hex_to_bin("0c8e")).unwrap();
+    assert_eq!(msg.sfn_prime, 100);
+    assert_eq!(msg.payload, SystemInformationBchPayload::CompleteSibList);
+}
+
+#[test]
+fn test_truncated_message() {
+    assert!(matches!(
+        DlDcchMessage::decode(&hex_to_bin("ef56")),
+        Err(ParsingError::TooShort(..))
+    ));
+}